@@ -16,11 +16,13 @@ use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, VecDeque};
 use std::fmt::Display;
 use std::io::Write;
-use std::{thread, time};
+use std::time;
 
-use protocol_event_writer::{ProtocolEventFileWriter, EVENT_INDICATOR_CHAR};
+use protocol_event_writer::{EventWriter, EVENT_INDICATOR_CHAR};
 
-use crate::BOOTLOADER_WRITE_MAX_SIZE;
+use crate::crc::calc_crc;
+use crate::crypto::FrameCipher;
+use crate::{BOOTLOADER_WRITE_MAX_SIZE, WORD_SIZE};
 
 type NodeId = u32;
 type RGBColor = (u8, u8, u8);
@@ -35,8 +37,70 @@ pub struct Client<'a> {
     /// buffer for storing bytes of yet to complete message
     pub buffer: VecDeque<u8>,
     pub color: RGBColor,
+    /// in-progress OTA transfer, if any; `None` once `FinishFwUpdate` has been sent
+    pub firmware_update: Option<UpdateProgress>,
+    /// bytes queued by `send_message` that the socket wasn't ready to accept yet; drained by
+    /// `flush_pending_write` once `main`'s poll loop sees this client become writable, so a slow
+    /// reader (e.g. mid firmware transfer) never blocks the whole event loop.
+    pub pending_write: VecDeque<u8>,
+    /// total bytes received from this client since it connected
+    pub bytes_in: u64,
+    /// total bytes sent to this client since it connected
+    pub bytes_out: u64,
+    /// bytes/sec received, as of the last `update_rate_window` rollover
+    pub rate_in: f64,
+    /// bytes/sec sent, as of the last `update_rate_window` rollover
+    pub rate_out: f64,
+    /// bytes received since `window_start`; internal bookkeeping for `update_rate_window`, use
+    /// `record_bytes_in` rather than touching this directly
+    pub window_bytes_in: u64,
+    /// bytes sent since `window_start`, capped at `max_bytes_out_per_window` by
+    /// `flush_pending_write`; internal bookkeeping for `update_rate_window`
+    pub window_bytes_out: u64,
+    /// start of the current rate-accounting window; internal bookkeeping for `update_rate_window`
+    pub window_start: time::Instant,
+    /// outbound rate limit in bytes per `update_rate_window` window, so a firmware push or
+    /// broadcast command can't saturate a constrained LPWAN backhaul; `None` means unlimited.
+    pub max_bytes_out_per_window: Option<u64>,
+    /// `Some` once `Message::Hello`/`HelloAck` (see `main`'s `client_token` arm) has negotiated an
+    /// authenticated-encryption transport for this connection; `None` means plaintext, which is
+    /// also the state every client starts in.
+    pub encryption: Option<FrameCipher>,
+    /// last time any byte arrived from this client, used by `main::sweep_clients` to detect a dead
+    /// connection at the application layer instead of waiting on the ~11 minute default TCP
+    /// timeout.
+    pub last_seen: time::Instant,
+    /// last time `Message::Ping` was sent to this client, or `None` if none has been sent since
+    /// connecting; only meaningful once `firmware_state` is `Correct`, see `main::sweep_clients`.
+    pub last_ping_sent: Option<time::Instant>,
 }
 
+/// Tracks a chunked firmware transfer so chunks the device NAKs can be resent without restarting
+/// the whole image, since transfer over LoRa is slow and easily interrupted. A chunk that gets
+/// neither an ack nor a NAK is not resent; the transfer then stays at that offset.
+///
+/// SCOPE: this only covers the host side of the transfer (chunking, per-chunk CRC, resend on
+/// NAK). It deliberately does NOT implement `embassy-boot`-style A/B flash slots,
+/// automatic rollback, or resume state persisted across a device reset:
+/// `components/firmware` has no `embassy-boot` integration, no secondary flash slot, and no flash
+/// driver of any kind today — the device instead lands each chunk via the STM32 system
+/// bootloader's own write protocol (see `BOOTLOADER_WRITE_MAX_SIZE`), which is a single-slot,
+/// single-attempt write with none of A/B, rollback, or resume semantics. Adding those is a
+/// from-scratch on-device bootloader rewrite (new partition layout, new boot-time slot-select and
+/// rollback logic, reset-persisted progress), not a chunk-transport change, so it's tracked as
+/// separate future work rather than folded into this transport layer.
+#[derive(Debug)]
+pub struct UpdateProgress {
+    binary: Vec<u8>,
+    /// offset of the next chunk to send; advances only once it has been acked
+    offset: usize,
+    /// consecutive NAKs of the chunk at `offset`, i.e. how many times it has been resent
+    retries: u8,
+}
+
+/// Give up on a chunk (and the whole transfer) after this many NAKed retries.
+const MAX_CHUNK_RETRIES: u8 = 5;
+
 impl Display for Client<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let mut props = Vec::new();
@@ -83,7 +147,30 @@ impl Client<'_> {
         }
     }
 
-    pub fn decode_log_data(&mut self, data: &[u8], event_writer: &mut ProtocolEventFileWriter) {
+    /// Account for `n` bytes read from this client, called from `main`'s client read path right
+    /// before `client.buffer.extend(&receive_buffer[..n])`.
+    pub fn record_bytes_in(&mut self, n: usize) {
+        self.bytes_in += n as u64;
+        self.window_bytes_in += n as u64;
+    }
+
+    /// Roll the rate-accounting window over once `window` has elapsed since `window_start`:
+    /// recomputes `rate_in`/`rate_out` from the bytes seen this window and refills the outbound
+    /// rate-limit budget. Called once per client per main-loop iteration from
+    /// `main::sweep_clients`.
+    pub fn update_rate_window(&mut self, now: time::Instant, window: time::Duration) {
+        let elapsed = now.saturating_duration_since(self.window_start);
+        if elapsed < window {
+            return;
+        }
+        self.rate_in = self.window_bytes_in as f64 / elapsed.as_secs_f64();
+        self.rate_out = self.window_bytes_out as f64 / elapsed.as_secs_f64();
+        self.window_bytes_in = 0;
+        self.window_bytes_out = 0;
+        self.window_start = now;
+    }
+
+    pub fn decode_log_data(&mut self, data: &[u8], event_writer: &mut dyn EventWriter) {
         self.log_decoder.received(data);
         // data might contain multiple log messages
         let id_str = self.identifier_str();
@@ -109,52 +196,159 @@ impl Client<'_> {
         }
     }
 
+    /// Queue `message` for sending and flush as much of it as the socket accepts right away.
+    /// Whatever doesn't fit is left in `pending_write`; the caller (`main`'s event loop) is
+    /// responsible for registering this client's connection for `Interest::WRITABLE` when that
+    /// queue is non-empty and draining it via `flush_pending_write`, so one slow client (e.g. mid
+    /// firmware transfer) never stalls the poll loop waiting on its socket buffer.
     pub fn send_message(&mut self, message: Message) -> Result<()> {
         let mut cbor_encoded = Vec::with_capacity(1024);
         ciborium::into_writer(&message, &mut cbor_encoded)?;
-        let mut cobs_encoded = cobs::encode_vec(cbor_encoded.as_slice());
+
+        let framed = match &self.encryption {
+            Some(cipher) => cipher.seal(&cbor_encoded),
+            None => cbor_encoded,
+        };
+
+        let mut cobs_encoded = cobs::encode_vec(framed.as_slice());
         cobs_encoded.push(0x00);
 
-        // TODO: this feels like a hack
-        // maybe start a thread for each client that waits on a channel and then makes sure the data
-        // is sent completely
-        let mut buf = cobs_encoded.as_slice();
-        while !buf.is_empty() {
-            match self.connection.write(buf) {
-                Ok(0) => {
-                    bail!("failed to write whole buffer");
+        self.pending_write.extend(cobs_encoded);
+        self.flush_pending_write()?;
+
+        Ok(())
+    }
+
+    /// Write as much of `pending_write` as the socket will currently accept without blocking,
+    /// without exceeding `max_bytes_out_per_window` for the current rate window if set. Returns
+    /// `true` once the queue is fully drained, `false` if the socket would block or the rate
+    /// budget is exhausted for now; either way the caller knows this client still needs
+    /// `Interest::WRITABLE` to make further progress.
+    pub fn flush_pending_write(&mut self) -> Result<bool> {
+        while !self.pending_write.is_empty() {
+            let (front, _) = self.pending_write.as_slices();
+            let front = match self.max_bytes_out_per_window {
+                Some(limit) if self.window_bytes_out >= limit => return Ok(false),
+                Some(limit) => {
+                    let budget = (limit - self.window_bytes_out) as usize;
+                    &front[..front.len().min(budget)]
                 }
-                Ok(n) => buf = &buf[n..],
-                Err(ref err) if err.kind() == std::io::ErrorKind::WouldBlock => {
-                    thread::sleep(time::Duration::from_millis(50))
+                None => front,
+            };
+            match self.connection.write(front) {
+                Ok(0) => bail!("failed to write to client"),
+                Ok(n) => {
+                    self.pending_write.drain(..n);
+                    self.bytes_out += n as u64;
+                    self.window_bytes_out += n as u64;
                 }
+                Err(ref err) if err.kind() == std::io::ErrorKind::WouldBlock => return Ok(false),
                 Err(ref err) if err.kind() == std::io::ErrorKind::Interrupted => {}
                 Err(err) => Err(err)?,
             }
         }
 
-        Ok(())
+        Ok(true)
     }
 
+    /// Start a chunked, acknowledged firmware transfer. Further progress happens as `FwChunkAck`/
+    /// `FwChunkNak` messages arrive; see `handle_fw_chunk_ack` and `handle_fw_chunk_nak`.
     pub fn update_firmware(&mut self, binary: &[u8]) -> Result<()> {
-        self.send_message(Message::InitFwUpdate)
-            .context("could not init firmware update")?;
-
-        let mut offset = 0;
-        for chunk in binary.chunks(BOOTLOADER_WRITE_MAX_SIZE) {
-            self.send_message(Message::FwChunk {
-                offset: offset as u32,
-                data: chunk.to_owned(),
-            })
-            .context("could not send firmware chunk")?;
-            offset += chunk.len();
+        let crc = calc_crc(binary, Some((0xff, pad_len(binary.len()))), WORD_SIZE)
+            .context("could not calculate CRC of firmware image")?;
+
+        self.send_message(Message::InitFwUpdate {
+            total_size: binary.len() as u32,
+            crc,
+        })
+        .context("could not init firmware update")?;
+
+        self.firmware_update = Some(UpdateProgress {
+            binary: binary.to_owned(),
+            offset: 0,
+            retries: 0,
+        });
+
+        self.send_next_fw_chunk()
+    }
+
+    /// Send the chunk at the transfer's current offset, or wrap up the transfer once the whole
+    /// image has been sent and acked: sends `FinishFwUpdate` followed by `GetInfo` so the device's
+    /// next `Info` report (handled in `main`, like any other) confirms its CRC now matches
+    /// `expected_flash_crc` before the client is marked `FirmwareState::Correct`.
+    fn send_next_fw_chunk(&mut self) -> Result<()> {
+        let Some(update) = &self.firmware_update else {
+            return Ok(());
+        };
+
+        let Some(chunk) = update
+            .binary
+            .chunks(BOOTLOADER_WRITE_MAX_SIZE)
+            .nth(update.offset / BOOTLOADER_WRITE_MAX_SIZE)
+        else {
+            self.firmware_update = None;
+            self.send_message(Message::FinishFwUpdate)
+                .context("could not finish firmware update")?;
+            return self
+                .send_message(Message::GetInfo)
+                .context("could not request post-update firmware info");
+        };
+
+        let offset = update.offset as u32;
+        let crc = calc_crc(chunk, Some((0xff, pad_len(chunk.len()))), WORD_SIZE)
+            .context("could not calculate CRC of firmware chunk")?;
+        let data = chunk.to_owned();
+
+        self.send_message(Message::FwChunk { offset, crc, data })
+            .context("could not send firmware chunk")
+    }
+
+    /// Advance the transfer once the device confirms it wrote `offset` successfully.
+    pub fn handle_fw_chunk_ack(&mut self, offset: u32) -> Result<()> {
+        let Some(update) = &mut self.firmware_update else {
+            return Ok(());
+        };
+        if update.offset as u32 != offset {
+            // stale ack for a chunk we already moved past (e.g. a retransmit's ack arriving late)
+            return Ok(());
         }
+        update.offset += BOOTLOADER_WRITE_MAX_SIZE;
+        update.retries = 0;
+        self.send_next_fw_chunk()
+    }
 
-        self.send_message(Message::FinishFwUpdate)
-            .context("could not finish firmware update")?;
+    /// Retry the chunk at `offset`, giving up on the whole transfer after `MAX_CHUNK_RETRIES`.
+    pub fn handle_fw_chunk_nak(&mut self, offset: u32) -> Result<()> {
+        let Some(update) = &mut self.firmware_update else {
+            return Ok(());
+        };
+        if update.offset as u32 != offset {
+            return Ok(());
+        }
+        update.retries += 1;
+        if update.retries > MAX_CHUNK_RETRIES {
+            self.firmware_update = None;
+            bail!("giving up on firmware update: chunk at offset {offset} still failing after {MAX_CHUNK_RETRIES} retries");
+        }
+        self.send_next_fw_chunk()
+    }
+}
 
-        Ok(())
+/// Padding needed to bring `len` bytes up to a multiple of `WORD_SIZE`, as `calc_crc` requires.
+fn pad_len(len: usize) -> usize {
+    (WORD_SIZE - len % WORD_SIZE) % WORD_SIZE
+}
+
+/// Format a bytes/sec rate for display in the `list` table and the periodic throughput summary.
+pub fn format_rate(bytes_per_sec: f64) -> String {
+    const UNITS: [&str; 4] = ["B/s", "KB/s", "MB/s", "GB/s"];
+    let mut rate = bytes_per_sec;
+    let mut unit = 0;
+    while rate >= 1024.0 && unit < UNITS.len() - 1 {
+        rate /= 1024.0;
+        unit += 1;
     }
+    format!("{:.1} {}", rate, UNITS[unit])
 }
 
 #[derive(Debug)]
@@ -181,12 +375,27 @@ impl std::fmt::Display for FirmwareState {
 #[derive(Debug, Serialize, Deserialize)]
 pub enum Message {
     Log(Vec<u8>),
-    InitFwUpdate,
+    /// Negotiates the transfer: total image size and a CRC32 of the whole (padded) image, checked
+    /// against the reassembled image after the last chunk.
+    InitFwUpdate {
+        total_size: u32,
+        crc: u32,
+    },
     FwChunk {
         offset: u32,
+        /// CRC32 of `data` (padded like `InitFwUpdate::crc`), checked by the device on receipt
+        crc: u32,
         #[serde(with = "serde_bytes")]
         data: Vec<u8>,
     },
+    /// Device confirms `offset` was written and its CRC matched; host may send the next chunk.
+    FwChunkAck {
+        offset: u32,
+    },
+    /// Device rejects `offset` (CRC mismatch or write failure); host resends that same chunk.
+    FwChunkNak {
+        offset: u32,
+    },
     FinishFwUpdate,
     Reset,
     Halt,
@@ -197,6 +406,19 @@ pub enum Message {
     },
     Error(String),
     Halted(bool),
+    /// Application-level heartbeat sent by the cloud to a `Correct`-firmware client that's gone
+    /// quiet; correct firmware echoes it straight back. See `main::sweep_clients`, which is what
+    /// actually notices the silence and disconnects a client that never answers.
+    Ping,
+    /// Sent by a device right after connecting, always in plaintext, to ask whether this
+    /// connection should switch to the PSK-authenticated-encryption transport (see the `crypto`
+    /// module). The cloud's `HelloAck` decides what actually happens, so a cloud started without a
+    /// PSK, or a device that never sends `Hello` at all, both fall back to today's plaintext link.
+    Hello { request_encryption: bool },
+    /// The cloud's reply to `Hello`, sent in plaintext: `encryption` is true only if both sides
+    /// support it (the cloud has a PSK configured and the device asked for it). Every frame after
+    /// this one is AEAD-sealed if and only if `encryption` is true.
+    HelloAck { encryption: bool },
 }
 
 pub struct Colors(HashMap<NodeId, RGBColor>, Vec<RGBColor>);
@@ -0,0 +1,175 @@
+//  _____       ______   ____
+// |_   _|     |  ____|/ ____|  Institute of Embedded Systems
+//   | |  _ __ | |__  | (___    Zurich University of Applied Sciences
+//   | | | '_ \|  __|  \___ \   8401 Winterthur, Switzerland
+//  _| |_| | | | |____ ____) |
+// |_____|_| |_|______|_____/
+//
+// Copyright 2025 Institute of Embedded Systems at Zurich University of Applied Sciences.
+// All rights reserved.
+// SPDX-License-Identifier: MIT
+
+//! The operator command set (`list`, `fwupdate`, `halt`, `reset`, ...), factored out of what used
+//! to be a stdin-only `handle_command` so the exact same command logic backs both the interactive
+//! console and remote operators connected to `main`'s control socket. [`run_command`] never prints:
+//! it returns a [`CommandOutcome`] that the stdin caller `println!`s and the control-socket caller
+//! serializes as a JSON reply, the same split `protocol_event_writer::EventWriter` draws between
+//! producing an event and persisting it.
+
+use std::collections::VecDeque;
+
+use mio::net::TcpStream;
+use serde::{Deserialize, Serialize};
+
+use crate::client::{format_rate, Client, FirmwareState, Message};
+use crate::slab::Slab;
+
+/// One accepted connection on the control socket, buffering bytes until a full newline-delimited
+/// `ControlRequest` line has arrived.
+pub struct ControlClient {
+    pub connection: TcpStream,
+    pub buffer: VecDeque<u8>,
+}
+
+/// One line of a newline-delimited JSON request read from a control-socket client, e.g.
+/// `{"command":"halt","argument":"3"}`.
+#[derive(Debug, Deserialize)]
+pub struct ControlRequest {
+    pub command: String,
+    #[serde(default)]
+    pub argument: String,
+}
+
+/// Result of running one operator command. Carries everything `list`/`help`/... would otherwise
+/// have printed, so a caller can format it however it needs to.
+#[derive(Debug, Serialize)]
+#[serde(tag = "status", content = "data", rename_all = "lowercase")]
+pub enum CommandOutcome {
+    Help(String),
+    Nodes(Vec<NodeInfo>),
+    Ok,
+    Error(String),
+}
+
+#[derive(Debug, Serialize)]
+pub struct NodeInfo {
+    pub index: usize,
+    pub id: String,
+    pub halted: String,
+    pub firmware: String,
+    pub address: String,
+    pub rate_in: String,
+    pub rate_out: String,
+}
+
+pub const HELP_TEXT: &str = "
+List of commands:
+
+  help | ?
+    print this message
+
+  [l]ist
+    list connected nodes
+
+  [fwu]pdate
+    update all nodes that run incorrect firmware
+
+  [h]alt (INDEX|all)
+    halt node with index INDEX or all nodes
+
+  [r]eset (INDEX|all)
+    reset node with index INDEX or all nodes
+";
+
+/// Split `help`/`list`/`halt 3`/... into a command and its (possibly empty) argument, the same way
+/// for both the stdin console and control-socket requests.
+pub fn parse_command(input: &str) -> (&str, &str) {
+    input.split_once(' ').unwrap_or((input, ""))
+}
+
+/// Run one command against `clients`. `clear` isn't handled here: clearing the terminal only makes
+/// sense against the local console, so the stdin caller intercepts it before reaching this
+/// function.
+pub fn run_command(
+    command: &str,
+    argument: &str,
+    clients: &mut Slab<Client>,
+    binary: &[u8],
+) -> CommandOutcome {
+    match command.to_lowercase().as_str() {
+        "help" | "?" => CommandOutcome::Help(HELP_TEXT.to_string()),
+        "list" | "l" => CommandOutcome::Nodes(
+            clients
+                .iter()
+                .enumerate()
+                .filter_map(|(index, client)| Some((index, client?)))
+                .filter(|(_, client)| client.node_id.is_some())
+                .map(|(index, client)| NodeInfo {
+                    index,
+                    id: client.identifier_str(),
+                    halted: client.halted_as_string().to_string(),
+                    firmware: client.firmware_state.to_string(),
+                    address: client
+                        .connection
+                        .peer_addr()
+                        .map(|addr| addr.to_string())
+                        .unwrap_or_else(|_| "UNKNOWN".to_string()),
+                    rate_in: format_rate(client.rate_in),
+                    rate_out: format_rate(client.rate_out),
+                })
+                .collect(),
+        ),
+        "fwupdate" | "fwu" => {
+            let mut errors = Vec::new();
+            for client in clients.iter_mut().flatten() {
+                if matches!(client.firmware_state, FirmwareState::Incorrect) {
+                    // non-blocking: `send_message` queues chunks instead of waiting on the socket,
+                    // and acks drive the rest of the transfer from `main`'s event loop, so kicking
+                    // many of these off back-to-back here runs them all in parallel
+                    if let Err(err) = client.update_firmware(binary) {
+                        errors.push(format!(
+                            "could not update firmware of {}: {}",
+                            client.identifier_str(),
+                            err
+                        ));
+                    }
+                }
+            }
+            if errors.is_empty() {
+                CommandOutcome::Ok
+            } else {
+                CommandOutcome::Error(errors.join("; "))
+            }
+        }
+        "reset" | "r" => reset_or_halt_outcome(true, argument, clients),
+        "halt" | "h" => reset_or_halt_outcome(false, argument, clients),
+        "" => CommandOutcome::Ok,
+        cmd => CommandOutcome::Error(format!("unknown command: {cmd}")),
+    }
+}
+
+fn reset_or_halt_outcome(reset: bool, argument: &str, clients: &mut Slab<Client>) -> CommandOutcome {
+    let action = if reset { "reset" } else { "halt" };
+
+    if argument == "all" {
+        for client in clients.iter_mut().flatten() {
+            let message = if reset { Message::Reset } else { Message::Halt };
+            if let Err(err) = client.send_message(message) {
+                return CommandOutcome::Error(format!("could not {action} {client}: {err}"));
+            }
+        }
+        return CommandOutcome::Ok;
+    }
+
+    let Ok(index) = argument.parse::<usize>() else {
+        return CommandOutcome::Error("index argument must be a decimal numeral or \"all\"".to_string());
+    };
+    let Some(client) = clients.get_mut(index) else {
+        return CommandOutcome::Error(format!("no node at index {index}"));
+    };
+    let message = if reset { Message::Reset } else { Message::Halt };
+    match client.send_message(message) {
+        Ok(()) => CommandOutcome::Ok,
+        Err(err) => CommandOutcome::Error(format!("could not {action} {client}: {err}")),
+    }
+}
@@ -0,0 +1,69 @@
+//  _____       ______   ____
+// |_   _|     |  ____|/ ____|  Institute of Embedded Systems
+//   | |  _ __ | |__  | (___    Zurich University of Applied Sciences
+//   | | | '_ \|  __|  \___ \   8401 Winterthur, Switzerland
+//  _| |_| | | | |____ ____) |
+// |_____|_| |_|______|_____/
+//
+// Copyright 2025 Institute of Embedded Systems at Zurich University of Applied Sciences.
+// All rights reserved.
+// SPDX-License-Identifier: MIT
+
+//! Optional authenticated-encryption transport for the node<->cloud link: every frame (log lines,
+//! firmware chunks, control messages) is sealed with XChaCha20-Poly1305 keyed from a pre-shared
+//! key loaded at startup (`main`'s PSK-file CLI arg), with a fresh random nonce per frame. Whether
+//! a given connection actually uses it is negotiated per-client via `client::Message::Hello`/
+//! `HelloAck` (see `main`'s `client_token` arm), so a cloud started with a PSK still accepts
+//! plaintext connections from devices that don't speak that handshake.
+
+use anyhow::{anyhow, bail, Context, Result};
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+
+/// Size in bytes of the random nonce prepended to every sealed frame.
+const NONCE_LEN: usize = 24;
+
+/// Wraps the keyed AEAD cipher used to seal/open frames on an encrypted connection. Cheap to
+/// clone (it only shares the PSK-derived key), so `main` hands every client that negotiates
+/// encryption its own clone rather than threading a reference through `Client`'s lifetime
+/// parameter.
+#[derive(Clone)]
+pub struct FrameCipher {
+    cipher: XChaCha20Poly1305,
+}
+
+impl FrameCipher {
+    pub fn new(psk: &[u8; 32]) -> Self {
+        Self {
+            cipher: XChaCha20Poly1305::new(Key::from_slice(psk)),
+        }
+    }
+
+    /// Seal `plaintext` (the CBOR-encoded message) behind a fresh random nonce, returning
+    /// `nonce || ciphertext` ready to be COBS-framed in place of the plaintext bytes.
+    pub fn seal(&self, plaintext: &[u8]) -> Vec<u8> {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let mut framed = nonce.to_vec();
+        framed.extend(
+            self.cipher
+                .encrypt(&nonce, plaintext)
+                .expect("encryption with a freshly generated nonce cannot fail"),
+        );
+        framed
+    }
+
+    /// Split a decoded frame into its nonce and ciphertext, verify it, and return the plaintext
+    /// CBOR bytes. Fails if the frame is too short to carry a nonce or authentication doesn't
+    /// check out (wrong key or tampering); the caller must disconnect the client rather than
+    /// trust anything about a frame that fails to open.
+    pub fn open(&self, framed: &[u8]) -> Result<Vec<u8>> {
+        if framed.len() < NONCE_LEN {
+            bail!("frame shorter than the nonce it must carry");
+        }
+        let (nonce, ciphertext) = framed.split_at(NONCE_LEN);
+        self.cipher
+            .decrypt(XNonce::from_slice(nonce), ciphertext)
+            .map_err(|_| anyhow!("frame failed authentication"))
+            .context("could not open encrypted frame")
+    }
+}
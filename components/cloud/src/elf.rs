@@ -10,29 +10,97 @@
 // SPDX-License-Identifier: MIT
 
 use anyhow::{anyhow, bail, Result};
+use crc::Crc;
 use object::{
-    elf::{FileHeader32, PT_LOAD},
+    elf::{FileHeader32, FileHeader64, PT_LOAD},
     read::elf::{FileHeader, ProgramHeader},
     Endianness, Object, ObjectSection,
 };
 use std::cmp::max;
 
-// inspired by https://github.com/probe-rs/probe-rs/blob/73acf92686a62489049b6da6fce940bf94b07da8/probe-rs/src/flashing/download.rs#L218-L307
-pub fn extract_from_elf(elf_file: &[u8], start_addr: u32) -> Result<Vec<u8>> {
-    let file_kind = object::FileKind::parse(elf_file)?;
+/// Flashable firmware image container format, see `extract_from_elf`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    /// Raw padded binary blob, one byte per flash address starting at `start_addr`.
+    Raw,
+    /// Intel HEX text format, as understood by most third-party flash programmers.
+    IntelHex,
+    /// Motorola S-record text format.
+    SRecord,
+}
 
-    if !matches!(file_kind, object::FileKind::Elf32) {
-        bail!("Unsupported ELF file type");
+/// Integrity info for a `ImageFormat::Raw` image, see `extract_from_elf`'s `compute_crc`
+/// parameter.
+#[derive(Debug, Clone)]
+pub struct ImageCrc {
+    /// CRC32 (CRC-32/ISO-HDLC) of the final gap-filled (`0xFF`) padded binary.
+    pub image_crc32: u32,
+    /// CRC32 of each source segment's raw bytes (before gap-filling), keyed by its physical
+    /// address, so a mismatch in `image_crc32` can be narrowed down to the offending segment.
+    pub segment_crc32: Vec<(u32, u32)>,
+}
+
+impl ImageCrc {
+    /// Check `self.image_crc32` against an `expected_crc32` (e.g. read back from a flashed
+    /// device), erroring out on mismatch. Most likely cause of a mismatch: two segments
+    /// overlapped and silently clobbered each other while gap-filling.
+    pub fn verify_against(&self, expected_crc32: u32) -> Result<()> {
+        if self.image_crc32 != expected_crc32 {
+            bail!(
+                "image CRC32 mismatch: expected {expected_crc32:08x}, computed {:08x} (do any \
+                 segments overlap?)",
+                self.image_crc32
+            );
+        }
+        Ok(())
     }
+}
 
-    let elf_header = FileHeader32::<Endianness>::parse(elf_file)?;
+fn crc32(data: &[u8]) -> u32 {
+    Crc::<u32>::new(&crc::CRC_32_ISO_HDLC).checksum(data)
+}
 
-    let elf_data = object::read::elf::ElfFile::<FileHeader32<Endianness>>::parse(elf_file)?;
+// inspired by https://github.com/probe-rs/probe-rs/blob/73acf92686a62489049b6da6fce940bf94b07da8/probe-rs/src/flashing/download.rs#L218-L307
+pub fn extract_from_elf(
+    elf_file: &[u8],
+    start_addr: u32,
+    format: ImageFormat,
+    compute_crc: bool,
+) -> Result<(Vec<u8>, Option<ImageCrc>)> {
+    match object::FileKind::parse(elf_file)? {
+        object::FileKind::Elf32 => {
+            extract::<FileHeader32<Endianness>>(elf_file, start_addr, format, compute_crc)
+        }
+        object::FileKind::Elf64 => {
+            extract::<FileHeader64<Endianness>>(elf_file, start_addr, format, compute_crc)
+        }
+        _ => bail!("Unsupported ELF file type"),
+    }
+}
+
+/// Does the actual extraction work for either `FileHeader32` or `FileHeader64`. Address
+/// arithmetic is kept at `u64` throughout and only narrowed to `u32` at the boundaries that are
+/// inherently 32-bit: the raw binary's offset from the caller's `start_addr`, and the Intel
+/// HEX/S-record container formats.
+fn extract<Elf>(
+    elf_file: &[u8],
+    start_addr: u32,
+    format: ImageFormat,
+    compute_crc: bool,
+) -> Result<(Vec<u8>, Option<ImageCrc>)>
+where
+    Elf: FileHeader<Endian = Endianness>,
+    Elf::Word: Into<u64>,
+{
+    let elf_header = Elf::parse(elf_file)?;
+
+    let elf_data = object::read::elf::ElfFile::<Elf>::parse(elf_file)?;
 
     let endian = elf_header.endian()?;
+    let entry_point: u64 = elf_header.e_entry(endian).into();
 
-    let mut extracted_data: Vec<(u32, &[u8])> = Vec::new();
-    let mut end_addr: u32 = 0;
+    let mut extracted_data: Vec<(u64, &[u8])> = Vec::new();
+    let mut end_addr: u64 = 0;
 
     for segment in elf_header.program_headers(endian, elf_file)? {
         let physical_addr: u64 = segment.p_paddr(endian).into();
@@ -74,28 +142,54 @@ pub fn extract_from_elf(elf_file: &[u8], start_addr: u32) -> Result<Vec<u8>> {
         let segment_end_addr = physical_addr
             .checked_add(segment_filesize)
             .ok_or(anyhow!("physical address or segment filesize out of range"))?;
-        end_addr = max(segment_end_addr as u32, end_addr);
+        end_addr = max(segment_end_addr, end_addr);
 
-        extracted_data.push((physical_addr as u32, section_data));
+        extracted_data.push((physical_addr, section_data));
     }
 
-    let bin_size = end_addr
-        .checked_sub(start_addr)
-        .ok_or(anyhow!("binary end address out of range"))?;
+    let start_addr = start_addr as u64;
 
-    if bin_size % 4 != 0 {
-        bail!("length of binary is not a multiple of 4 bytes");
-    }
+    match format {
+        ImageFormat::Raw => {
+            let bin_size = end_addr
+                .checked_sub(start_addr)
+                .ok_or(anyhow!("binary end address out of range"))?;
 
-    let mut bin = vec![0xff; bin_size as usize];
-    for (addr, data) in extracted_data.iter() {
-        let start = addr
-            .checked_sub(start_addr)
-            .ok_or(anyhow!("segment address out of range"))? as usize;
-        bin[start..][..data.len()].copy_from_slice(data);
-    }
+            if bin_size % 4 != 0 {
+                bail!("length of binary is not a multiple of 4 bytes");
+            }
+
+            let mut bin = vec![0xff; bin_size as usize];
+            for (addr, data) in extracted_data.iter() {
+                let start = addr
+                    .checked_sub(start_addr)
+                    .ok_or(anyhow!("segment address out of range"))? as usize;
+                bin[start..][..data.len()].copy_from_slice(data);
+            }
 
-    Ok(bin)
+            let crc = compute_crc
+                .then(|| -> Result<ImageCrc> {
+                    Ok(ImageCrc {
+                        image_crc32: crc32(&bin),
+                        segment_crc32: extracted_data
+                            .iter()
+                            .map(|(addr, data)| Ok((narrow_addr(*addr)?, crc32(data))))
+                            .collect::<Result<Vec<_>>>()?,
+                    })
+                })
+                .transpose()?;
+
+            Ok((bin, crc))
+        }
+        ImageFormat::IntelHex => {
+            let segments = narrow_segments(&extracted_data)?;
+            Ok((to_intel_hex(&segments, narrow_addr(entry_point)?), None))
+        }
+        ImageFormat::SRecord => {
+            let segments = narrow_segments(&extracted_data)?;
+            Ok((to_s_record(&segments, narrow_addr(entry_point)?), None))
+        }
+    }
 
     // let mut dst = vec![0u32; (bin_size / 4) as usize];
     // // not sure if the encoding of the binary is specified by the endianness in the elf header
@@ -109,3 +203,225 @@ pub fn extract_from_elf(elf_file: &[u8], start_addr: u32) -> Result<Vec<u8>> {
     // }
     // Ok(dst)
 }
+
+/// Narrow a 64-bit address to `u32`, e.g. for container formats (Intel HEX, S-record) that are
+/// inherently 32-bit.
+fn narrow_addr(addr: u64) -> Result<u32> {
+    u32::try_from(addr).map_err(|_| anyhow!("address {addr:#x} does not fit in 32 bits"))
+}
+
+fn narrow_segments<'a>(segments: &[(u64, &'a [u8])]) -> Result<Vec<(u32, &'a [u8])>> {
+    segments
+        .iter()
+        .map(|(addr, data)| Ok((narrow_addr(*addr)?, *data)))
+        .collect()
+}
+
+/// Number of data bytes per Intel HEX data record (`04`/`05` records carry their own fixed-size
+/// payload regardless of this).
+const IHEX_BYTES_PER_LINE: usize = 16;
+/// Number of data bytes per Motorola S-record `S3` data record.
+const SREC_BYTES_PER_LINE: usize = 32;
+
+/// Two's-complement checksum of an Intel HEX record's byte count, address, type and data bytes.
+fn ihex_checksum(bytes: &[u8]) -> u8 {
+    let sum: u32 = bytes.iter().map(|&b| b as u32).sum();
+    (!(sum as u8)).wrapping_add(1)
+}
+
+/// Render one `:LLAAAATTDD..CC` Intel HEX record line, including its trailing newline.
+fn ihex_record(record_type: u8, address: u16, data: &[u8]) -> String {
+    let mut bytes = Vec::with_capacity(4 + data.len());
+    bytes.push(data.len() as u8);
+    bytes.extend_from_slice(&address.to_be_bytes());
+    bytes.push(record_type);
+    bytes.extend_from_slice(data);
+    let checksum = ihex_checksum(&bytes);
+
+    let mut line = String::with_capacity(1 + bytes.len() * 2 + 2 + 1);
+    line.push(':');
+    for byte in &bytes {
+        line.push_str(&format!("{byte:02X}"));
+    }
+    line.push_str(&format!("{checksum:02X}"));
+    line.push('\n');
+    line
+}
+
+/// Convert extracted ELF segments to Intel HEX, emitting a `04` extended-linear-address record
+/// whenever a segment (or a line within it) crosses into a new 64KiB page, and a `05`
+/// start-linear-address record for `entry_point`.
+fn to_intel_hex(segments: &[(u32, &[u8])], entry_point: u32) -> Vec<u8> {
+    let mut hex = String::new();
+    let mut current_upper: Option<u16> = None;
+
+    for (addr, data) in segments {
+        for (i, chunk) in data.chunks(IHEX_BYTES_PER_LINE).enumerate() {
+            let chunk_addr = addr + (i * IHEX_BYTES_PER_LINE) as u32;
+            let upper = (chunk_addr >> 16) as u16;
+            if current_upper != Some(upper) {
+                hex.push_str(&ihex_record(0x04, 0, &upper.to_be_bytes()));
+                current_upper = Some(upper);
+            }
+            hex.push_str(&ihex_record(0x00, chunk_addr as u16, chunk));
+        }
+    }
+
+    hex.push_str(&ihex_record(0x05, 0, &entry_point.to_be_bytes()));
+    hex.push_str(&ihex_record(0x01, 0, &[]));
+    hex.into_bytes()
+}
+
+/// One's-complement checksum of an S-record's byte count, address and data bytes.
+fn srec_checksum(bytes: &[u8]) -> u8 {
+    let sum: u32 = bytes.iter().map(|&b| b as u32).sum();
+    !(sum as u8)
+}
+
+/// Render one `S3`/`S7` S-record line with a 4-byte address field, including its trailing newline.
+fn srec_record(record_type: u8, address: u32, data: &[u8]) -> String {
+    let mut bytes = Vec::with_capacity(5 + data.len());
+    bytes.push((4 + data.len() + 1) as u8);
+    bytes.extend_from_slice(&address.to_be_bytes());
+    bytes.extend_from_slice(data);
+    let checksum = srec_checksum(&bytes);
+
+    let mut line = String::with_capacity(2 + bytes.len() * 2 + 2 + 1);
+    line.push_str(&format!("S{record_type}"));
+    for byte in &bytes {
+        line.push_str(&format!("{byte:02X}"));
+    }
+    line.push_str(&format!("{checksum:02X}"));
+    line.push('\n');
+    line
+}
+
+/// Convert extracted ELF segments to Motorola S-record, emitting one `S3` data record per line and
+/// a trailing `S7` termination record carrying `entry_point` as the start address.
+fn to_s_record(segments: &[(u32, &[u8])], entry_point: u32) -> Vec<u8> {
+    let mut srec = String::new();
+
+    for (addr, data) in segments {
+        for (i, chunk) in data.chunks(SREC_BYTES_PER_LINE).enumerate() {
+            let chunk_addr = addr + (i * SREC_BYTES_PER_LINE) as u32;
+            srec.push_str(&srec_record(3, chunk_addr, chunk));
+        }
+    }
+
+    srec.push_str(&srec_record(7, entry_point, &[]));
+    srec.into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc32_matches_known_check_value() {
+        // the standard CRC-32/ISO-HDLC check value for the ASCII string "123456789"
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn verify_against_accepts_matching_crc_and_rejects_mismatch() {
+        let image_crc = ImageCrc {
+            image_crc32: crc32(b"firmware"),
+            segment_crc32: vec![(0x0800_0000, crc32(b"firmware"))],
+        };
+        assert!(image_crc.verify_against(crc32(b"firmware")).is_ok());
+        assert!(image_crc.verify_against(crc32(b"firmware").wrapping_add(1)).is_err());
+    }
+
+    /// Hand-assembled minimal 32-bit little-endian ELF with a single `PT_LOAD` segment backed by
+    /// one section (so `extract`'s "segment contains at least one section" check passes), holding
+    /// exactly `data`, physically loaded at `addr`.
+    fn minimal_elf32(addr: u32, data: &[u8]) -> Vec<u8> {
+        let data_offset = 52 + 32; // right after the ELF header and its one program header
+        let shstrtab: &[u8] = b"\0.data\0.shstrtab\0";
+        let shstrtab_offset = data_offset + data.len() as u32;
+        let shoff = shstrtab_offset + shstrtab.len() as u32;
+
+        let mut elf = Vec::new();
+        // e_ident
+        elf.extend_from_slice(&[0x7f, b'E', b'L', b'F', 1, 1, 1, 0]);
+        elf.extend_from_slice(&[0; 8]);
+        elf.extend_from_slice(&2u16.to_le_bytes()); // e_type = ET_EXEC
+        elf.extend_from_slice(&40u16.to_le_bytes()); // e_machine = EM_ARM
+        elf.extend_from_slice(&1u32.to_le_bytes()); // e_version
+        elf.extend_from_slice(&addr.to_le_bytes()); // e_entry
+        elf.extend_from_slice(&52u32.to_le_bytes()); // e_phoff
+        elf.extend_from_slice(&shoff.to_le_bytes()); // e_shoff
+        elf.extend_from_slice(&0u32.to_le_bytes()); // e_flags
+        elf.extend_from_slice(&52u16.to_le_bytes()); // e_ehsize
+        elf.extend_from_slice(&32u16.to_le_bytes()); // e_phentsize
+        elf.extend_from_slice(&1u16.to_le_bytes()); // e_phnum
+        elf.extend_from_slice(&40u16.to_le_bytes()); // e_shentsize
+        elf.extend_from_slice(&3u16.to_le_bytes()); // e_shnum (null, .data, .shstrtab)
+        elf.extend_from_slice(&2u16.to_le_bytes()); // e_shstrndx
+        assert_eq!(elf.len(), 52);
+
+        // program header: one PT_LOAD segment covering `data`
+        elf.extend_from_slice(&1u32.to_le_bytes()); // p_type = PT_LOAD
+        elf.extend_from_slice(&data_offset.to_le_bytes()); // p_offset
+        elf.extend_from_slice(&addr.to_le_bytes()); // p_vaddr
+        elf.extend_from_slice(&addr.to_le_bytes()); // p_paddr
+        elf.extend_from_slice(&(data.len() as u32).to_le_bytes()); // p_filesz
+        elf.extend_from_slice(&(data.len() as u32).to_le_bytes()); // p_memsz
+        elf.extend_from_slice(&5u32.to_le_bytes()); // p_flags = R|X
+        elf.extend_from_slice(&4u32.to_le_bytes()); // p_align
+        assert_eq!(elf.len(), (data_offset) as usize);
+
+        elf.extend_from_slice(data);
+        elf.extend_from_slice(shstrtab);
+        assert_eq!(elf.len(), shoff as usize);
+
+        // section 0: null
+        elf.extend_from_slice(&[0u8; 40]);
+        // section 1: .data, PROGBITS, covers the PT_LOAD segment's file range
+        elf.extend_from_slice(&1u32.to_le_bytes()); // sh_name (".data" at offset 1)
+        elf.extend_from_slice(&1u32.to_le_bytes()); // sh_type = SHT_PROGBITS
+        elf.extend_from_slice(&3u32.to_le_bytes()); // sh_flags = ALLOC|WRITE
+        elf.extend_from_slice(&addr.to_le_bytes()); // sh_addr
+        elf.extend_from_slice(&data_offset.to_le_bytes()); // sh_offset
+        elf.extend_from_slice(&(data.len() as u32).to_le_bytes()); // sh_size
+        elf.extend_from_slice(&0u32.to_le_bytes()); // sh_link
+        elf.extend_from_slice(&0u32.to_le_bytes()); // sh_info
+        elf.extend_from_slice(&4u32.to_le_bytes()); // sh_addralign
+        elf.extend_from_slice(&0u32.to_le_bytes()); // sh_entsize
+        // section 2: .shstrtab, STRTAB
+        elf.extend_from_slice(&7u32.to_le_bytes()); // sh_name (".shstrtab" at offset 7)
+        elf.extend_from_slice(&3u32.to_le_bytes()); // sh_type = SHT_STRTAB
+        elf.extend_from_slice(&0u32.to_le_bytes()); // sh_flags
+        elf.extend_from_slice(&0u32.to_le_bytes()); // sh_addr
+        elf.extend_from_slice(&shstrtab_offset.to_le_bytes()); // sh_offset
+        elf.extend_from_slice(&(shstrtab.len() as u32).to_le_bytes()); // sh_size
+        elf.extend_from_slice(&0u32.to_le_bytes()); // sh_link
+        elf.extend_from_slice(&0u32.to_le_bytes()); // sh_info
+        elf.extend_from_slice(&1u32.to_le_bytes()); // sh_addralign
+        elf.extend_from_slice(&0u32.to_le_bytes()); // sh_entsize
+
+        elf
+    }
+
+    #[test]
+    fn extract_from_elf_omits_crc_unless_requested() {
+        let data = [0xDEu32.to_le_bytes(), 0xADu32.to_le_bytes()].concat();
+        let elf = minimal_elf32(0x1000, &data);
+
+        let (binary, crc) = extract_from_elf(&elf, 0x1000, ImageFormat::Raw, false).unwrap();
+        assert_eq!(binary, data);
+        assert!(crc.is_none());
+    }
+
+    #[test]
+    fn extract_from_elf_computes_matching_image_and_segment_crcs() {
+        let data = [0xDEu32.to_le_bytes(), 0xADu32.to_le_bytes()].concat();
+        let elf = minimal_elf32(0x1000, &data);
+
+        let (binary, crc) = extract_from_elf(&elf, 0x1000, ImageFormat::Raw, true).unwrap();
+        let crc = crc.unwrap();
+        assert_eq!(crc.image_crc32, crc32(&binary));
+        assert_eq!(crc.segment_crc32, vec![(0x1000, crc32(&data))]);
+        assert!(crc.verify_against(crc32(&binary)).is_ok());
+    }
+}
@@ -15,7 +15,7 @@ use anyhow::{anyhow, Context, Result};
 use mio::net::TcpListener;
 use mio::unix::SourceFd;
 use mio::{Events, Interest, Poll, Token};
-use protocol_event_writer::ProtocolEventFileWriter;
+use protocol_event_writer::{CsvEncoder, EventWriter, MultiEventWriter, ProtocolEventFileWriter};
 use std::collections::VecDeque;
 use std::env;
 use std::io::{self, Read, Write};
@@ -23,13 +23,13 @@ use std::mem::size_of;
 use std::time::{Duration, Instant};
 
 mod client;
+mod control;
 mod crc;
+mod crypto;
 mod elf;
+mod mqtt;
 mod slab;
-use crate::{client::*, crc::*, elf::*, slab::*};
-
-// TODO: default tcp timeout when a client disconnects is ~11 min on my machine, try to reduce this?
-// this is a socket option but rust does not have an API to change it
+use crate::{client::*, control::*, crc::*, crypto::*, elf::*, mqtt::*, slab::*};
 
 const DEFAULT_ELF_PATH: &str =
     "/tmp/cargo/target/thumbv7em-none-eabi/release/lightning_firmware_for_stm32wl55";
@@ -43,10 +43,25 @@ const ERASED_BYTE_VALUE: u8 = 0xff;
 const WORD_SIZE: usize = size_of::<u32>();
 const BOOTLOADER_WRITE_MAX_SIZE: usize = 256;
 
+/// How often a `Correct`-firmware client that hasn't spoken on its own gets sent a `Message::Ping`.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(10);
+/// A client that hasn't sent a byte in this long is considered dead and disconnected, replacing
+/// the ~11 minute default TCP disconnect detection (Rust exposes no API to tune that timeout) with
+/// an application-level one; see `sweep_clients`.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(30);
+/// How often per-client transfer rates are recomputed, outbound rate-limit budgets refill, and the
+/// aggregate throughput summary line is printed; see `sweep_clients`.
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+
 const DEFAULT_LISTEN_ADDR: &str = "0.0.0.0:50000";
+const DEFAULT_CONTROL_LISTEN_ADDR: &str = "0.0.0.0:50001";
 /// Set server token to maximum possible value as client tokens are allocated from 0
 const SERVER_TOKEN: Token = Token(usize::MAX);
 const STDIN_TOKEN: Token = Token(usize::MAX - 1);
+const CONTROL_SERVER_TOKEN: Token = Token(usize::MAX - 2);
+/// Control-socket client tokens are allocated from this offset upward, mirroring how device client
+/// tokens are allocated from 0 upward, so the two token namespaces can never collide.
+const CONTROL_CLIENT_TOKEN_BASE: usize = usize::MAX / 2;
 
 fn main() -> Result<()> {
     let args: Vec<String> = env::args().collect();
@@ -61,12 +76,59 @@ fn main() -> Result<()> {
         .map(|arg| arg.as_str())
         .unwrap_or(DEFAULT_ELF_PATH);
 
+    let mqtt_broker_addr = args.get(3).map(|arg| arg.as_str());
+
+    let control_listen_addr = args
+        .get(4)
+        .map(|arg| arg.as_str())
+        .unwrap_or(DEFAULT_CONTROL_LISTEN_ADDR);
+
+    // bytes per `RATE_WINDOW` a single client's outbound writes (firmware pushes, broadcast
+    // commands, ...) may use before `flush_pending_write` defers the rest to a later poll
+    // iteration; unset means unlimited.
+    let max_bytes_per_sec: Option<u64> = args
+        .get(5)
+        .map(|arg| arg.parse())
+        .transpose()
+        .context("rate limit must be a positive integer number of bytes per second")?;
+
+    // path to a 32-byte pre-shared key file; when set, clients that request it via `Message::Hello`
+    // get an authenticated-encryption link (see `crypto`), clients whose `Hello` declines it are
+    // disconnected, and clients that never send `Hello` stay plaintext.
+    let server_cipher: Option<FrameCipher> = args
+        .get(6)
+        .map(|psk_path| -> Result<FrameCipher> {
+            let psk = std::fs::read(psk_path).context("could not read PSK file")?;
+            let psk: [u8; 32] = psk
+                .try_into()
+                .map_err(|psk: Vec<u8>| anyhow!("PSK file must contain exactly 32 bytes, got {}", psk.len()))?;
+            Ok(FrameCipher::new(&psk))
+        })
+        .transpose()?;
+
+    // expected CRC32 (CRC-32/ISO-HDLC) of the extracted binary, e.g. from a build manifest or CI
+    // artifact; when set, a mismatch means the ELF at `elf_path` isn't the image that was meant to
+    // be flashed (wrong build, corrupted download, ...) and we refuse to start rather than push it
+    // out to the fleet.
+    let expected_image_crc32: Option<u32> = args
+        .get(7)
+        .map(|arg| u32::from_str_radix(arg.trim_start_matches("0x"), 16))
+        .transpose()
+        .context("expected image CRC32 must be a hex string, e.g. 0xdeadbeef")?;
+
     println!("reading firmware elf from {elf_path}");
 
     let elf_file = std::fs::read(elf_path).context("could not open firmware ELF")?;
 
-    let binary =
-        extract_from_elf(&elf_file, FLASH_OFFSET).context("could not extract binary from elf")?;
+    let (binary, image_crc) = extract_from_elf(&elf_file, FLASH_OFFSET, ImageFormat::Raw, true)
+        .context("could not extract binary from elf")?;
+    let image_crc = image_crc.expect("compute_crc was requested");
+    println!("extracted binary: {} bytes, crc32 {:08x}", binary.len(), image_crc.image_crc32);
+    if let Some(expected_image_crc32) = expected_image_crc32 {
+        image_crc
+            .verify_against(expected_image_crc32)
+            .context("extracted binary does not match the expected image CRC32")?;
+    }
     // calc crc of expected flash content
     let expected_flash_crc = calc_crc(
         &binary,
@@ -87,16 +149,32 @@ fn main() -> Result<()> {
     let defmt_table = Box::leak(defmt_table);
     assert!(defmt_table.encoding().can_recover());
 
-    let mut event_writer = ProtocolEventFileWriter::new(EVENT_FILE_PATH);
+    let mut mqtt_sink = mqtt_broker_addr
+        .map(|addr| MqttEventSink::new(addr, "lpwan-cloud"))
+        .transpose()
+        .context("could not connect to mqtt broker")?;
+
+    let mut event_writer: Box<dyn EventWriter> = match &mqtt_sink {
+        Some(mqtt_sink) => Box::new(MultiEventWriter(vec![
+            Box::new(ProtocolEventFileWriter::new(EVENT_FILE_PATH, Box::new(CsvEncoder))),
+            Box::new(mqtt_sink.clone()),
+        ])),
+        None => Box::new(ProtocolEventFileWriter::new(EVENT_FILE_PATH, Box::new(CsvEncoder))),
+    };
 
     let mut client_colors = client::Colors::new();
 
     let mut tcp_listener = TcpListener::bind(listen_addr.parse()?)?;
     println!("listening on {listen_addr}");
 
+    let mut control_clients: Slab<ControlClient> = Slab::new();
+    let mut control_listener = TcpListener::bind(control_listen_addr.parse()?)?;
+    println!("control socket listening on {control_listen_addr}");
+
     let mut receive_buffer = [0; 1024];
 
     let mut last_log_activity: Option<Instant> = None;
+    let mut last_throughput_summary = Instant::now();
 
     let stdin = io::stdin();
     let mut input = String::new();
@@ -110,9 +188,21 @@ fn main() -> Result<()> {
     poll.registry()
         .register(&mut tcp_listener, SERVER_TOKEN, Interest::READABLE)?;
 
+    poll.registry().register(
+        &mut control_listener,
+        CONTROL_SERVER_TOKEN,
+        Interest::READABLE,
+    )?;
+
     loop {
-        poll.poll(&mut events, None)?;
-        for event in events.iter() {
+        let poll_timeout = sweep_clients(
+            &poll,
+            &mut clients,
+            Instant::now(),
+            &mut last_throughput_summary,
+        )?;
+        poll.poll(&mut events, poll_timeout)?;
+        'events: for event in events.iter() {
             match event.token() {
                 STDIN_TOKEN => {
                     input.clear();
@@ -122,9 +212,39 @@ fn main() -> Result<()> {
                         return Ok(());
                     }
                     let input = input.trim();
-                    if let Err(err) = handle_command(input, &mut clients, &binary) {
-                        println!("could not handle command: {err}")
+                    let (command, argument) = control::parse_command(input);
+                    if matches!(command.to_lowercase().as_str(), "clear" | "c") {
+                        clear_screen();
+                    } else {
+                        let outcome = control::run_command(command, argument, &mut clients, &binary);
+                        print_outcome(&outcome);
                     }
+                    print!("> ");
+                    io::stdout().flush().unwrap();
+                }
+                CONTROL_SERVER_TOKEN => {
+                    let (connection, addr) = match control_listener.accept() {
+                        Ok(pair) => pair,
+                        Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => break,
+                        Err(err) => Err(err)?,
+                    };
+
+                    println!("new control connection from {:?}", addr);
+
+                    let control_client = ControlClient {
+                        connection,
+                        buffer: VecDeque::with_capacity(1024),
+                    };
+                    let control_client_index = control_clients.insert(control_client);
+
+                    poll.registry().register(
+                        &mut control_clients
+                            .get_mut(control_client_index)
+                            .unwrap()
+                            .connection,
+                        Token(CONTROL_CLIENT_TOKEN_BASE + control_client_index),
+                        Interest::READABLE,
+                    )?;
                 }
                 SERVER_TOKEN => {
                     let (connection, client_addr) = match tcp_listener.accept() {
@@ -158,6 +278,19 @@ fn main() -> Result<()> {
                         log_decoder: defmt_table.new_stream_decoder(),
                         buffer: VecDeque::with_capacity(1024),
                         color: (0xff, 0xff, 0xff),
+                        firmware_update: None,
+                        pending_write: VecDeque::new(),
+                        bytes_in: 0,
+                        bytes_out: 0,
+                        rate_in: 0.0,
+                        rate_out: 0.0,
+                        window_bytes_in: 0,
+                        window_bytes_out: 0,
+                        window_start: Instant::now(),
+                        max_bytes_out_per_window: max_bytes_per_sec,
+                        encryption: None,
+                        last_seen: Instant::now(),
+                        last_ping_sent: None,
                     };
 
                     let client_index = clients.insert(client);
@@ -168,11 +301,87 @@ fn main() -> Result<()> {
                         Interest::READABLE,
                     )?;
                 }
+                token if token.0 >= CONTROL_CLIENT_TOKEN_BASE => {
+                    let control_client_index = token.0 - CONTROL_CLIENT_TOKEN_BASE;
+                    let control_client = control_clients
+                        .get_mut(control_client_index)
+                        .context("control client token not in list")?;
+
+                    let n = match control_client.connection.read(&mut receive_buffer) {
+                        Ok(0) => {
+                            control_clients
+                                .try_remove(control_client_index)
+                                .context("could not remove control client: token not in list")?;
+                            continue;
+                        }
+                        Ok(n) => n,
+                        Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => continue,
+                        Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+                        Err(err) => {
+                            println!("could not read from control socket: {}", err);
+                            let _ = control_client.connection.shutdown(std::net::Shutdown::Both);
+                            continue;
+                        }
+                    };
+
+                    control_client.buffer.extend(&receive_buffer[..n]);
+
+                    while let Some(newline_index) =
+                        control_client.buffer.iter().position(|&b| b == b'\n')
+                    {
+                        let line: Vec<u8> = control_client.buffer.drain(..=newline_index).collect();
+                        let line = &line[..line.len() - 1];
+
+                        let outcome = match std::str::from_utf8(line)
+                            .ok()
+                            .and_then(|line| serde_json::from_str::<ControlRequest>(line).ok())
+                        {
+                            Some(request) => control::run_command(
+                                &request.command,
+                                &request.argument,
+                                &mut clients,
+                                &binary,
+                            ),
+                            None => CommandOutcome::Error("could not parse request".to_string()),
+                        };
+
+                        let mut reply = serde_json::to_vec(&outcome)
+                            .expect("CommandOutcome is always serializable");
+                        reply.push(b'\n');
+                        if let Err(err) = control_client.connection.write_all(&reply) {
+                            println!("could not write control reply: {}", err);
+                        }
+                    }
+                }
                 client_token => {
                     let client = clients
                         .get_mut(client_token.0)
                         .context("client token not in list")?;
 
+                    if event.is_writable() {
+                        match client.flush_pending_write() {
+                            Ok(drained) => {
+                                let interest = if drained {
+                                    Interest::READABLE
+                                } else {
+                                    Interest::READABLE | Interest::WRITABLE
+                                };
+                                poll.registry()
+                                    .reregister(&mut client.connection, client_token, interest)?;
+                            }
+                            Err(err) => {
+                                println!("could not write to client {}: {}", client, err);
+                                let _ = client.connection.shutdown(std::net::Shutdown::Both);
+                                clients.try_remove(client_token.0);
+                                continue;
+                            }
+                        }
+                    }
+
+                    if !event.is_readable() {
+                        continue;
+                    }
+
                     let n = match client.connection.read(&mut receive_buffer) {
                         Ok(0) => {
                             // connection closed
@@ -194,6 +403,8 @@ fn main() -> Result<()> {
                         }
                     };
 
+                    client.last_seen = Instant::now();
+                    client.record_bytes_in(n);
                     client.buffer.extend(&receive_buffer[..n]);
 
                     if client.buffer.len() > 10_000 {
@@ -225,17 +436,39 @@ fn main() -> Result<()> {
                             }
                         };
 
-                        let message: Message =
-                            match ciborium::from_reader(&cobs_decoded[..n_decoded]) {
-                                Ok(message) => message,
+                        let frame = &cobs_decoded[..n_decoded];
+                        let plaintext = match &client.encryption {
+                            Some(cipher) => match cipher.open(frame) {
+                                Ok(plaintext) => plaintext,
                                 Err(err) => {
-                                    println!("could not decode CBOR object: {}", err);
-                                    continue;
+                                    println!(
+                                        "dropping unauthenticated frame from {}, disconnecting: {}",
+                                        client, err
+                                    );
+                                    let _ =
+                                        client.connection.shutdown(std::net::Shutdown::Both);
+                                    clients
+                                        .try_remove(client_token.0)
+                                        .context("could not remove client: token not in list")?;
+                                    continue 'events;
                                 }
-                            };
+                            },
+                            None => frame.to_vec(),
+                        };
+
+                        let message: Message = match ciborium::from_reader(plaintext.as_slice()) {
+                            Ok(message) => message,
+                            Err(err) => {
+                                println!("could not decode CBOR object: {}", err);
+                                continue;
+                            }
+                        };
 
                         if client.node_id.is_none()
-                            && !matches!(message, Message::Info { id: _, crc: _ })
+                            && !matches!(
+                                message,
+                                Message::Info { id: _, crc: _ } | Message::Hello { .. }
+                            )
                         {
                             println!("message from unknown client: {client}");
                         }
@@ -251,7 +484,7 @@ fn main() -> Result<()> {
                                 }
                                 last_log_activity = Some(now);
                                 if matches!(client.firmware_state, FirmwareState::Correct) {
-                                    client.decode_log_data(data, &mut event_writer);
+                                    client.decode_log_data(data, event_writer.as_mut());
                                 } else {
                                     println!(
                                         "log message from client with unknown firmware: {}",
@@ -293,8 +526,41 @@ fn main() -> Result<()> {
                                         client.identifier_str()
                                     );
                                 }
+
+                                if let (Some(mqtt_sink), Some(id)) = (&mut mqtt_sink, client.node_id)
+                                {
+                                    mqtt_sink.publish_firmware_state(
+                                        id,
+                                        client.color,
+                                        &client.firmware_state,
+                                    );
+                                }
+                            }
+                            Message::FwChunkAck { offset } => {
+                                if let Err(err) = client.handle_fw_chunk_ack(offset) {
+                                    println!(
+                                        "firmware update of {} failed: {}",
+                                        client.identifier_str(),
+                                        err
+                                    );
+                                }
+                            }
+                            Message::FwChunkNak { offset } => {
+                                if let Err(err) = client.handle_fw_chunk_nak(offset) {
+                                    println!(
+                                        "firmware update of {} failed: {}",
+                                        client.identifier_str(),
+                                        err
+                                    );
+                                }
+                            }
+                            Message::Error(ref msg) => {
+                                println!("Error from {}: {}", client, msg);
+                                if let (Some(mqtt_sink), Some(id)) = (&mut mqtt_sink, client.node_id)
+                                {
+                                    mqtt_sink.publish_error(id, msg);
+                                }
                             }
-                            Message::Error(ref msg) => println!("Error from {}: {}", client, msg),
                             Message::Halted(halted) => {
                                 client.halted = Some(halted);
                                 println!(
@@ -305,142 +571,228 @@ fn main() -> Result<()> {
                                         false => "running",
                                     }
                                 );
+                                if let (Some(mqtt_sink), Some(id)) = (&mut mqtt_sink, client.node_id)
+                                {
+                                    mqtt_sink.publish_halted(id, halted);
+                                }
+                            }
+                            // `client.last_seen` was already bumped above when the bytes arrived;
+                            // the echoed ping itself carries no further information.
+                            Message::Ping => {}
+                            Message::Hello { request_encryption } => {
+                                // A server running with a PSK must not let the client talk it out
+                                // of encryption: `request_encryption` is read off the wire before
+                                // any cipher is installed, so an on-path attacker could otherwise
+                                // flip it to `false` and downgrade the whole session to plaintext.
+                                if server_cipher.is_some() && !request_encryption {
+                                    println!(
+                                        "{} did not negotiate encrypted transport but server requires it, disconnecting",
+                                        client
+                                    );
+                                    let _ =
+                                        client.connection.shutdown(std::net::Shutdown::Both);
+                                    clients
+                                        .try_remove(client_token.0)
+                                        .context("could not remove client: token not in list")?;
+                                    continue 'events;
+                                }
+                                let encryption = request_encryption && server_cipher.is_some();
+                                // `HelloAck` is always the last plaintext frame: send it before
+                                // switching this client over, so the device can rely on that to
+                                // know when to start expecting sealed frames itself.
+                                if let Err(err) =
+                                    client.send_message(Message::HelloAck { encryption })
+                                {
+                                    println!("could not send hello-ack to {}: {}", client, err);
+                                }
+                                client.encryption = if encryption {
+                                    server_cipher.clone()
+                                } else {
+                                    None
+                                };
+                                println!(
+                                    "{} {} encrypted transport",
+                                    client,
+                                    if encryption { "negotiated" } else { "did not negotiate" }
+                                );
                             }
                             _ => println!("unhandled msg received: {:?}", &message),
                         }
                     }
+
+                    // handling the messages above (e.g. an ack queuing the next firmware chunk)
+                    // may have left bytes in `pending_write`; make sure we get a writable event for
+                    // them instead of waiting on the next unrelated read.
+                    if !client.pending_write.is_empty() {
+                        poll.registry().reregister(
+                            &mut client.connection,
+                            client_token,
+                            Interest::READABLE | Interest::WRITABLE,
+                        )?;
+                    }
                 }
             }
         }
     }
 }
 
-fn handle_command(input: &str, clients: &mut Slab<Client>, binary: &[u8]) -> Result<()> {
-    let (command, argument) = match input.split_once(' ') {
-        Some((cmd, arg)) => (cmd, arg),
-        None => (input, ""),
-    };
-    match command.to_lowercase().as_str() {
-        "help" | "?" => {
-            println!(
-                "
-List of commands:
-
-  help | ?
-    print this message
-
-  [l]ist
-    list connected nodes
+/// Ping `Correct`-firmware clients that have gone quiet for `HEARTBEAT_INTERVAL`, disconnect any
+/// client (regardless of firmware state) that hasn't sent a byte in `CLIENT_TIMEOUT`, and roll
+/// every client's throughput-accounting window every `RATE_WINDOW` (also resuming any write that a
+/// rate limit had deferred, since refilling the budget doesn't by itself generate a new mio
+/// writable event). Returns how long until the next such deadline, so `main`'s `poll.poll` timeout
+/// gets recomputed every iteration instead of blocking forever and missing it.
+fn sweep_clients(
+    poll: &Poll,
+    clients: &mut Slab<Client>,
+    now: Instant,
+    last_throughput_summary: &mut Instant,
+) -> Result<Option<Duration>> {
+    fn soonest(current: Option<Duration>, candidate: Duration) -> Option<Duration> {
+        Some(current.map_or(candidate, |current| current.min(candidate)))
+    }
 
-  [fwu]pdate
-    update all nodes that run incorrect firmware
+    let mut next_wakeup = Some(RATE_WINDOW);
+    let mut timed_out = Vec::new();
+    let mut write_failed = Vec::new();
 
-  [h]alt (INDEX|all)
-    halt node with index INDEX or all nodes
+    for (index, client) in clients.iter_mut().enumerate() {
+        let Some(client) = client else { continue };
 
-  [r]eset (INDEX|all)
-    reset node with index INDEX or all nodes
-"
-            );
+        let since_last_seen = now.saturating_duration_since(client.last_seen);
+        if since_last_seen >= CLIENT_TIMEOUT {
+            timed_out.push(index);
+            continue;
         }
-        "list" | "l" => {
-            println!(
-                "
-| {:10} | {:10} | {:10} | {:10} | {:22} |
-|------------+------------+------------+------------+------------------------|",
-                "index", "id", "halted", "firmware", "address"
-            );
-            for (index, client) in clients.iter().flatten().enumerate() {
-                if client.node_id.is_none() {
+        next_wakeup = soonest(next_wakeup, CLIENT_TIMEOUT - since_last_seen);
+
+        client.update_rate_window(now, RATE_WINDOW);
+        if !client.pending_write.is_empty() {
+            match client.flush_pending_write() {
+                Ok(drained) => {
+                    let interest = if drained {
+                        Interest::READABLE
+                    } else {
+                        Interest::READABLE | Interest::WRITABLE
+                    };
+                    poll.registry()
+                        .reregister(&mut client.connection, Token(index), interest)?;
+                }
+                Err(err) => {
+                    println!("could not write to {}: {}", client, err);
+                    write_failed.push(index);
                     continue;
                 }
-                println!(
-                    "| {:<10} | {:10} | {:10} | {:10} | {:22} |",
-                    index,
-                    client.identifier_str(),
-                    client.halted_as_string(),
-                    client.firmware_state.to_string(),
-                    match client.connection.peer_addr() {
-                        Ok(addr) => format!("{}", addr),
-                        Err(_) => "UNKNOWN".to_string(),
-                    }
-                );
             }
-            println!();
         }
-        "fwupdate" | "fwu" => {
-            for client in clients.iter_mut().flatten() {
-                if matches!(client.firmware_state, FirmwareState::Incorrect) {
-                    // TODO: this could be done in a separate thread
-                    println!("updating firmware of {}", client.identifier_str());
-                    if let Err(err) = client.update_firmware(binary) {
-                        println!(
-                            "could not update firmware of {}: {}",
-                            client.identifier_str(),
-                            err
-                        )
-                    };
-                }
-            }
+
+        if !matches!(client.firmware_state, FirmwareState::Correct) {
+            continue;
         }
-        "reset" | "r" => handle_reset_and_halt_command(true, argument, clients)?,
-        "halt" | "h" => handle_reset_and_halt_command(false, argument, clients)?,
-        "clear" | "c" => {
-            const CSI: &[u8] = b"\x1b[";
-            const CURSOR_HOME: &[u8] = b"H";
-            const ERASE_SCREEN: &[u8] = b"2J";
-            io::stdout().write_all(CSI).unwrap();
-            io::stdout().write_all(CURSOR_HOME).unwrap();
-            io::stdout().write_all(CSI).unwrap();
-            io::stdout().write_all(ERASE_SCREEN).unwrap();
+
+        let last_activity = client
+            .last_ping_sent
+            .map_or(client.last_seen, |sent| sent.max(client.last_seen));
+        let since_last_ping = now.saturating_duration_since(last_activity);
+        if since_last_ping < HEARTBEAT_INTERVAL {
+            next_wakeup = soonest(next_wakeup, HEARTBEAT_INTERVAL - since_last_ping);
+            continue;
         }
-        "" => {}
-        cmd => println!("unknown command: {cmd}"),
+
+        if let Err(err) = client.send_message(Message::Ping) {
+            println!("could not ping {}: {}", client, err);
+        }
+        client.last_ping_sent = Some(now);
+        next_wakeup = soonest(next_wakeup, HEARTBEAT_INTERVAL);
     }
 
-    // prompt
-    print!("> ");
-    io::stdout().flush().unwrap();
+    if now.saturating_duration_since(*last_throughput_summary) >= RATE_WINDOW {
+        print_throughput_summary(clients);
+        *last_throughput_summary = now;
+    }
 
-    Ok(())
-}
+    for index in write_failed {
+        if let Some(client) = clients.get_mut(index) {
+            let _ = client.connection.shutdown(std::net::Shutdown::Both);
+        }
+        clients.try_remove(index);
+    }
 
-fn handle_reset_and_halt_command(
-    reset: bool,
-    argument: &str,
-    clients: &mut Slab<Client>,
-) -> Result<()> {
-    if argument == "all" {
-        for client in clients.iter_mut().flatten() {
-            client
-                .send_message(if reset { Message::Reset } else { Message::Halt })
-                .context(format!(
-                    "could not {} {}",
-                    if reset { "reset" } else { "halt" },
-                    client
-                ))?;
+    for index in timed_out {
+        if let Some(client) = clients.get_mut(index) {
+            println!("client {} timed out, disconnecting", client);
+            let _ = client.connection.shutdown(std::net::Shutdown::Both);
         }
-    } else {
-        let index: usize =
-            str::parse(argument).context("index argument must be a decimal numeral or \"all\"")?;
-        let client = clients
-            .get_mut(index)
-            .context(format!("no node at index {}", index))?;
-        println!(
-            "{}ing {}",
-            if reset { "reset" } else { "halt" },
-            client.identifier_str()
-        );
-        client
-            .send_message(if reset { Message::Reset } else { Message::Halt })
-            .context(format!(
-                "could not {} {}",
-                if reset { "reset" } else { "halt" },
-                client
-            ))?;
+        clients.try_remove(index);
     }
 
-    Ok(())
+    Ok(next_wakeup)
+}
+
+/// Print a one-line aggregate throughput summary across all connected clients, roughly once per
+/// `RATE_WINDOW`; the per-client breakdown is available via the `list` command's `in`/`out`
+/// columns.
+fn print_throughput_summary(clients: &Slab<Client>) {
+    let mut total_rate_in = 0.0;
+    let mut total_rate_out = 0.0;
+    let mut n = 0;
+    for client in clients.iter().flatten() {
+        total_rate_in += client.rate_in;
+        total_rate_out += client.rate_out;
+        n += 1;
+    }
+    if n == 0 {
+        return;
+    }
+    println!(
+        "throughput: {} in, {} out across {} client{}",
+        format_rate(total_rate_in),
+        format_rate(total_rate_out),
+        n,
+        if n == 1 { "" } else { "s" }
+    );
+}
+
+fn clear_screen() {
+    const CSI: &[u8] = b"\x1b[";
+    const CURSOR_HOME: &[u8] = b"H";
+    const ERASE_SCREEN: &[u8] = b"2J";
+    io::stdout().write_all(CSI).unwrap();
+    io::stdout().write_all(CURSOR_HOME).unwrap();
+    io::stdout().write_all(CSI).unwrap();
+    io::stdout().write_all(ERASE_SCREEN).unwrap();
+}
+
+/// Render a `CommandOutcome` the way the stdin console used to print it directly. A control-socket
+/// client gets the same outcome serialized as JSON instead; see the `token.0 >=
+/// CONTROL_CLIENT_TOKEN_BASE` branch above.
+fn print_outcome(outcome: &CommandOutcome) {
+    match outcome {
+        CommandOutcome::Help(text) => println!("{text}"),
+        CommandOutcome::Nodes(nodes) => {
+            println!(
+                "
+| {:10} | {:10} | {:10} | {:10} | {:22} | {:10} | {:10} |
+|------------+------------+------------+------------+------------------------+------------+------------|",
+                "index", "id", "halted", "firmware", "address", "in", "out"
+            );
+            for node in nodes {
+                println!(
+                    "| {:<10} | {:10} | {:10} | {:10} | {:22} | {:10} | {:10} |",
+                    node.index,
+                    node.id,
+                    node.halted,
+                    node.firmware,
+                    node.address,
+                    node.rate_in,
+                    node.rate_out
+                );
+            }
+            println!();
+        }
+        CommandOutcome::Ok => {}
+        CommandOutcome::Error(err) => println!("could not handle command: {err}"),
+    }
 }
 
 fn cobs_decode_from_iter<'a>(
@@ -0,0 +1,106 @@
+//  _____       ______   ____
+// |_   _|     |  ____|/ ____|  Institute of Embedded Systems
+//   | |  _ __ | |__  | (___    Zurich University of Applied Sciences
+//   | | | '_ \|  __|  \___ \   8401 Winterthur, Switzerland
+//  _| |_| | | | |____ ____) |
+// |_____|_| |_|______|_____/
+//
+// Copyright 2025 Institute of Embedded Systems at Zurich University of Applied Sciences.
+// All rights reserved.
+// SPDX-License-Identifier: MIT
+
+//! Publishes decoded protocol events and per-client state to an MQTT broker, so a fleet of nodes
+//! can be watched from a dashboard instead of only this terminal.
+//!
+//! `rumqttc::Client` drives its `Connection` from a background thread spawned in
+//! [`MqttEventSink::new`], so a slow or unreachable broker only stalls that thread, never this
+//! crate's `mio` poll loop in `main`. `Client` is a cheap, cloneable handle onto that connection,
+//! which is why [`MqttEventSink`] derives `Clone`: one clone is fanned into the same
+//! `protocol_event_writer::EventWriter` chain as the CSV writer (for decoded log lines), while
+//! `main`'s `match message` arms hold onto another to publish the richer per-client state those
+//! lines don't carry (color, firmware correctness, halted/running).
+
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use rumqttc::{Client, MqttOptions, QoS};
+
+use protocol_event_writer::{EventWriter, ProtocolEvent};
+
+use crate::client::FirmwareState;
+
+const MQTT_KEEP_ALIVE: Duration = Duration::from_secs(30);
+/// Retained: a dashboard that (re)connects after a node last reported in should still see its
+/// last known state instead of nothing until the node speaks again.
+const RETAIN: bool = true;
+
+/// Publishes decoded defmt log lines (via `EventWriter`, same as `ProtocolEventFileWriter`) and
+/// per-client state changes (via its own methods, called directly from `main`) to
+/// `lpwan/<node_id>/...` topics.
+#[derive(Clone)]
+pub struct MqttEventSink {
+    client: Client,
+}
+
+impl MqttEventSink {
+    /// Connects to the broker at `broker_addr` (`host:port`) under `client_id`.
+    pub fn new(broker_addr: &str, client_id: &str) -> Result<Self> {
+        let (host, port) = broker_addr
+            .rsplit_once(':')
+            .context("mqtt broker address must be host:port")?;
+        let mut options = MqttOptions::new(client_id, host, port.parse().context("invalid mqtt broker port")?);
+        options.set_keep_alive(MQTT_KEEP_ALIVE);
+
+        let (client, mut connection) = Client::new(options, 16);
+        thread::spawn(move || {
+            for notification in connection.iter() {
+                if let Err(err) = notification {
+                    println!("mqtt connection error: {err}");
+                }
+            }
+        });
+
+        Ok(Self { client })
+    }
+
+    fn publish(&mut self, topic: String, payload: String) {
+        if let Err(err) = self.client.publish(&topic, QoS::AtLeastOnce, RETAIN, payload) {
+            println!("could not publish to {topic}: {err}");
+        }
+    }
+
+    /// Publish `Message::Info`'s firmware-correctness outcome for `node_id`, alongside its
+    /// assigned dashboard color, on `lpwan/<node_id>/firmware_state`.
+    pub fn publish_firmware_state(&mut self, node_id: u32, color: (u8, u8, u8), state: &FirmwareState) {
+        self.publish(
+            format!("lpwan/{:08x}/firmware_state", node_id),
+            format!(r#"{{"color":[{},{},{}],"state":"{}"}}"#, color.0, color.1, color.2, state),
+        );
+    }
+
+    /// Publish `Message::Halted`'s run/halt state for `node_id` on `lpwan/<node_id>/halted`.
+    pub fn publish_halted(&mut self, node_id: u32, halted: bool) {
+        self.publish(format!("lpwan/{:08x}/halted", node_id), halted.to_string());
+    }
+
+    /// Publish `Message::Error`'s message for `node_id` on `lpwan/<node_id>/error`.
+    pub fn publish_error(&mut self, node_id: u32, message: &str) {
+        self.publish(format!("lpwan/{:08x}/error", node_id), message.to_string());
+    }
+}
+
+impl EventWriter for MqttEventSink {
+    /// Parse one decoded `$uptime;node_id;kind;content` defmt log line (see
+    /// `client::Client::decode_log_data`) and publish it as JSON on `lpwan/<node_id>/log`.
+    fn write_event(&mut self, event: &str) {
+        let Some(event) = ProtocolEvent::parse(event) else {
+            return;
+        };
+        let node_id = event.node_id;
+        let json = serde_json::to_string(&event).expect("ProtocolEvent is always serializable");
+        self.publish(format!("lpwan/{:08x}/log", node_id), json);
+    }
+
+    fn flush(&mut self) {}
+}
@@ -11,58 +11,82 @@
 
 //! Collection similar to https://docs.rs/slab/latest/slab/
 
+use std::num::NonZeroU32;
 use std::slice;
 
-pub struct Slab<T>(Vec<Option<T>>);
+enum Entry<T> {
+    /// Free slot, holding the index of the next free slot (or this slab's `next_free` sentinel,
+    /// i.e. `len()`, if this was the last one freed).
+    Vacant(usize),
+    Occupied(T),
+}
+
+/// Vec-backed free list: `insert`/`try_remove` are O(1), reusing freed slots via a `next_free`
+/// intrusive linked list threaded through the vacant entries.
+pub struct Slab<T> {
+    entries: Vec<Entry<T>>,
+    /// Index of the next free slot to hand out, or `entries.len()` if none is free (i.e. `insert`
+    /// must grow the vec).
+    next_free: usize,
+}
 
 impl<T> Slab<T> {
     pub fn new() -> Self {
-        Self(Vec::new())
+        Self {
+            entries: Vec::new(),
+            next_free: 0,
+        }
     }
 
     pub fn insert(&mut self, element: T) -> usize {
-        if let Some(index) = self.get_free_index() {
-            self.0[index] = Some(element);
-            index
+        let index = self.next_free;
+        if index < self.entries.len() {
+            self.next_free = match self.entries[index] {
+                Entry::Vacant(next_free) => next_free,
+                Entry::Occupied(_) => unreachable!("next_free must always point at a vacant slot"),
+            };
+            self.entries[index] = Entry::Occupied(element);
         } else {
-            self.0.push(Some(element));
-            self.0.len() - 1
+            self.entries.push(Entry::Occupied(element));
+            self.next_free = self.entries.len();
         }
-    }
-
-    /// Returns index of first free slot or None if all slots are occupied.
-    fn get_free_index(&self) -> Option<usize> {
-        for (index, slot) in self.0.iter().enumerate() {
-            if slot.is_none() {
-                return Some(index);
-            }
-        }
-        None
+        index
     }
 
     pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
-        self.0.get_mut(index)?.as_mut()
+        match self.entries.get_mut(index)? {
+            Entry::Occupied(value) => Some(value),
+            Entry::Vacant(_) => None,
+        }
     }
 
     #[allow(dead_code)]
     pub fn get(&mut self, index: usize) -> Option<&T> {
-        self.0.get(index)?.as_ref()
+        match self.entries.get(index)? {
+            Entry::Occupied(value) => Some(value),
+            Entry::Vacant(_) => None,
+        }
     }
 
     pub fn try_remove(&mut self, index: usize) -> Option<T> {
-        if index < self.0.len() {
-            self.0.get_mut(index)?.take()
-        } else {
-            None
+        let entry = self.entries.get_mut(index)?;
+        if matches!(entry, Entry::Vacant(_)) {
+            return None;
+        }
+        let removed = std::mem::replace(entry, Entry::Vacant(self.next_free));
+        self.next_free = index;
+        match removed {
+            Entry::Occupied(value) => Some(value),
+            Entry::Vacant(_) => unreachable!("already checked for Vacant above"),
         }
     }
 
-    pub fn iter(&self) -> slice::Iter<'_, Option<T>> {
-        self.0.iter()
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter(self.entries.iter())
     }
 
-    pub fn iter_mut(&mut self) -> slice::IterMut<'_, Option<T>> {
-        self.0.iter_mut()
+    pub fn iter_mut(&mut self) -> IterMut<'_, T> {
+        IterMut(self.entries.iter_mut())
     }
 }
 
@@ -72,9 +96,37 @@ impl<T> Default for Slab<T> {
     }
 }
 
+/// Yields `Option<&T>` like the old `Vec<Option<T>>`-backed `Slab` did, so existing
+/// `.iter().flatten()` call sites keep working unchanged.
+pub struct Iter<'a, T>(slice::Iter<'a, Entry<T>>);
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = Option<&'a T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|entry| match entry {
+            Entry::Occupied(value) => Some(value),
+            Entry::Vacant(_) => None,
+        })
+    }
+}
+
+pub struct IterMut<'a, T>(slice::IterMut<'a, Entry<T>>);
+
+impl<'a, T> Iterator for IterMut<'a, T> {
+    type Item = Option<&'a mut T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|entry| match entry {
+            Entry::Occupied(value) => Some(value),
+            Entry::Vacant(_) => None,
+        })
+    }
+}
+
 impl<'a, T> IntoIterator for &'a Slab<T> {
-    type Item = &'a Option<T>;
-    type IntoIter = slice::Iter<'a, Option<T>>;
+    type Item = Option<&'a T>;
+    type IntoIter = Iter<'a, T>;
 
     fn into_iter(self) -> Self::IntoIter {
         self.iter()
@@ -82,14 +134,123 @@ impl<'a, T> IntoIterator for &'a Slab<T> {
 }
 
 impl<'a, T> IntoIterator for &'a mut Slab<T> {
-    type Item = &'a mut Option<T>;
-    type IntoIter = slice::IterMut<'a, Option<T>>;
+    type Item = Option<&'a mut T>;
+    type IntoIter = IterMut<'a, T>;
 
     fn into_iter(self) -> Self::IntoIter {
         self.iter_mut()
     }
 }
 
+/// Key into a `GenerationalSlab`: pairs a slot index with the generation it was inserted at, so a
+/// stale key from a slot that has since been removed and reused is rejected by `get`/`get_mut`
+/// instead of silently returning the new occupant. `generation` is `NonZeroU32` so `Option<Key>`
+/// is the same size as `Key`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Key {
+    index: usize,
+    generation: NonZeroU32,
+}
+
+enum GenSlot<T> {
+    Vacant(usize),
+    Occupied(T),
+}
+
+/// Like `Slab`, but `insert` returns a `Key` carrying a generation counter, and `get`/`get_mut`
+/// reject a `Key` whose generation doesn't match the slot's current occupant.
+#[allow(dead_code)]
+pub struct GenerationalSlab<T> {
+    entries: Vec<GenSlot<T>>,
+    /// Current generation of each slot, in lockstep with `entries`. Bumped on `try_remove`, so a
+    /// `Key` handed out before the removal no longer matches.
+    generations: Vec<NonZeroU32>,
+    next_free: usize,
+}
+
+const FIRST_GENERATION: NonZeroU32 = NonZeroU32::MIN;
+
+/// Bump a slot's generation, wrapping past `u32::MAX` back to `FIRST_GENERATION` instead of 0 so
+/// it stays a valid `NonZeroU32`.
+fn next_generation(generation: NonZeroU32) -> NonZeroU32 {
+    NonZeroU32::new(generation.get().wrapping_add(1)).unwrap_or(FIRST_GENERATION)
+}
+
+#[allow(dead_code)]
+impl<T> GenerationalSlab<T> {
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            generations: Vec::new(),
+            next_free: 0,
+        }
+    }
+
+    pub fn insert(&mut self, element: T) -> Key {
+        let index = self.next_free;
+        if index < self.entries.len() {
+            self.next_free = match self.entries[index] {
+                GenSlot::Vacant(next_free) => next_free,
+                GenSlot::Occupied(_) => {
+                    unreachable!("next_free must always point at a vacant slot")
+                }
+            };
+            self.entries[index] = GenSlot::Occupied(element);
+        } else {
+            self.entries.push(GenSlot::Occupied(element));
+            self.generations.push(FIRST_GENERATION);
+            self.next_free = self.entries.len();
+        }
+        Key {
+            index,
+            generation: self.generations[index],
+        }
+    }
+
+    pub fn get(&self, key: Key) -> Option<&T> {
+        if self.generations.get(key.index).copied() != Some(key.generation) {
+            return None;
+        }
+        match self.entries.get(key.index)? {
+            GenSlot::Occupied(value) => Some(value),
+            GenSlot::Vacant(_) => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, key: Key) -> Option<&mut T> {
+        if self.generations.get(key.index).copied() != Some(key.generation) {
+            return None;
+        }
+        match self.entries.get_mut(key.index)? {
+            GenSlot::Occupied(value) => Some(value),
+            GenSlot::Vacant(_) => None,
+        }
+    }
+
+    pub fn try_remove(&mut self, key: Key) -> Option<T> {
+        if self.generations.get(key.index).copied() != Some(key.generation) {
+            return None;
+        }
+        let entry = self.entries.get_mut(key.index)?;
+        if matches!(entry, GenSlot::Vacant(_)) {
+            return None;
+        }
+        let removed = std::mem::replace(entry, GenSlot::Vacant(self.next_free));
+        self.next_free = key.index;
+        self.generations[key.index] = next_generation(key.generation);
+        match removed {
+            GenSlot::Occupied(value) => Some(value),
+            GenSlot::Vacant(_) => unreachable!("already checked for Vacant above"),
+        }
+    }
+}
+
+impl<T> Default for GenerationalSlab<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -104,4 +265,31 @@ mod tests {
         assert_eq!(slab.get(i1), Some(1).as_ref());
         assert_eq!(slab.get_mut(i2), Some(2).as_mut());
     }
+
+    #[test]
+    fn reuses_freed_slots() {
+        let mut slab = Slab::new();
+
+        let i1 = slab.insert(1);
+        let i2 = slab.insert(2);
+        slab.try_remove(i1);
+        let i3 = slab.insert(3);
+
+        assert_eq!(i3, i1);
+        assert_eq!(slab.get(i2), Some(2).as_ref());
+        assert_eq!(slab.get(i3), Some(3).as_ref());
+    }
+
+    #[test]
+    fn generational_key_rejects_stale_index() {
+        let mut slab = GenerationalSlab::new();
+
+        let k1 = slab.insert(1);
+        slab.try_remove(k1).unwrap();
+        let k2 = slab.insert(2);
+
+        assert_eq!(k2.index, k1.index);
+        assert_eq!(slab.get(k1), None);
+        assert_eq!(slab.get(k2), Some(2).as_ref());
+    }
 }
@@ -0,0 +1,183 @@
+//  _____       ______   ____
+// |_   _|     |  ____|/ ____|  Institute of Embedded Systems
+//   | |  _ __ | |__  | (___    Zurich University of Applied Sciences
+//   | | | '_ \|  __|  \___ \   8401 Winterthur, Switzerland
+//  _| |_| | | | |____ ____) |
+// |_____|_| |_|______|_____/
+//
+// Copyright 2025 Institute of Embedded Systems at Zurich University of Applied Sciences.
+// All rights reserved.
+// SPDX-License-Identifier: MIT
+
+//! Class-A LoRaWAN endpoint mode, selected at boot instead of the Lightning mesh.
+//!
+//! Gives the framework a standards-compliant baseline to benchmark Lightning against on identical
+//! hardware: same SX126x radio and board, driven by the `lorawan-device` async stack instead of
+//! our own state machine.
+//!
+//! TODO: the exact `radio::PhyRxTx` trait surface (method names/signatures for `tx`/`setup_rx`/
+//! `rx`) depends on the `lorawan-device` version pinned in Cargo.toml, which does not exist in
+//! this tree yet; adjust `Sx126xPhy` to match once the dependency is added.
+
+#[allow(unused_imports)]
+use defmt::{debug, error, info, warn};
+use embassy_time::{Delay, Duration, Timer};
+use lora_phy::{mod_traits::RadioKind, LoRa};
+use lorawan::default_crypto::DefaultFactory;
+use lorawan_device::{
+    async_device::{radio, Device, JoinResponse},
+    region, AppEui, AppKey, DevEui,
+};
+use rand_core::RngCore;
+
+use crate::{APP_KEY, DEVEUI, JOIN_EUI, MAX_MESSAGE_SIZE};
+
+/// RX1 delay after an uplink, before RX2 opens (EU868 default, no prior `RXTimingSetupReq`).
+const RX1_DELAY: Duration = Duration::from_secs(1);
+
+/// Adapts the already-constructed `LoRa` driver to `lorawan_device`'s radio interface.
+///
+/// Reuses the same SX126x instance the Lightning mesh would otherwise drive, so switching modes at
+/// boot does not require different wiring or board support code.
+pub(crate) struct Sx126xPhy<'a, RK, DLY>
+where
+    RK: RadioKind,
+    DLY: lora_phy::DelayNs,
+{
+    lora: &'a mut LoRa<RK, DLY>,
+    /// Packet params from the most recent `setup_rx`, needed again once `rx` actually reads.
+    rx_pkt_params: Option<lora_phy::mod_params::PacketParams>,
+}
+
+impl<'a, RK, DLY> Sx126xPhy<'a, RK, DLY>
+where
+    RK: RadioKind,
+    DLY: lora_phy::DelayNs,
+{
+    pub(crate) fn new(lora: &'a mut LoRa<RK, DLY>) -> Self {
+        Self {
+            lora,
+            rx_pkt_params: None,
+        }
+    }
+}
+
+impl<RK, DLY> radio::PhyRxTx for Sx126xPhy<'_, RK, DLY>
+where
+    RK: RadioKind,
+    DLY: lora_phy::DelayNs,
+{
+    type PhyError = lora_phy::mod_params::RadioError;
+
+    async fn tx(&mut self, config: radio::TxConfig, buffer: &[u8]) -> Result<u32, Self::PhyError> {
+        let modulation_params = self.lora.create_modulation_params(
+            config.rf.datarate.spreading_factor(),
+            config.rf.datarate.bandwidth(),
+            region::Frequency::coding_rate(),
+            config.rf.frequency,
+        )?;
+        let mut tx_pkt_params = self.lora.create_tx_packet_params(
+            8,
+            false,
+            true,
+            false,
+            &modulation_params,
+        )?;
+        self.lora
+            .prepare_for_tx(&modulation_params, &mut tx_pkt_params, config.pw.into(), buffer)
+            .await?;
+        self.lora.tx().await?;
+        Ok(0)
+    }
+
+    async fn setup_rx(&mut self, config: radio::RfConfig) -> Result<(), Self::PhyError> {
+        let modulation_params = self.lora.create_modulation_params(
+            config.datarate.spreading_factor(),
+            config.datarate.bandwidth(),
+            region::Frequency::coding_rate(),
+            config.frequency,
+        )?;
+        let rx_pkt_params = self.lora.create_rx_packet_params(
+            8,
+            false,
+            MAX_MESSAGE_SIZE as u8,
+            true,
+            false,
+            &modulation_params,
+        )?;
+        self.lora
+            .prepare_for_rx(lora_phy::RxMode::Single(0), &modulation_params, &rx_pkt_params)
+            .await?;
+        self.rx_pkt_params = Some(rx_pkt_params);
+        Ok(())
+    }
+
+    async fn rx(&mut self, buffer: &mut [u8]) -> Result<(usize, radio::RxQuality), Self::PhyError> {
+        let rx_pkt_params = self
+            .rx_pkt_params
+            .as_ref()
+            .expect("rx called without a preceding setup_rx");
+        let (len, status) = self.lora.rx(rx_pkt_params, buffer).await?;
+        Ok((
+            len as usize,
+            radio::RxQuality::new(status.rssi, status.snr as i8),
+        ))
+    }
+}
+
+/// Run the Class-A LoRaWAN join+uplink loop instead of the Lightning mesh.
+///
+/// `next_payload` yields the next application payload (mirrors `Lightning::set_payload`'s counter).
+pub(crate) async fn run<RK, DLY>(
+    lora: &mut LoRa<RK, DLY>,
+    rng: impl RngCore + Clone,
+    mut next_payload: impl FnMut() -> u16,
+) -> !
+where
+    RK: RadioKind,
+    DLY: lora_phy::DelayNs,
+{
+    let radio = Sx126xPhy::new(lora);
+    let mut device: Device<_, DefaultFactory, _, _> = Device::new(
+        region::Configuration::new(region::Region::EU868),
+        radio,
+        Delay,
+        rng,
+    );
+
+    loop {
+        match device
+            .join(&lorawan_device::async_device::JoinMode::OTAA {
+                deveui: DevEui::from(DEVEUI),
+                appeui: AppEui::from(JOIN_EUI),
+                appkey: AppKey::from(APP_KEY),
+            })
+            .await
+        {
+            Ok(JoinResponse::JoinSuccess) => {
+                info!("LoRaWAN: joined network");
+                break;
+            }
+            Ok(JoinResponse::NoJoinAccept) => {
+                warn!("LoRaWAN: join rejected, retrying");
+            }
+            Err(err) => {
+                warn!("LoRaWAN: join error: {:?}", defmt::Debug2Format(&err));
+            }
+        }
+        Timer::after(RX1_DELAY).await;
+    }
+
+    loop {
+        let payload = next_payload().to_le_bytes();
+        match device.send(&payload, 1, false).await {
+            Ok(Some(downlink)) => {
+                info!("LoRaWAN: downlink: {:?}", downlink.data());
+            }
+            Ok(None) => {}
+            Err(err) => {
+                warn!("LoRaWAN: uplink error: {:?}", defmt::Debug2Format(&err));
+            }
+        }
+    }
+}
@@ -22,6 +22,9 @@ use embassy_executor::Spawner;
 use panic_probe as _;
 
 mod iv;
+#[cfg(feature = "lorawan")]
+mod lorawan_mode;
+mod phy;
 
 #[allow(unused_imports)]
 use defmt::{dbg, debug, error, info, panic, warn};
@@ -31,12 +34,13 @@ use embassy_time::{Delay, Duration, Instant, Timer};
 use heapless::Vec;
 use lora_modulation::BaseBandModulationParams;
 use lora_phy::{
-    mod_params::{Bandwidth, CodingRate, ModulationParams, SpreadingFactor, *},
+    mod_params::{Bandwidth, CadParams, CodingRate, ExitMode, ModulationParams, SpreadingFactor, *},
     mod_traits::RadioKind,
     sx126x::{self, Stm32wl, Sx126x},
     LoRa, RxMode,
 };
 use postcard::{from_bytes, to_vec};
+use rand_core::RngCore;
 
 // for log-serial
 #[allow(unused_imports)]
@@ -50,6 +54,8 @@ use static_cell::StaticCell;
 use lightning::{self, Lightning, Message, OwnAndChildData};
 use protocol_api::*;
 
+use phy::PhyMode;
+
 // TODO: add checksum to messages to detect transmission errors
 
 /// The first 32bits of the UID64 is a unique (among stm32wl5x devices) device number
@@ -73,6 +79,51 @@ const LORA_USE_HIGH_POWER_PA: bool = false;
 /// Packets with lower RSSI than this value get ignored.
 const MIN_RSSI: i16 = -70;
 
+/// Maximum number of carrier sense attempts before giving up on a transmit slot.
+const MAX_CARRIER_SENSE_RETRIES: u8 = 5;
+/// Base backoff delay; grows exponentially with each retry.
+const CARRIER_SENSE_BACKOFF_BASE_MS: u32 = 10;
+
+// TODO: move these parameters elsewhere to make them configurable by the application
+#[cfg(feature = "lorawan")]
+const DEVEUI: [u8; 8] = [0, 0, 0, 0, 0, 0, 0, 0];
+#[cfg(feature = "lorawan")]
+const JOIN_EUI: [u8; 8] = [0, 0, 0, 0, 0, 0, 0, 0];
+#[cfg(feature = "lorawan")]
+const APP_KEY: [u8; 16] = [0; 16];
+
+/// Number of CAD symbols to sample, scaled with `LORA_SPREADING_FACTOR`.
+const fn cad_symbol_count(sf: SpreadingFactor) -> NbCadSymbol {
+    match sf {
+        SpreadingFactor::_5 | SpreadingFactor::_6 | SpreadingFactor::_7 => NbCadSymbol::_2,
+        SpreadingFactor::_8 | SpreadingFactor::_9 => NbCadSymbol::_4,
+        SpreadingFactor::_10 | SpreadingFactor::_11 | SpreadingFactor::_12 => NbCadSymbol::_8,
+    }
+}
+
+/// CAD detection peak/min thresholds, tuned per spreading factor.
+const fn cad_thresholds(sf: SpreadingFactor) -> (u8, u8) {
+    // (det_peak, det_min), taken from the SX126x application note recommendations
+    match sf {
+        SpreadingFactor::_5 | SpreadingFactor::_6 => (21, 10),
+        SpreadingFactor::_7 | SpreadingFactor::_8 => (22, 10),
+        SpreadingFactor::_9 | SpreadingFactor::_10 => (23, 10),
+        SpreadingFactor::_11 | SpreadingFactor::_12 => (24, 10),
+    }
+}
+
+/// Convert an ADR-recommended spreading factor (7..=12) to the radio's `SpreadingFactor`.
+const fn spreading_factor_from_adr(sf: u8) -> SpreadingFactor {
+    match sf {
+        7 => SpreadingFactor::_7,
+        8 => SpreadingFactor::_8,
+        9 => SpreadingFactor::_9,
+        10 => SpreadingFactor::_10,
+        11 => SpreadingFactor::_11,
+        _ => SpreadingFactor::_12,
+    }
+}
+
 // https://www.ofcomnet.ch/api/rir/1008/44 fits 10 125khz channels with channel distance of
 // 125khz * 1.5: (865e6-863e6-125e3/2)/(125e3*1.5) ~= 10.33
 fn get_channel_frequency(n: u8) -> u32 {
@@ -80,10 +131,46 @@ fn get_channel_frequency(n: u8) -> u32 {
     863_000_000 + LORA_BANDWIDTH.hz() * (2 + 3 * n as u32) / 2
 }
 
-/// Required for calculating time on air
-#[allow(dead_code)]
-const LORA_PARAMS: BaseBandModulationParams =
-    BaseBandModulationParams::new(LORA_SPREADING_FACTOR, LORA_BANDWIDTH, LORA_CODING_RATE);
+/// One past the highest channel index accepted by `get_channel_frequency`.
+const NUM_PHYSICAL_CHANNELS: usize = 11;
+
+/// Regulatory duty-cycle limit applied to every sub-band, see ETSI EN 300 220.
+const DUTY_CYCLE_PERCENT: u32 = 1;
+
+/// Tracks per-sub-band duty-cycle state for ISM-band compliance.
+///
+/// Our channels are spaced closely enough that each one is its own regulatory sub-band, so
+/// sub-bands are tracked directly by channel index (derived from `get_channel_frequency`).
+struct DutyCycle {
+    next_allowed: [Option<Instant>; NUM_PHYSICAL_CHANNELS],
+    accumulated_airtime_us: [u64; NUM_PHYSICAL_CHANNELS],
+}
+
+impl DutyCycle {
+    fn new() -> Self {
+        Self {
+            next_allowed: [None; NUM_PHYSICAL_CHANNELS],
+            accumulated_airtime_us: [0; NUM_PHYSICAL_CHANNELS],
+        }
+    }
+
+    /// Time at which `channel`'s sub-band becomes available for transmission again.
+    fn next_allowed(&self, channel: u8) -> Option<Instant> {
+        self.next_allowed[channel as usize]
+    }
+
+    /// Record a transmission and schedule the resulting off-period.
+    fn record_transmit(&mut self, channel: u8, toa_us: u32) {
+        let off_period_us = toa_us as u64 * (100 / DUTY_CYCLE_PERCENT as u64 - 1);
+        self.accumulated_airtime_us[channel as usize] += toa_us as u64;
+        self.next_allowed[channel as usize] =
+            Some(Instant::now() + Duration::from_micros(off_period_us));
+    }
+
+    fn accumulated_airtime_us(&self, channel: u8) -> u64 {
+        self.accumulated_airtime_us[channel as usize]
+    }
+}
 
 bind_interrupts!(struct Irqs{
     SUBGHZ_RADIO => iv::InterruptHandler;
@@ -114,8 +201,12 @@ async fn main(_spawner: Spawner) {
     }
     let p = embassy_stm32::init(config);
 
-    // init pin early so it's stable when read
+    // init pins early so they're stable when read
     let is_sink_pin = gpio::Input::new(p.PB3, gpio::Pull::Up);
+    // pin low selects LoRaWAN endpoint mode over the Lightning mesh, matching the polarity of
+    // `is_sink_pin`
+    #[cfg(feature = "lorawan")]
+    let lorawan_mode_pin = gpio::Input::new(p.PB5, gpio::Pull::Up);
 
     #[cfg(feature = "log-serial")]
     {
@@ -145,6 +236,8 @@ async fn main(_spawner: Spawner) {
 
     node.is_sink = is_sink_pin.is_low();
 
+    let phy_mode = PhyMode::select();
+
     let mut rng = Rng::new(p.RNG, Irqs);
 
     let config = sx126x::Config {
@@ -162,16 +255,21 @@ async fn main(_spawner: Spawner) {
         .await
         .unwrap();
 
-    let modulation_params = get_modulation_params(&mut lora, 0);
+    #[cfg(feature = "lorawan")]
+    if lorawan_mode_pin.is_low() {
+        let mut n: u16 = 0;
+        lorawan_mode::run(&mut lora, rng, move || {
+            let payload = n;
+            n += 1;
+            payload
+        })
+        .await;
+    }
+
+    let modulation_params = get_modulation_params(&mut lora, &phy_mode, 0);
 
     let mut tx_pkt_params = {
-        match lora.create_tx_packet_params(
-            LORA_PREAMBLE_LEN,
-            LORA_IMPLICIT_HEADER,
-            LORA_CRC_ON,
-            LORA_IQ_INVERTED,
-            &modulation_params,
-        ) {
+        match phy_mode.tx_packet_params(&mut lora, &modulation_params) {
             Ok(pp) => pp,
             Err(err) => {
                 info!("radio error = {}", err);
@@ -183,14 +281,7 @@ async fn main(_spawner: Spawner) {
     let mut receive_buffer = [0u8; MAX_MESSAGE_SIZE];
 
     let rx_pkt_params = {
-        match lora.create_rx_packet_params(
-            LORA_PREAMBLE_LEN,
-            LORA_IMPLICIT_HEADER,
-            receive_buffer.len() as u8,
-            LORA_CRC_ON,
-            LORA_IQ_INVERTED,
-            &modulation_params,
-        ) {
+        match phy_mode.rx_packet_params(&mut lora, receive_buffer.len() as u8, &modulation_params) {
             Ok(pp) => pp,
             Err(err) => {
                 info!("radio error = {}", err);
@@ -199,8 +290,14 @@ async fn main(_spawner: Spawner) {
         }
     };
 
+    let mut duty_cycle = DutyCycle::new();
+
     let mut rx_msg = None;
+    let mut rx_snr_db = None;
     let mut n: u16 = 0;
+    // set when the previous loop iteration's `Action::Transmit` didn't make it onto the air, so
+    // the next `node.progress` call can be told instead of silently advancing as if it had sent
+    let mut transmit_failed = false;
     loop {
         if !node.has_payload() {
             node.set_payload(n);
@@ -208,19 +305,23 @@ async fn main(_spawner: Spawner) {
         }
 
         let mut now = Instant::now().as_millis();
-        let (action, uplink_data) = node.progress(now, rx_msg.take(), &mut rng);
+        let (action, uplink_data) = if transmit_failed {
+            transmit_failed = false;
+            // already the concrete `OwnAndChildData`, unlike `progress`'s opaque return below
+            node.notify_transmit_failed(now, &mut rng)
+        } else {
+            let (action, uplink_data) = node.progress(now, rx_msg.take(), rx_snr_db.take(), &mut rng);
+            (action, uplink_data.map(OwnAndChildData::from_iter))
+        };
         if let Some(uplink_data) = uplink_data {
-            info!(
-                "New uplink data: {}",
-                OwnAndChildData::from_iter(uplink_data)
-            );
+            info!("New uplink data: {}", uplink_data);
         }
         match action {
             Action::Wait { end } => {
                 Timer::at(Instant::from_millis(end)).await;
             }
             Action::Receive { end, channel } => {
-                let modulation_params = get_modulation_params(&mut lora, channel);
+                let modulation_params = get_modulation_params(&mut lora, &phy_mode, channel);
                 while end > now {
                     match lora_receive(
                         &mut lora,
@@ -231,9 +332,10 @@ async fn main(_spawner: Spawner) {
                     )
                     .await
                     {
-                        Ok(()) => match from_bytes(&receive_buffer) {
+                        Ok(snr) => match from_bytes(&receive_buffer) {
                             Ok(msg) => {
                                 rx_msg = Some(msg);
+                                rx_snr_db = Some(snr as i8);
                                 break;
                             }
                             Err(err) => warn!("could not de-serialize message: {:?}", err),
@@ -262,19 +364,77 @@ async fn main(_spawner: Spawner) {
                 message,
                 delay,
             } => {
+                if let Some(next_allowed) = duty_cycle.next_allowed(channel) {
+                    if next_allowed > Instant::now() {
+                        info!("sub-band {} busy (duty cycle), delaying transmit", channel);
+                        Timer::at(next_allowed).await;
+                    }
+                }
                 if let Some(delay_ms) = delay {
                     Timer::after_millis(delay_ms).await;
                 }
-                let modulation_params = get_modulation_params(&mut lora, channel);
+                // uplink messages (Connect/Data) carry this node's own ADR-negotiated SF/power;
+                // downlink messages (beacons/acks) still use the fixed defaults, since children
+                // share a single channel and the protocol only negotiates ADR per child so far
+                let adr = matches!(&message, Message::Connect { .. } | Message::Data { .. })
+                    .then(|| node.own_adr());
+                // the SF about to be used for this transmit: the per-hop ADR recommendation for
+                // uplinks, or the PHY's default otherwise. Threaded into both the airtime estimate
+                // below and `carrier_sense` (via `lora_transmit`), since both depend on actual
+                // airtime and a higher negotiated SF takes measurably longer on the air than the
+                // fixed default.
+                let spreading_factor = adr.map_or_else(
+                    || phy_mode.spreading_factor(),
+                    |adr| spreading_factor_from_adr(adr.spreading_factor),
+                );
+                let modulation_params = match adr {
+                    Some(_) => phy_mode.modulation_params_with_sf(
+                        &mut lora,
+                        get_channel_frequency(channel),
+                        spreading_factor,
+                    ),
+                    None => get_modulation_params(&mut lora, &phy_mode, channel),
+                };
+                let tx_power_dbm = adr.map_or(LORA_OUTPUT_POWER, |adr| adr.tx_power_dbm as i32);
                 let transmit_buffer: Vec<u8, MAX_MESSAGE_SIZE> = to_vec(&message).unwrap();
                 info!("transmitting {}", message);
-                lora_transmit(
+                if let Err(err) = lora_transmit(
                     &mut lora,
                     &mut tx_pkt_params,
                     &modulation_params,
+                    &phy_mode,
+                    channel,
+                    spreading_factor,
+                    tx_power_dbm,
                     transmit_buffer.as_slice(),
+                    &mut rng,
                 )
-                .await;
+                .await
+                {
+                    // channel stayed busy or the radio faulted; surfacing this (instead of the
+                    // previous silent `info!`) lets an operator notice a node that can never get
+                    // a clear slot. Tell the state machine on the next loop iteration so it
+                    // reschedules fire-and-forget sends instead of silently treating this as sent.
+                    warn!("transmit failed: {:?}", err);
+                    transmit_failed = true;
+                } else {
+                    let toa_us = BaseBandModulationParams::new(
+                        spreading_factor,
+                        LORA_BANDWIDTH,
+                        LORA_CODING_RATE,
+                    )
+                    .time_on_air_us(
+                        Some(LORA_PREAMBLE_LEN as u8),
+                        !LORA_IMPLICIT_HEADER,
+                        transmit_buffer.len() as u8,
+                    );
+                    duty_cycle.record_transmit(channel, toa_us);
+                    info!(
+                        "sub-band {} accumulated airtime: {} us",
+                        channel,
+                        duty_cycle.accumulated_airtime_us(channel)
+                    );
+                }
             }
             Action::None => {}
         }
@@ -285,50 +445,113 @@ async fn main(_spawner: Spawner) {
     // }
 }
 
-fn get_modulation_params<RK, DLY>(lora: &mut LoRa<RK, DLY>, channel: u8) -> ModulationParams
+fn get_modulation_params<RK, DLY>(
+    lora: &mut LoRa<RK, DLY>,
+    phy_mode: &PhyMode,
+    channel: u8,
+) -> ModulationParams
 where
     RK: RadioKind,
     DLY: lora_phy::DelayNs,
 {
-    lora.create_modulation_params(
-        LORA_SPREADING_FACTOR,
-        LORA_BANDWIDTH,
-        LORA_CODING_RATE,
-        get_channel_frequency(channel),
-    )
-    .unwrap()
+    phy_mode.modulation_params(lora, get_channel_frequency(channel))
+}
+
+/// Listen for activity on `channel` before transmitting.
+///
+/// Configures CAD with SF-dependent symbol count and thresholds, runs detection, and always
+/// returns the radio to standby afterwards. Returns `true` when activity was detected.
+///
+/// `spreading_factor` must be the SF the pending transmission will actually use (e.g. an
+/// ADR-negotiated one), not just the PHY's fixed default: CAD's symbol timing and detection
+/// thresholds are tuned per SF, and sensing at the wrong one risks missing activity on an airtime
+/// the real transmit would actually collide with.
+async fn carrier_sense<RK, DLY>(
+    lora: &mut LoRa<RK, DLY>,
+    phy_mode: &PhyMode,
+    channel: u8,
+    spreading_factor: SpreadingFactor,
+) -> Result<bool, RadioError>
+where
+    RK: RadioKind,
+    DLY: lora_phy::DelayNs,
+{
+    let modulation_params =
+        phy_mode.modulation_params_with_sf(lora, get_channel_frequency(channel), spreading_factor);
+    let (cad_det_peak, cad_det_min) = cad_thresholds(spreading_factor);
+    let cad_params = CadParams {
+        cad_symb_num: cad_symbol_count(spreading_factor),
+        cad_det_peak,
+        cad_det_min,
+        cad_exit_mode: ExitMode::Standby,
+        cad_timeout: 0,
+    };
+
+    if let Err(err) = lora.prepare_for_cad(&modulation_params, cad_params).await {
+        info!("radio error = {}", err);
+        return Err(err);
+    }
+
+    let activity_detected = match lora.cad().await {
+        Ok(detected) => detected,
+        Err(err) => {
+            info!("radio error = {}", err);
+            return Err(err);
+        }
+    };
+
+    if let Err(err) = lora.enter_standby().await {
+        error!("radio could not enter standby after CAD: {}", err);
+    }
+
+    Ok(activity_detected)
 }
 
 async fn lora_transmit<RK, DLY>(
     lora: &mut LoRa<RK, DLY>,
     tx_pkt_params: &mut PacketParams,
     modulation_params: &ModulationParams,
+    phy_mode: &PhyMode,
+    channel: u8,
+    spreading_factor: SpreadingFactor,
+    tx_power_dbm: i32,
     buffer: &[u8],
-) where
+    rng: &mut impl RngCore,
+) -> Result<(), TransmitError>
+where
     RK: RadioKind,
     DLY: lora_phy::DelayNs,
 {
-    // info!(
-    //     "time on air: {} us",
-    //     LORA_PARAMS.time_on_air_us(
-    //         Some(LORA_PREAMBLE_LEN as u8),
-    //         !LORA_IMPLICIT_HEADER,
-    //         buffer.len() as u8,
-    //     )
-    // );
+    let mut backoff_ms = CARRIER_SENSE_BACKOFF_BASE_MS;
+    for retry in 0..MAX_CARRIER_SENSE_RETRIES {
+        match carrier_sense(lora, phy_mode, channel, spreading_factor).await {
+            Ok(false) => break,
+            Ok(true) => {
+                info!("channel busy, backing off");
+                if retry + 1 == MAX_CARRIER_SENSE_RETRIES {
+                    return Err(TransmitError::ChannelBusy);
+                }
+                Timer::after_millis((rng.next_u32() % backoff_ms) as u64).await;
+                backoff_ms = backoff_ms.saturating_mul(2);
+            }
+            Err(_) => return Err(TransmitError::RadioError),
+        }
+    }
 
     if let Err(err) = lora
-        .prepare_for_tx(modulation_params, tx_pkt_params, LORA_OUTPUT_POWER, buffer)
+        .prepare_for_tx(modulation_params, tx_pkt_params, tx_power_dbm, buffer)
         .await
     {
         info!("radio error = {}", err);
-        return;
+        return Err(TransmitError::RadioError);
     };
 
-    // TODO: return error
     if let Err(err) = lora.tx().await {
         info!("radio error = {}", err);
+        return Err(TransmitError::RadioError);
     };
+
+    Ok(())
 }
 
 async fn lora_receive<RK, DLY>(
@@ -337,7 +560,7 @@ async fn lora_receive<RK, DLY>(
     modulation_params: &ModulationParams,
     buffer: &mut [u8; MAX_MESSAGE_SIZE],
     timeout: Duration,
-) -> Result<(), ReceiveError>
+) -> Result<i16, ReceiveError>
 where
     RK: RadioKind,
     DLY: lora_phy::DelayNs,
@@ -355,7 +578,7 @@ where
 
     *buffer = [00u8; MAX_MESSAGE_SIZE];
 
-    match embassy_time::with_timeout(timeout, lora.rx(rx_pkt_params, buffer)).await {
+    let snr = match embassy_time::with_timeout(timeout, lora.rx(rx_pkt_params, buffer)).await {
         Ok(rx) => match rx {
             Ok((_received_len, rx_pkt_status)) => {
                 info!(
@@ -365,6 +588,7 @@ where
                 if rx_pkt_status.rssi < MIN_RSSI {
                     Err(ReceiveError::InsufficientSignalStrength)?
                 }
+                rx_pkt_status.snr
             }
             Err(err) => {
                 info!("rx unsuccessful: {}", err);
@@ -374,9 +598,9 @@ where
         Err(_) => {
             Err(ReceiveError::Timeout)?;
         }
-    }
+    };
 
-    Ok(())
+    Ok(snr)
 }
 
 enum ReceiveError {
@@ -385,6 +609,13 @@ enum ReceiveError {
     Timeout,
 }
 
+#[derive(Debug)]
+enum TransmitError {
+    /// Channel stayed busy for `MAX_CARRIER_SENSE_RETRIES` carrier sense attempts
+    ChannelBusy,
+    RadioError,
+}
+
 // prevent panic messages from being printed twice when `defmt::panic` is invoked
 #[defmt::panic_handler]
 fn panic() -> ! {
@@ -0,0 +1,188 @@
+//  _____       ______   ____
+// |_   _|     |  ____|/ ____|  Institute of Embedded Systems
+//   | |  _ __ | |__  | (___    Zurich University of Applied Sciences
+//   | | | '_ \|  __|  \___ \   8401 Winterthur, Switzerland
+//  _| |_| | | | |____ ____) |
+// |_____|_| |_|______|_____/
+//
+// Copyright 2025 Institute of Embedded Systems at Zurich University of Applied Sciences.
+// All rights reserved.
+// SPDX-License-Identifier: MIT
+
+//! PHY-agnostic modulation configuration, fixed at boot by `PhyMode::select`, which always picks
+//! LoRa; no GPIO is read to choose the mode.
+
+use lora_phy::{
+    mod_params::{Bandwidth, CodingRate, ModulationParams, PacketParams, RadioError, SpreadingFactor},
+    mod_traits::RadioKind,
+    LoRa,
+};
+
+use crate::{
+    LORA_BANDWIDTH, LORA_CODING_RATE, LORA_CRC_ON, LORA_IMPLICIT_HEADER, LORA_IQ_INVERTED,
+    LORA_PREAMBLE_LEN, LORA_SPREADING_FACTOR,
+};
+
+/// LoRa modulation configuration, mirrors the constants used before PHY selection existed.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct LoRaConfig {
+    pub(crate) spreading_factor: SpreadingFactor,
+    pub(crate) bandwidth: Bandwidth,
+    pub(crate) coding_rate: CodingRate,
+}
+
+impl Default for LoRaConfig {
+    fn default() -> Self {
+        Self {
+            spreading_factor: LORA_SPREADING_FACTOR,
+            bandwidth: LORA_BANDWIDTH,
+            coding_rate: LORA_CODING_RATE,
+        }
+    }
+}
+
+/// Gaussian pulse-shaping BT product applied to (G)FSK symbols.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum GfskPulseShape {
+    None,
+    Bt0_3,
+    Bt0_5,
+    Bt0_7,
+    Bt1_0,
+}
+
+/// (G)FSK modulation configuration, mirrors the SX126x FSK parameter set.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct FskConfig {
+    pub(crate) bitrate_bps: u32,
+    pub(crate) frequency_deviation_hz: u32,
+    pub(crate) rx_bandwidth_hz: u32,
+    pub(crate) pulse_shape: GfskPulseShape,
+}
+
+impl Default for FskConfig {
+    fn default() -> Self {
+        Self {
+            bitrate_bps: 50_000,
+            frequency_deviation_hz: 25_000,
+            rx_bandwidth_hz: 100_000,
+            pulse_shape: GfskPulseShape::Bt0_5,
+        }
+    }
+}
+
+/// PHY-agnostic modulation mode, picked once at boot so the protocol logic above it (which only
+/// ever sees `Action`s and RSSI/SNR) stays unchanged across PHYs.
+///
+/// `Gfsk` is not yet reachable from [`select`](PhyMode::select): `lora_phy` doesn't expose a
+/// dedicated (G)FSK modulation/packet-params constructor for the sx126x driver this firmware
+/// uses, so there's no way to actually put the radio in FSK mode yet. The variant and
+/// [`FskConfig`] stay as the intended extension point for cross-PHY experiments once upstream
+/// grows that support, rather than being removed and rebuilt later.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum PhyMode {
+    LoRa(LoRaConfig),
+    #[allow(dead_code)]
+    Gfsk(FskConfig),
+}
+
+impl PhyMode {
+    /// The PHY configured for this boot. Always LoRa for now, see `PhyMode::Gfsk`'s doc comment.
+    pub(crate) fn select() -> Self {
+        PhyMode::LoRa(LoRaConfig::default())
+    }
+
+    pub(crate) fn modulation_params<RK, DLY>(
+        &self,
+        lora: &mut LoRa<RK, DLY>,
+        channel_frequency: u32,
+    ) -> ModulationParams
+    where
+        RK: RadioKind,
+        DLY: lora_phy::DelayNs,
+    {
+        self.modulation_params_with_sf(lora, channel_frequency, self.spreading_factor())
+    }
+
+    /// Like `modulation_params`, but overrides the spreading factor, e.g. with an ADR
+    /// recommendation. GFSK has no spreading factor, so the override only affects LoRa.
+    pub(crate) fn modulation_params_with_sf<RK, DLY>(
+        &self,
+        lora: &mut LoRa<RK, DLY>,
+        channel_frequency: u32,
+        spreading_factor: SpreadingFactor,
+    ) -> ModulationParams
+    where
+        RK: RadioKind,
+        DLY: lora_phy::DelayNs,
+    {
+        match self {
+            PhyMode::LoRa(cfg) => lora
+                .create_modulation_params(
+                    spreading_factor,
+                    cfg.bandwidth,
+                    cfg.coding_rate,
+                    channel_frequency,
+                )
+                .unwrap(),
+            // TODO: lora-phy does not yet expose a dedicated (G)FSK modulation-params
+            // constructor; route through the LoRa one until upstream grows the FSK-specific
+            // constructor mirrored by `FskConfig`, so the bitrate/deviation/bandwidth/pulse-shape
+            // fields are currently only recorded, not yet applied to the radio.
+            PhyMode::Gfsk(_cfg) => lora
+                .create_modulation_params(
+                    LORA_SPREADING_FACTOR,
+                    LORA_BANDWIDTH,
+                    LORA_CODING_RATE,
+                    channel_frequency,
+                )
+                .unwrap(),
+        }
+    }
+
+    /// Current spreading factor, or the fixed LoRa default for GFSK (which ignores it).
+    pub(crate) fn spreading_factor(&self) -> SpreadingFactor {
+        match self {
+            PhyMode::LoRa(cfg) => cfg.spreading_factor,
+            PhyMode::Gfsk(_) => LORA_SPREADING_FACTOR,
+        }
+    }
+
+    pub(crate) fn tx_packet_params<RK, DLY>(
+        &self,
+        lora: &mut LoRa<RK, DLY>,
+        modulation_params: &ModulationParams,
+    ) -> Result<PacketParams, RadioError>
+    where
+        RK: RadioKind,
+        DLY: lora_phy::DelayNs,
+    {
+        lora.create_tx_packet_params(
+            LORA_PREAMBLE_LEN,
+            LORA_IMPLICIT_HEADER,
+            LORA_CRC_ON,
+            LORA_IQ_INVERTED,
+            modulation_params,
+        )
+    }
+
+    pub(crate) fn rx_packet_params<RK, DLY>(
+        &self,
+        lora: &mut LoRa<RK, DLY>,
+        receive_buffer_len: u8,
+        modulation_params: &ModulationParams,
+    ) -> Result<PacketParams, RadioError>
+    where
+        RK: RadioKind,
+        DLY: lora_phy::DelayNs,
+    {
+        lora.create_rx_packet_params(
+            LORA_PREAMBLE_LEN,
+            LORA_IMPLICIT_HEADER,
+            receive_buffer_len,
+            LORA_CRC_ON,
+            LORA_IQ_INVERTED,
+            modulation_params,
+        )
+    }
+}
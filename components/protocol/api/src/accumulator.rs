@@ -0,0 +1,265 @@
+//  _____       ______   ____
+// |_   _|     |  ____|/ ____|  Institute of Embedded Systems
+//   | |  _ __ | |__  | (___    Zurich University of Applied Sciences
+//   | | | '_ \|  __|  \___ \   8401 Winterthur, Switzerland
+//  _| |_| | | | |____ ____) |
+// |_____|_| |_|______|_____/
+//
+// Copyright 2025 Institute of Embedded Systems at Zurich University of Applied Sciences.
+// All rights reserved.
+// SPDX-License-Identifier: MIT
+
+//! Merkle Mountain Range accumulator, so a sink can cryptographically verify the provenance and
+//! completeness of data aggregated up a tree of forwarding nodes.
+//!
+//! Leaves are hashed with a small FNV-1a 32-bit hash, not the stm32-bootloader-specific CRC in
+//! the cloud component's `crc.rs` (that lives in a separate, std-only binary downstream of this
+//! crate, so it isn't reachable here, and isn't a general-purpose hash anyway).
+//!
+//! An MMR is a forest of perfect binary trees ("peaks"), one per set bit of the number of leaves
+//! appended so far, kept as a `heapless::Vec` indexed from the oldest/tallest peak to the
+//! newest/shortest. Appending a leaf merges it into existing peaks of matching height,
+//! bottom-up, until a height with no peak is reached. The root is obtained by "bagging" the
+//! peaks: folding them pairwise from the newest/shortest leftward.
+
+use heapless::Vec;
+
+/// Something that can be hashed into an MMR leaf.
+pub trait Hashable {
+    fn hash_leaf(&self) -> u32;
+}
+
+const FNV_OFFSET_BASIS: u32 = 0x811c_9dc5;
+const FNV_PRIME: u32 = 0x0100_0193;
+
+/// FNV-1a over `bytes`. Used internally to combine two node hashes into their parent; also
+/// exposed so a `Hashable` implementation can hash its own byte representation with the same
+/// primitive instead of pulling in a second hash function.
+pub fn fnv1a(bytes: &[u8]) -> u32 {
+    bytes
+        .iter()
+        .fold(FNV_OFFSET_BASIS, |hash, &byte| (hash ^ byte as u32).wrapping_mul(FNV_PRIME))
+}
+
+fn hash_pair(left: u32, right: u32) -> u32 {
+    let mut bytes = [0u8; 8];
+    bytes[..4].copy_from_slice(&left.to_le_bytes());
+    bytes[4..].copy_from_slice(&right.to_le_bytes());
+    fnv1a(&bytes)
+}
+
+/// Fold `peaks` from the newest/shortest (the last entry) leftward into a single root.
+fn bag(peaks: &[(u8, u32)]) -> Option<u32> {
+    let (&(_, last), rest) = peaks.split_last()?;
+    Some(rest.iter().rev().fold(last, |acc, &(_, hash)| hash_pair(hash, acc)))
+}
+
+/// Append-only Merkle Mountain Range. `N` bounds the number of simultaneous peaks, i.e. this
+/// accumulator can hold up to `2^N - 1` leaves.
+#[derive(Debug, Clone)]
+pub struct Accumulator<const N: usize> {
+    /// `(height, hash)` peaks, ordered oldest/tallest-first to newest/shortest-last.
+    peaks: Vec<(u8, u32), N>,
+}
+
+impl<const N: usize> Default for Accumulator<N> {
+    fn default() -> Self {
+        Self { peaks: Vec::new() }
+    }
+}
+
+impl<const N: usize> Accumulator<N> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append a leaf, merging it with existing peaks of matching height bottom-up.
+    pub fn append(&mut self, leaf: &impl Hashable) {
+        let mut node = leaf.hash_leaf();
+        let mut height = 0u8;
+        while matches!(self.peaks.last(), Some(&(peak_height, _)) if peak_height == height) {
+            let (_, peak_hash) = self.peaks.pop().unwrap();
+            node = hash_pair(peak_hash, node);
+            height += 1;
+        }
+        // `N` peaks covers up to `2^N - 1` leaves, so this never runs out of room within that
+        // budget.
+        self.peaks.push((height, node)).ok().unwrap();
+    }
+
+    /// The accumulator root, or `None` if no leaf has been appended yet.
+    pub fn root(&self) -> Option<u32> {
+        bag(&self.peaks)
+    }
+}
+
+/// Rebuild an accumulator from `leaves` and return its root.
+pub fn root<const N: usize>(leaves: &[impl Hashable]) -> Option<u32> {
+    let mut accumulator = Accumulator::<N>::new();
+    for leaf in leaves {
+        accumulator.append(leaf);
+    }
+    accumulator.root()
+}
+
+/// Rebuild the accumulator from `leaves` and check that it matches `expected_root`, e.g. the root
+/// a sink received alongside the aggregated data it's meant to cover.
+pub fn verify_root<const N: usize>(leaves: &[impl Hashable], expected_root: u32) -> bool {
+    root::<N>(leaves) == Some(expected_root)
+}
+
+/// Which side of a merge a sibling hash was on, needed to redo the merge in the recorded order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+/// Proof that `leaves[leaf_index]` (not stored here, re-hashed by the verifier) is a member of
+/// the accumulator rooted at the `root` it was built against.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Proof<const N: usize> {
+    /// Height of the peak this leaf ends up in once its own siblings have been merged in.
+    leaf_height: u8,
+    /// Hashes merged with this leaf's node on its way up to its peak, leaf-to-peak order.
+    siblings: Vec<(u32, Side), N>,
+    /// The other peaks, excluding the one this leaf's path ends at, oldest/tallest-first.
+    other_peaks: Vec<(u8, u32), N>,
+}
+
+/// Build a membership proof for `leaves[leaf_index]` by replaying the same append cascade as
+/// `root`/`verify_root`, recording the sibling hash (and which side it was on) at each merge that
+/// involves this leaf's running node — including merges that happen while appending later
+/// leaves, once this leaf's node has settled into a peak. Returns `None` if `leaf_index` is out
+/// of bounds.
+pub fn prove<const N: usize>(leaves: &[impl Hashable], leaf_index: usize) -> Option<Proof<N>> {
+    if leaf_index >= leaves.len() {
+        return None;
+    }
+
+    let mut peaks: Vec<(u8, u32), N> = Vec::new();
+    let mut siblings: Vec<(u32, Side), N> = Vec::new();
+    let mut leaf_height = 0u8;
+
+    for (index, leaf) in leaves.iter().enumerate() {
+        let mut node = leaf.hash_leaf();
+        let mut height = 0u8;
+        // Whether `node` is (part of) the leaf we're proving membership for.
+        let mut is_our_node = index == leaf_index;
+
+        while matches!(peaks.last(), Some(&(peak_height, _)) if peak_height == height) {
+            let (_, peak_hash) = peaks.pop().unwrap();
+            if is_our_node {
+                // `peak_hash` is an older peak merging in from our left.
+                siblings.push((peak_hash, Side::Left)).ok()?;
+            } else if index > leaf_index && leaf_height == height {
+                // our previously-settled peak is being consumed by this later leaf's cascade;
+                // `node` (not yet merged) is the sibling, approaching from our right.
+                siblings.push((node, Side::Right)).ok()?;
+                is_our_node = true;
+            }
+            node = hash_pair(peak_hash, node);
+            height += 1;
+        }
+
+        if is_our_node {
+            leaf_height = height;
+        }
+        peaks.push((height, node)).ok()?;
+    }
+
+    let other_peaks = peaks.iter().copied().filter(|&(height, _)| height != leaf_height).collect();
+
+    Some(Proof { leaf_height, siblings, other_peaks })
+}
+
+/// Verify that `leaf` is a member of the accumulator rooted at `root`, per `proof`.
+pub fn verify_proof<const N: usize>(leaf: &impl Hashable, proof: &Proof<N>, root: u32) -> bool {
+    let node = proof.siblings.iter().fold(leaf.hash_leaf(), |node, &(sibling, side)| match side {
+        Side::Left => hash_pair(sibling, node),
+        Side::Right => hash_pair(node, sibling),
+    });
+
+    let mut peaks: Vec<(u8, u32), N> = Vec::new();
+    let mut inserted = false;
+    for &(height, hash) in proof.other_peaks.iter() {
+        if !inserted && proof.leaf_height > height {
+            if peaks.push((proof.leaf_height, node)).is_err() {
+                return false;
+            }
+            inserted = true;
+        }
+        if peaks.push((height, hash)).is_err() {
+            return false;
+        }
+    }
+    if !inserted && peaks.push((proof.leaf_height, node)).is_err() {
+        return false;
+    }
+
+    bag(&peaks) == Some(root)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    impl Hashable for u32 {
+        fn hash_leaf(&self) -> u32 {
+            fnv1a(&self.to_le_bytes())
+        }
+    }
+
+    #[test]
+    fn root_matches_verify_root() {
+        for count in [0u32, 1, 2, 3, 4, 5, 7, 8, 16, 17] {
+            let leaves: std::vec::Vec<u32> = (0..count).collect();
+            let root = root::<8>(&leaves);
+            if count == 0 {
+                assert_eq!(root, None);
+            } else {
+                assert!(verify_root::<8>(&leaves, root.unwrap()));
+            }
+            assert!(!verify_root::<8>(&leaves, root.unwrap_or(0).wrapping_add(1)));
+        }
+    }
+
+    #[test]
+    fn appending_one_at_a_time_matches_rebuilding_from_scratch() {
+        let leaves: std::vec::Vec<u32> = (0..13).collect();
+
+        let mut accumulator = Accumulator::<8>::new();
+        for leaf in &leaves {
+            accumulator.append(leaf);
+        }
+
+        assert_eq!(accumulator.root(), root::<8>(&leaves));
+    }
+
+    #[test]
+    fn every_leaf_proves_membership() {
+        let leaves: std::vec::Vec<u32> = (0..13).collect();
+        let root = root::<8>(&leaves).unwrap();
+
+        for index in 0..leaves.len() {
+            let proof = prove::<8>(&leaves, index).unwrap();
+            assert!(verify_proof(&leaves[index], &proof, root));
+        }
+    }
+
+    #[test]
+    fn proof_rejects_the_wrong_leaf_or_root() {
+        let leaves: std::vec::Vec<u32> = (0..5).collect();
+        let root = root::<8>(&leaves).unwrap();
+        let proof = prove::<8>(&leaves, 2).unwrap();
+
+        assert!(!verify_proof(&leaves[3], &proof, root));
+        assert!(!verify_proof(&leaves[2], &proof, root.wrapping_add(1)));
+    }
+
+    #[test]
+    fn prove_rejects_out_of_bounds_index() {
+        let leaves: std::vec::Vec<u32> = (0..3).collect();
+        assert!(prove::<8>(&leaves, 3).is_none());
+    }
+}
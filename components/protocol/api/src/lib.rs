@@ -12,9 +12,12 @@
 #![cfg_attr(not(test), no_std)]
 
 use rand_core::RngCore;
+use serde::{Deserialize, Serialize};
+
+pub mod accumulator;
 
 /// A states' action
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum Action<TIME, MESSAGE, CHANNEL> {
     /// Do nothing
@@ -36,6 +39,39 @@ pub trait ProtocolData<P: Protocol + ?Sized> {
     fn get_payload(&self) -> P::Payload;
 }
 
+/// A parent candidate a node is evaluating (or has already chosen) while building its route to a
+/// sink, exposed via [`Protocol::branches`].
+///
+/// Named after blockchain fork-choice: `id` is the candidate's own identity (this branch's tip),
+/// `parent` continues the chain one hop further (if known), and `length` is the hop count from
+/// the tip to the nearest sink — the value a fork-choice rule minimizes (instead of maximizing a
+/// chain length, as a blockchain fork choice would) when picking a branch to join.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Branch<NodeId> {
+    id: NodeId,
+    parent: Option<NodeId>,
+    length: u8,
+}
+
+impl<NodeId: Copy> Branch<NodeId> {
+    pub fn new(id: NodeId, parent: Option<NodeId>, length: u8) -> Self {
+        Self { id, parent, length }
+    }
+
+    pub fn id(&self) -> NodeId {
+        self.id
+    }
+
+    pub fn parent(&self) -> Option<NodeId> {
+        self.parent
+    }
+
+    pub fn length(&self) -> u8 {
+        self.length
+    }
+}
+
 pub trait Protocol {
     type TimeMs: Copy + Eq + Ord;
     type NodeId: Copy + Eq;
@@ -48,14 +84,17 @@ pub trait Protocol {
 
     /// Make progress in state machine
     ///
-    /// Returns action to execute and node data if node is a sink. This function must be called
-    /// again after the returned action has been executed.
+    /// `rx_snr_db` is the SNR of `message` as measured by the radio, if any; used by protocols
+    /// that adapt their data rate to measured link quality. Returns action to execute and node
+    /// data if node is a sink. This function must be called again after the returned action has
+    /// been executed.
     #[must_use]
     #[allow(clippy::type_complexity)]
     fn progress<T: RngCore>(
         &mut self,
         time: Self::TimeMs,
         message: Option<Self::Message>,
+        rx_snr_db: Option<i8>,
         rng: T,
     ) -> (
         Action<Self::TimeMs, Self::Message, Self::Channel>,
@@ -72,4 +111,16 @@ pub trait Protocol {
     fn set_payload(&mut self, payload: Self::Payload);
 
     fn has_payload(&self) -> bool;
+
+    /// The branch (candidate parent) this node has currently chosen to route through, if any,
+    /// and the other candidates it heard but didn't pick, so callers can inspect the converged
+    /// tree shape instead of just `progress`'s action/data output, e.g. to assert on tree depth
+    /// in a test or to log route churn in a simulation.
+    #[allow(clippy::type_complexity)]
+    fn branches(
+        &self,
+    ) -> (
+        Option<Branch<Self::NodeId>>,
+        impl IntoIterator<Item = Branch<Self::NodeId>>,
+    );
 }
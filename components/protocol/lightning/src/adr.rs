@@ -0,0 +1,190 @@
+//  _____       ______   ____
+// |_   _|     |  ____|/ ____|  Institute of Embedded Systems
+//   | |  _ __ | |__  | (___    Zurich University of Applied Sciences
+//   | | | '_ \|  __|  \___ \   8401 Winterthur, Switzerland
+//  _| |_| | | | |____ ____) |
+// |_____|_| |_|______|_____/
+//
+// Copyright 2025 Institute of Embedded Systems at Zurich University of Applied Sciences.
+// All rights reserved.
+// SPDX-License-Identifier: MIT
+
+//! Adaptive Data Rate: per-link spreading-factor/TX-power recommendation from measured SNR margin.
+//!
+//! ADR is computed per hop (by whichever node is the parent on that link) rather than centralized
+//! at the sink, since channel conditions differ hop to hop and the mesh already acks per hop.
+
+use serde::{Deserialize, Serialize};
+
+/// Lowest spreading factor (fastest, least robust).
+const MIN_SPREADING_FACTOR: u8 = 7;
+/// Highest spreading factor (slowest, most robust).
+const MAX_SPREADING_FACTOR: u8 = 12;
+const MIN_TX_POWER_DBM: i8 = -17;
+const MAX_TX_POWER_DBM: i8 = 22;
+const TX_POWER_STEP_DB: i8 = 3;
+/// Fixed safety margin added on top of the SF's required SNR.
+const INSTALLATION_MARGIN_DB: i8 = 10;
+
+/// Recommended PHY configuration for a link, piggybacked on `Message::DataAck`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct AdrCommand {
+    pub spreading_factor: u8,
+    pub tx_power_dbm: i8,
+}
+
+impl Default for AdrCommand {
+    /// Most robust configuration: the safe starting point before any link quality is known, and
+    /// what a node falls back to once it stops hearing acks.
+    fn default() -> Self {
+        Self {
+            spreading_factor: MAX_SPREADING_FACTOR,
+            tx_power_dbm: MAX_TX_POWER_DBM,
+        }
+    }
+}
+
+/// Minimum demodulation SNR per spreading factor (SX126x datasheet, dB), SF7..SF12.
+fn required_snr_db(spreading_factor: u8) -> i8 {
+    match spreading_factor {
+        7 => -7,
+        8 => -10,
+        9 => -12,
+        10 => -15,
+        11 => -17,
+        _ => -20,
+    }
+}
+
+/// `margin_dB = snr_measured - required_snr(SF) - installation_margin`
+pub(crate) fn margin_db(snr_measured_db: i8, spreading_factor: u8) -> i8 {
+    snr_measured_db - required_snr_db(spreading_factor) - INSTALLATION_MARGIN_DB
+}
+
+impl AdrCommand {
+    /// Step `self` toward `margin_db`, `Nstep = floor(margin_db / 3)` steps at a time.
+    ///
+    /// Positive margin: first lower the spreading factor toward SF7 (faster), then lower TX power
+    /// toward the minimum. Negative margin: first raise TX power toward the maximum, then raise the
+    /// spreading factor (more robust).
+    pub(crate) fn step(&self, margin_db: i8) -> Self {
+        let mut spreading_factor = self.spreading_factor;
+        let mut tx_power_dbm = self.tx_power_dbm;
+        let mut n_steps = margin_db.div_euclid(3);
+
+        while n_steps > 0 {
+            if spreading_factor > MIN_SPREADING_FACTOR {
+                spreading_factor -= 1;
+            } else if tx_power_dbm > MIN_TX_POWER_DBM {
+                tx_power_dbm = (tx_power_dbm - TX_POWER_STEP_DB).max(MIN_TX_POWER_DBM);
+            } else {
+                break;
+            }
+            n_steps -= 1;
+        }
+
+        while n_steps < 0 {
+            if tx_power_dbm < MAX_TX_POWER_DBM {
+                tx_power_dbm = (tx_power_dbm + TX_POWER_STEP_DB).min(MAX_TX_POWER_DBM);
+            } else if spreading_factor < MAX_SPREADING_FACTOR {
+                spreading_factor += 1;
+            } else {
+                break;
+            }
+            n_steps += 1;
+        }
+
+        Self {
+            spreading_factor,
+            tx_power_dbm,
+        }
+    }
+
+    /// Recommend a new configuration given a freshly measured SNR for a link currently running at
+    /// `self`.
+    pub(crate) fn recommend(&self, snr_measured_db: i8) -> Self {
+        self.step(margin_db(snr_measured_db, self.spreading_factor))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_is_most_robust() {
+        let adr = AdrCommand::default();
+        assert_eq!(adr.spreading_factor, MAX_SPREADING_FACTOR);
+        assert_eq!(adr.tx_power_dbm, MAX_TX_POWER_DBM);
+    }
+
+    #[test]
+    fn large_positive_margin_lowers_sf_before_power() {
+        let adr = AdrCommand {
+            spreading_factor: 10,
+            tx_power_dbm: 14,
+        };
+        // required_snr(SF10) = -15, so margin = snr - (-15) - 10 = snr + 5
+        let stepped = adr.recommend(-5); // margin = 0 => Nstep 0, no change
+        assert_eq!(stepped, adr);
+
+        let stepped = adr.recommend(0); // margin = 5 => Nstep 1
+        assert_eq!(
+            stepped,
+            AdrCommand {
+                spreading_factor: 9,
+                tx_power_dbm: 14
+            }
+        );
+    }
+
+    #[test]
+    fn reduces_power_once_sf_floor_reached() {
+        let adr = AdrCommand {
+            spreading_factor: MIN_SPREADING_FACTOR,
+            tx_power_dbm: 14,
+        };
+        // required_snr(SF7) = -7, margin = snr + 7 - 10 = snr - 3
+        let stepped = adr.recommend(12); // margin = 9 => Nstep 3
+        assert_eq!(
+            stepped,
+            AdrCommand {
+                spreading_factor: MIN_SPREADING_FACTOR,
+                tx_power_dbm: 5
+            }
+        );
+    }
+
+    #[test]
+    fn negative_margin_raises_power_before_sf() {
+        let adr = AdrCommand {
+            spreading_factor: 9,
+            tx_power_dbm: 5,
+        };
+        // required_snr(SF9) = -12, margin = snr + 12 - 10 = snr + 2
+        let stepped = adr.recommend(-8); // margin = -6 => Nstep -2
+        assert_eq!(
+            stepped,
+            AdrCommand {
+                spreading_factor: 9,
+                tx_power_dbm: 11
+            }
+        );
+    }
+
+    #[test]
+    fn clamps_at_extremes() {
+        let adr = AdrCommand {
+            spreading_factor: MIN_SPREADING_FACTOR,
+            tx_power_dbm: MIN_TX_POWER_DBM,
+        };
+        assert_eq!(adr.step(30), adr);
+
+        let adr = AdrCommand {
+            spreading_factor: MAX_SPREADING_FACTOR,
+            tx_power_dbm: MAX_TX_POWER_DBM,
+        };
+        assert_eq!(adr.step(-30), adr);
+    }
+}
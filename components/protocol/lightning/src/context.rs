@@ -14,19 +14,39 @@ use crate::*;
 #[derive(Debug)]
 pub(crate) struct Context {
     pub(crate) channels: Channels,
-    pub(crate) windows: Windows,
+    pub(crate) windows: Windows<DefaultPolicy>,
     pub(crate) hops_to_sink: Option<Hops>,
+    /// ID of this node's own parent, as advertised in its beacons (see `Message::Beacon::id`).
+    /// `None` for a sink, which has no parent.
+    pub(crate) parent_id: Option<NodeId>,
+    /// Our parent's own parent's ID, if any, so the branch we're connected through (see
+    /// `Lightning::branches`) can expose more than just its own tip.
+    pub(crate) parents_parent_id: Option<NodeId>,
     pub(crate) child_data: ChildData,
     pub(crate) potential_connect_beacons: PotentialConnectBeacons,
+    /// This node's own spreading factor/TX power toward its parent, as recommended by the parent.
+    pub(crate) own_adr: AdrCommand,
+    /// Per-child spreading factor/TX power, recommended from this node's own SNR measurements.
+    pub(crate) child_adr: ChildAdr,
+    /// Credits granted by our own parent for our next `SendData` (flow control, see
+    /// `compute_credits`). Unconstrained until a `ConnectAck`/`DataAck` says otherwise.
+    pub(crate) credits: u8,
+    /// Consecutive missed `ListenForData` windows per child, see `record_missed_child_window`.
+    pub(crate) missed_child_windows: MissedChildWindows,
+    /// Snapshot for a fast reconnect to the same parent after the next `Reset`, see
+    /// `reset` and `ReconnectContext`.
+    pub(crate) reconnect: Option<ReconnectContext>,
 }
 
 /// Stores beacon info for selecting a parent
 #[derive(Debug)]
 pub(crate) struct BeaconInfo {
     /// Time beacon was received
-    pub(crate) time_seen: TimeMs,
+    pub(crate) time_seen: Instant,
     /// Hop count from beacon
     pub(crate) hops: Hops,
+    /// ID of the beacon's sender, i.e. this candidate branch's tip (see `Message::Beacon::id`)
+    pub(crate) id: NodeId,
 }
 
 impl Default for Context {
@@ -34,15 +54,112 @@ impl Default for Context {
         Self {
             channels: Channels::default(),
             hops_to_sink: None,
+            parent_id: None,
+            parents_parent_id: None,
             child_data: heapless::Vec::default(),
-            windows: Windows::new(MIN_WINDOW_CLEARANCE),
+            windows: Windows::new(MIN_WINDOW_CLEARANCE, DefaultPolicy, DUTY_CYCLE_LIMIT),
             potential_connect_beacons: PotentialConnectBeacons::new(),
+            own_adr: AdrCommand::default(),
+            child_adr: ChildAdr::new(),
+            credits: u8::MAX,
+            missed_child_windows: MissedChildWindows::new(),
+            reconnect: None,
         }
     }
 }
 
 impl Context {
-    pub(crate) fn reset(&mut self) {
+    /// Reset to a fresh `Context`, preserving a fast-reconnect snapshot: if we currently have a
+    /// parent, a new snapshot is captured from it; otherwise any snapshot already stored (e.g.
+    /// seeded from non-volatile storage at boot via `Lightning::seed_reconnect_snapshot`) is kept
+    /// as is.
+    pub(crate) fn reset(&mut self, time: Instant) {
+        let derived_snapshot = self
+            .channels
+            .parent
+            .zip(self.hops_to_sink)
+            .zip(self.parent_id)
+            .map(|((parent_channel, hops_to_sink), parent_id)| ReconnectContext {
+                parent_channel,
+                parents_parent_channel: self.channels.parents_parent_channel,
+                hops_to_sink,
+                parent_id,
+                parents_parent_id: self.parents_parent_id,
+                next_parent_window_min: self
+                    .windows
+                    .next_kind(WindowKind::Parent)
+                    .map(|start| start.saturating_sub(time).as_millis().div_ceil(MS_PER_MIN) as u8)
+                    .unwrap_or(0),
+            });
+        let snapshot = derived_snapshot.or(self.reconnect);
         *self = Self::default();
+        self.reconnect = snapshot;
+    }
+
+    /// Recommend an updated ADR configuration for `child_id` given a freshly measured SNR,
+    /// remembering it so it can be fed back in the next `DataAck`.
+    pub(crate) fn recommend_child_adr(
+        &mut self,
+        child_id: NodeId,
+        snr_measured_db: i8,
+    ) -> AdrCommand {
+        let current = self
+            .child_adr
+            .iter()
+            .find(|(id, _)| *id == child_id)
+            .map(|(_, adr)| *adr)
+            .unwrap_or_default();
+        let recommended = current.recommend(snr_measured_db);
+        self.set_child_adr(child_id, recommended);
+        recommended
+    }
+
+    /// Credits to grant a child for its next `SendData`: this node's remaining free `child_data`
+    /// capacity, divided across `children_count` currently connected children so a single child
+    /// can't starve the others (borrowed from BLE L2CAP's LeCreditConnReq/LeCreditFlowInd
+    /// scheme). Always at least 1, so a child with nothing queued still gets to report in next
+    /// window.
+    pub(crate) fn compute_credits(&self, children_count: usize) -> u8 {
+        let free = self.child_data.capacity() - self.child_data.len();
+        let children = children_count.max(1);
+        (free / children).clamp(1, u8::MAX as usize) as u8
+    }
+
+    fn set_child_adr(&mut self, child_id: NodeId, adr: AdrCommand) {
+        if let Some(entry) = self.child_adr.iter_mut().find(|(id, _)| *id == child_id) {
+            entry.1 = adr;
+        } else if self.child_adr.push((child_id, adr)).is_err() {
+            warn!(
+                "too many children to track ADR for, dropping entry for {:x}",
+                child_id
+            );
+        }
+    }
+
+    /// Record a missed `ListenForData` window for `child_id`, returning the updated consecutive-
+    /// miss count.
+    pub(crate) fn record_missed_child_window(&mut self, child_id: NodeId) -> u8 {
+        if let Some(entry) = self
+            .missed_child_windows
+            .iter_mut()
+            .find(|(id, _)| *id == child_id)
+        {
+            entry.1 += 1;
+            entry.1
+        } else {
+            if self.missed_child_windows.push((child_id, 1)).is_err() {
+                warn!(
+                    "too many children to track missed windows for, dropping entry for {:x}",
+                    child_id
+                );
+            }
+            1
+        }
+    }
+
+    /// Clear `child_id`'s consecutive-miss counter, e.g. once it sends data again or its window
+    /// has been reclaimed.
+    pub(crate) fn clear_missed_child_window(&mut self, child_id: NodeId) {
+        self.missed_child_windows.retain(|(id, _)| *id != child_id);
     }
 }
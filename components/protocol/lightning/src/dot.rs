@@ -0,0 +1,80 @@
+//  _____       ______   ____
+// |_   _|     |  ____|/ ____|  Institute of Embedded Systems
+//   | |  _ __ | |__  | (___    Zurich University of Applied Sciences
+//   | | | '_ \|  __|  \___ \   8401 Winterthur, Switzerland
+//  _| |_| | | | |____ ____) |
+// |_____|_| |_|______|_____/
+//
+// Copyright 2025 Institute of Embedded Systems at Zurich University of Applied Sciences.
+// All rights reserved.
+// SPDX-License-Identifier: MIT
+
+//! Render a node's local view of the network as a Graphviz graph, for debugging join/parent-
+//! selection bugs: dump every node's view (e.g. from a simulation harness) and stitch the
+//! fragments together into one graph of the whole network.
+
+use core::fmt::Write;
+
+use crate::*;
+
+/// Whether to render as a directed or undirected graph.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Directed,
+    Undirected,
+}
+
+impl Kind {
+    fn graph_keyword(self) -> &'static str {
+        match self {
+            Kind::Directed => "digraph",
+            Kind::Undirected => "graph",
+        }
+    }
+
+    fn edge_op(self) -> &'static str {
+        match self {
+            Kind::Directed => "->",
+            Kind::Undirected => "--",
+        }
+    }
+}
+
+impl Context {
+    /// Write this node's local view (parent, if known, and children) as a complete Graphviz
+    /// `digraph { ... }`/`graph { ... }`. The children drawn are those held in `child_adr`: a
+    /// child appears once it has sent data with a measured SNR and stays until this node resets.
+    /// `self_id` is this node's own ID, since `Context` does not store it.
+    pub(crate) fn fmt_dot(
+        &self,
+        self_id: NodeId,
+        kind: Kind,
+        f: &mut impl Write,
+    ) -> core::fmt::Result {
+        writeln!(f, "{} {{", kind.graph_keyword())?;
+
+        match self.hops_to_sink {
+            Some(hops) => writeln!(f, "  \"{self_id:x}\" [label=\"{self_id:x}\\nhops={hops}\"];")?,
+            None => writeln!(f, "  \"{self_id:x}\" [label=\"{self_id:x}\\nhops=?\"];")?,
+        }
+
+        if let Some((parent_channel, parent_id)) = self.channels.parent.zip(self.parent_id) {
+            writeln!(
+                f,
+                "  \"{self_id:x}\" {} \"{parent_id:x}\" [label=\"ch {parent_channel}\"];",
+                kind.edge_op()
+            )?;
+        }
+
+        for (child_id, _) in self.child_adr.iter() {
+            write!(f, "  \"{self_id:x}\" {} \"{child_id:x}\" [label=\"ch ", kind.edge_op())?;
+            match self.channels.children {
+                Some(channel) => write!(f, "{channel}")?,
+                None => write!(f, "?")?,
+            }
+            writeln!(f, "\"];")?;
+        }
+
+        writeln!(f, "}}")
+    }
+}
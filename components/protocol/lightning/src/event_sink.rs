@@ -0,0 +1,184 @@
+//  _____       ______   ____
+// |_   _|     |  ____|/ ____|  Institute of Embedded Systems
+//   | |  _ __ | |__  | (___    Zurich University of Applied Sciences
+//   | | | '_ \|  __|  \___ \   8401 Winterthur, Switzerland
+//  _| |_| | | | |____ ____) |
+// |_____|_| |_|______|_____/
+//
+// Copyright 2025 Institute of Embedded Systems at Zurich University of Applied Sciences.
+// All rights reserved.
+// SPDX-License-Identifier: MIT
+
+//! Structured protocol events, decoupled from any particular log backend.
+//!
+//! `Lightning::next` used to format event text directly via the `event_log_*!` macros, which
+//! meant a simulation harness or analyzer had to scrape `$uptime;node_id;kind;content` lines back
+//! out of the `log`/`defmt` backend to know what happened. Instead `next` now emits a typed
+//! [`Event`] to whatever [`EventSink`] the node was constructed with; [`LogSink`] is the default
+//! and reproduces the old text lines so existing parsers keep working, but a test (or a richer
+//! analyzer) can install its own sink and read the event stream directly.
+
+use crate::*;
+
+/// A single state-machine event, emitted via `EventSink::emit` at each `Lightning::next`
+/// transition.
+#[derive(Debug, Clone, Copy)]
+pub enum Event<'a> {
+    /// The node has (re)started.
+    Reset { is_sink: bool },
+    /// `message` was received.
+    Message { message: &'a Message },
+    /// The node transitioned into the state named `state`.
+    StateChange { state: &'static str },
+    /// `child_id` connected.
+    NewChild { child_id: NodeId },
+    /// `child_id`'s window was reclaimed, gracefully or after too many missed windows.
+    ChildLost { child_id: NodeId },
+    /// `child_id`'s `Message::Data` batch didn't rebuild to the MMR root it was sent with (lost,
+    /// duplicated, or injected records); the batch was dropped instead of aggregated.
+    DataIntegrityCheckFailed { child_id: NodeId },
+    /// The `Action::Transmit` last returned while in `state` never made it onto the air (channel
+    /// busy through CAD, or a radio fault); the window it was scheduled in is being retried
+    /// instead of treated as sent.
+    TransmitFailed { state: &'static str },
+    /// The node is about to perform `action`.
+    Action { action: &'a ProtocolAction },
+}
+
+/// Receives structured protocol events. Implement this instead of scraping `LogSink`'s text
+/// output to observe exactly what a `Lightning` node does.
+pub trait EventSink {
+    fn emit(&mut self, time: TimeMs, node: NodeId, event: Event<'_>);
+}
+
+/// Default `EventSink`, reproducing the `$uptime;node_id;kind;content` text lines this crate has
+/// always emitted via `log`/`defmt`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LogSink;
+
+impl EventSink for LogSink {
+    fn emit(&mut self, time: TimeMs, node: NodeId, event: Event<'_>) {
+        match event {
+            Event::Reset { is_sink } => {
+                info!("${};{};reset;{{\"is_sink\":{}}}", time, node, is_sink);
+            }
+            Event::Message { message } => {
+                info!("${};{};message;{}", time, node, message);
+            }
+            Event::StateChange { state } => {
+                info!("${};{};state;\"{}\"", time, node, state);
+            }
+            Event::NewChild { child_id } => {
+                info!("${};{};new_child;\"{}\"", time, node, child_id);
+            }
+            Event::ChildLost { child_id } => {
+                info!("${};{};child_lost;\"{}\"", time, node, child_id);
+            }
+            Event::DataIntegrityCheckFailed { child_id } => {
+                error!(
+                    "${};{};data_integrity_check_failed;\"{}\"",
+                    time, node, child_id
+                );
+            }
+            Event::TransmitFailed { state } => {
+                error!("${};{};transmit_failed;\"{}\"", time, node, state);
+            }
+            Event::Action { action } => {
+                info!(
+                    "${};{};action;{}",
+                    time,
+                    node,
+                    DisplayableAction(action, time)
+                );
+            }
+        }
+    }
+}
+
+pub(crate) struct DisplayableAction<'a>(pub(crate) &'a ProtocolAction, pub(crate) TimeMs);
+
+/// action as JSON to make it parseable
+macro_rules! action_to_json_string {
+    ($fmt:expr,$write:tt,$action:expr,$time:expr) => {
+        match $action {
+            Action::None => $write!($fmt, "{{\"kind\":\"none\"}}"),
+            Action::Wait { end } => {
+                $write!(
+                    $fmt,
+                    "{{\"kind\":\"wait\",\"duration\":{}}}",
+                    *end as i64 - $time as i64
+                )
+            }
+            Action::Receive { end, channel } => $write!(
+                $fmt,
+                "{{\"kind\":\"receive\",\"duration\":{},\"channel\":{}}}",
+                *end as i64 - $time as i64,
+                channel,
+            ),
+            Action::Transmit {
+                channel,
+                delay,
+                message: _,
+            } => $write!(
+                $fmt,
+                "{{\"kind\":\"transmit\",\"channel\":{},\"delay_ms\":{}}}",
+                channel,
+                delay.unwrap_or(0),
+            ),
+        }
+    };
+}
+
+impl core::fmt::Display for DisplayableAction<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        action_to_json_string!(f, write, self.0, self.1)
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for DisplayableAction<'_> {
+    fn format(&self, fmt: defmt::Formatter) {
+        use defmt::write;
+        action_to_json_string!(fmt, write, self.0, self.1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// In-memory sink a test can install to assert on the exact event stream a run produced,
+    /// instead of parsing `LogSink`'s text output.
+    #[derive(Debug, Default)]
+    struct RecordingSink {
+        reset_count: usize,
+        new_children: heapless::Vec<NodeId, 8>,
+        child_losses: heapless::Vec<NodeId, 8>,
+    }
+
+    impl EventSink for RecordingSink {
+        fn emit(&mut self, _time: TimeMs, _node: NodeId, event: Event<'_>) {
+            match event {
+                Event::Reset { .. } => self.reset_count += 1,
+                Event::NewChild { child_id } => {
+                    let _ = self.new_children.push(child_id);
+                }
+                Event::ChildLost { child_id } => {
+                    let _ = self.child_losses.push(child_id);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    #[test]
+    fn recording_sink_sees_reset_and_child_events() {
+        let mut sink = RecordingSink::default();
+        sink.emit(0, 1, Event::Reset { is_sink: true });
+        sink.emit(1, 1, Event::NewChild { child_id: 2 });
+        sink.emit(2, 1, Event::ChildLost { child_id: 2 });
+        assert_eq!(sink.reset_count, 1);
+        assert_eq!(sink.new_children.as_slice(), &[2]);
+        assert_eq!(sink.child_losses.as_slice(), &[2]);
+    }
+}
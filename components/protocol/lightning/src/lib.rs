@@ -17,6 +17,8 @@ use rand_core::RngCore;
 use protocol_api::*;
 
 mod message;
+mod time;
+use crate::time::*;
 mod window;
 use crate::window::*;
 mod channel;
@@ -25,11 +27,28 @@ mod context;
 use crate::context::*;
 mod states;
 use crate::states::*;
-mod event_log;
+mod adr;
+use crate::adr::*;
+mod dot;
+mod event_sink;
+use crate::event_sink::*;
 mod lightning;
+mod reconnect;
+use crate::reconnect::*;
 mod state_machine;
-
-pub use crate::{lightning::Lightning, message::Message, message::NodeData};
+#[cfg(test)]
+mod trace;
+mod wire;
+
+pub use crate::{
+    adr::AdrCommand,
+    dot::Kind,
+    event_sink::{Event, EventSink, LogSink},
+    lightning::Lightning,
+    message::{Message, NodeData},
+    reconnect::ReconnectContext,
+    wire::{decode, encode, DecodeError, EncodeError},
+};
 
 #[cfg(feature = "defmt")]
 #[allow(unused_imports)]
@@ -39,11 +58,13 @@ use defmt::{debug, error, info, warn};
 #[allow(unused_imports)]
 use log::{debug, error, info, warn};
 
-// TODO: use proper time types
-const MS_PER_S: TimeMs = 1000;
-const MS_PER_MIN: TimeMs = 60 * MS_PER_S;
+const MS_PER_S: u64 = 1000;
+const MS_PER_MIN: u64 = 60 * MS_PER_S;
 
-/// Time as milliseconds since start
+/// Time as milliseconds since start, per [`protocol_api::Protocol::TimeMs`]. Internally this crate
+/// uses the distinct [`time::Instant`]/[`time::Duration`] types instead; `progress` converts at the
+/// boundary so the rest of the `Protocol` impl (and callers like the simulator and firmware) can
+/// keep dealing in plain milliseconds.
 pub type TimeMs = u64;
 /// Node identifier
 pub type NodeId = u32;
@@ -51,19 +72,48 @@ pub type NodeId = u32;
 pub type Channel = u8;
 pub type OwnAndChildData = Vec<NodeData, { MAX_DESCENDANTS + 1 }>;
 
-type LightningAction = Action<TimeMs, Message, Channel>;
+/// Convert a count of whole minutes (as carried in wire fields like `next_window_min` and in
+/// `CHILD_DATA_INTERVAL_MIN`) to a `Duration`.
+const fn minutes(n: u8) -> Duration {
+    duration_from_millis(n as u64 * MS_PER_MIN)
+}
+
+/// Internal representation of a state's action, used throughout `window`/`state_machine`/
+/// `context`. `Lightning::progress` converts this to `ProtocolAction` at the `Protocol` boundary.
+type LightningAction = Action<Instant, Message, Channel>;
+/// The `Protocol::progress` boundary's action representation: same shape as `LightningAction`, but
+/// with plain millisecond `TimeMs` instead of `Instant`, for callers outside this crate.
+type ProtocolAction = Action<TimeMs, Message, Channel>;
 type Payload = u16;
 type Hops = u8;
 type PotentialConnectBeacons = Vec<BeaconInfo, MAX_BEACONS_TO_COLLECT>;
 type ChildData = Vec<NodeData, MAX_DESCENDANTS>;
+/// Per-child ADR state, keyed by the child's `NodeId`.
+type ChildAdr = Vec<(NodeId, AdrCommand), MAX_CHILDREN>;
+/// Per-child consecutive missed-`ListenForData`-window counter, keyed by the child's `NodeId`.
+type MissedChildWindows = Vec<(NodeId, u8), MAX_CHILDREN>;
 
 // TODO: move these parameters elsewhere to make them configurable by the application
 
-const BEACON_INTERVAL_MS: TimeMs = 30 * 1000;
+const BEACON_INTERVAL_MS: Duration = duration_from_millis(30 * 1000);
 const CHILD_DATA_INTERVAL_MIN: u8 = 5;
+const CHILD_DATA_INTERVAL: Duration = minutes(CHILD_DATA_INTERVAL_MIN);
+/// Recurrence periods fed to `Windows::is_feasible`. The parent window is modelled as recurring
+/// once per beacon interval, like the beacon itself; its real spacing is the `next_window_min`
+/// whole minutes from each parent ack, close to a multiple of that interval.
+const WINDOW_PERIODS: WindowPeriods = WindowPeriods {
+    beacon: BEACON_INTERVAL_MS,
+    parent: BEACON_INTERVAL_MS,
+    child: CHILD_DATA_INTERVAL,
+};
 const NUM_CHANNELS: u8 = 8;
 const MAX_CHILDREN: usize = 6;
 const MAX_DESCENDANTS: usize = 16;
+/// Bounds the number of simultaneous MMR peaks for the `SendData` accumulator (see
+/// `protocol_api::accumulator`); must satisfy `2^ACC_PEAKS - 1 >= MAX_DESCENDANTS + 1`.
+const ACC_PEAKS: usize = 5;
+/// Number of consecutive missed `ListenForData` windows before a child's window is reclaimed.
+const MAX_MISSED_CHILD_WINDOWS: u8 = 3;
 /// Maximum number of scheduled windows.
 /// one window per child + connect window + parent window
 /// (no beacon window because node does not send a beacon when it has max number of children)
@@ -72,22 +122,42 @@ const MAX_BEACONS_TO_COLLECT: usize = 16;
 
 // the following parameter values are tweaked for the LoRa test network
 
-const RESPONSE_LISTEN_DURATION_MS: TimeMs = 200;
+const RESPONSE_LISTEN_DURATION_MS: Duration = duration_from_millis(200);
 /// Minimum distance that is maintained between windows.
 /// Compensates for message time on air and time firmware requires to process actions. For the
 /// beacon window (which is the window with the most messages) this is ~300ms in test network
 /// (stm32wl55, SF8, BW 125KHz, 12 symbols preamble, 4/6 coding rate).
-const MIN_WINDOW_CLEARANCE: TimeMs = 300;
-const DATA_RECEIVE_WINDOW: TimeMs = 350;
-const RANDOM_CONNECT_RANGE_MS: TimeMs = 400;
+const MIN_WINDOW_CLEARANCE: Duration = duration_from_millis(300);
+const DATA_RECEIVE_WINDOW: Duration = duration_from_millis(350);
+const RANDOM_CONNECT_RANGE_MS: Duration = duration_from_millis(400);
 /// Must be longer than sender of the beacon takes to handle the SendConnect state
-const CONNECT_RESPONSE_DELAY_MS: TimeMs = 100;
+const CONNECT_RESPONSE_DELAY_MS: Duration = duration_from_millis(100);
+/// Number of times a losing (or un-acked) `Connect` attempt backs off into a fresh `DelayConnect`
+/// with a larger random offset before giving up and doing a full `Reset`.
+const MAX_CONNECT_RETRIES: u8 = 5;
 /// Maximum expected clock drift between two nodes.
 const CLOCK_DRIFT_PPM: u32 = 30;
 /// How long to enter receive mode at the time the best parent is expected to send a beacon.
-const BEST_BEACON_LISTEN_TIME: TimeMs = MIN_WINDOW_CLEARANCE * 3;
+const BEST_BEACON_LISTEN_TIME: Duration =
+    duration_from_millis(duration_as_millis(MIN_WINDOW_CLEARANCE) * 3);
 /// Delay to give the receiver time to enter receive mode.
-const SEND_DELAY: TimeMs = 5;
+const SEND_DELAY: Duration = duration_from_millis(5);
+/// Regulatory transmit duty-cycle budget applied to every channel: 1% over a rolling hour, the
+/// most restrictive EU868 sub-band this crate's test network uses. A deployment on a 10%-band
+/// would pass a more permissive `DutyCycleLimit` in here instead.
+const DUTY_CYCLE_LIMIT: DutyCycleLimit = DutyCycleLimit {
+    percent: 1,
+    period: duration_from_millis(60 * 60 * MS_PER_S),
+};
+
+/// `SEND_DELAY`, expressed as `protocol_api::Action::Transmit`'s `delay` wants it: that field
+/// shares its type with `Wait`/`Receive`'s absolute `end` (see `protocol_api::Action`), so this
+/// fixed send delay has to be expressed as an `Instant` even though it's really a duration. Not a
+/// `const` because converting via `Instant::from_millis` isn't `const`-callable under `cfg(test)`
+/// (there it comes from the `TimeValue` trait, not an inherent `const fn`).
+pub(crate) fn send_delay_action() -> Instant {
+    Instant::from_millis(SEND_DELAY.as_millis())
+}
 
 /// Extend duration to compensate for clock inaccuracies of two nodes.
 ///
@@ -95,8 +165,8 @@ const SEND_DELAY: TimeMs = 5;
 /// is used to adjust the senders wake up time to ensure the receiver is guaranteed to have entered
 /// receive mode before the sender starts sending. Must be used to extend the wait time of each wait
 /// state that precedes a send state. And the receive stop time.
-pub(crate) fn adjust_for_clock_inaccuracies(duration: TimeMs) -> TimeMs {
-    duration * (1_000_000 + CLOCK_DRIFT_PPM) as u64 / 1_000_000
+pub(crate) fn adjust_for_clock_inaccuracies(duration: Duration) -> Duration {
+    Duration::from_millis(duration.as_millis() * (1_000_000 + CLOCK_DRIFT_PPM) as u64 / 1_000_000)
 }
 
 /// Reduce duration to compensate for clock inaccuracies of two nodes.
@@ -104,8 +174,13 @@ pub(crate) fn adjust_for_clock_inaccuracies(duration: TimeMs) -> TimeMs {
 /// Normally the sender extends its sleep time to compensate but this only works when two nodes have
 /// agreed to talk to each other at a certain time. When waiting for a certain beacon to be sent
 /// again this is not the case.
-pub(crate) fn adjust_for_clock_inaccuracies_sub(duration: TimeMs) -> TimeMs {
-    duration * (1_000_000 - CLOCK_DRIFT_PPM) as u64 / 1_000_000
+pub(crate) fn adjust_for_clock_inaccuracies_sub(duration: Duration) -> Duration {
+    Duration::from_millis(duration.as_millis() * (1_000_000 - CLOCK_DRIFT_PPM) as u64 / 1_000_000)
+}
+
+/// Uniform random jitter in `[0, max)`, e.g. to desynchronize siblings' beacon timing.
+pub(crate) fn jitter(rng: &mut impl RngCore, max: Duration) -> Duration {
+    Duration::from_millis(rng.next_u32() as u64 % max.as_millis())
 }
 
 /// Wraps defmt::write and returns Ok() to make it behave like core::write!.
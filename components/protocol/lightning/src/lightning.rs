@@ -12,17 +12,18 @@
 use crate::*;
 
 #[derive(Debug)]
-pub struct Lightning {
+pub struct Lightning<S: EventSink = LogSink> {
     pub(crate) id: NodeId,
     pub(crate) state: State,
     pub(crate) context: Context,
+    pub(crate) sink: S,
     /// Whether node can uplink data (e.g. has reception to a gateway, ...)
     pub is_sink: bool,
     /// Payload to send to the parent
     pub payload: Option<Payload>,
 }
 
-impl protocol_api::Protocol for Lightning {
+impl<S: EventSink + Default> protocol_api::Protocol for Lightning<S> {
     type TimeMs = TimeMs;
     type NodeId = NodeId;
     type Message = Message;
@@ -33,12 +34,13 @@ impl protocol_api::Protocol for Lightning {
     fn new(id: Self::NodeId) -> Self {
         #[allow(clippy::assertions_on_constants)]
         const {
-            assert!(RANDOM_CONNECT_RANGE_MS > CONNECT_RESPONSE_DELAY_MS);
+            assert!(duration_as_millis(RANDOM_CONNECT_RANGE_MS) > duration_as_millis(CONNECT_RESPONSE_DELAY_MS));
         }
         Self {
             id,
             state: State::default(),
             context: Context::default(),
+            sink: S::default(),
             is_sink: false,
             payload: None,
         }
@@ -48,17 +50,111 @@ impl protocol_api::Protocol for Lightning {
         &mut self,
         time: Self::TimeMs,
         message: Option<Self::Message>,
+        rx_snr_db: Option<i8>,
         rng: T,
     ) -> (
-        LightningAction,
+        ProtocolAction,
         Option<impl IntoIterator<Item = Self::Data>>,
     ) {
+        self.progress_impl(time, message, rx_snr_db, false, rng)
+    }
+
+    fn id(&self) -> Self::NodeId {
+        self.id
+    }
+
+    fn set_is_sink(&mut self, is_sink: bool) {
+        self.is_sink = is_sink;
+    }
+
+    fn is_sink(&self) -> bool {
+        self.is_sink
+    }
+
+    fn set_payload(&mut self, payload: Self::Payload) {
+        self.payload.replace(payload);
+    }
+
+    fn has_payload(&self) -> bool {
+        self.payload.is_some()
+    }
+
+    fn branches(&self) -> (Option<Branch<NodeId>>, impl IntoIterator<Item = Branch<NodeId>>) {
+        let current = self
+            .context
+            .parent_id
+            .zip(self.context.hops_to_sink)
+            .map(|(id, hops_to_sink)| {
+                Branch::new(id, self.context.parents_parent_id, hops_to_sink - 1)
+            });
+
+        // candidates we heard but didn't pick; we only learn a rejected candidate's own parent if
+        // we end up choosing it (see `Context::parents_parent_id`), so those always report `None`
+        let mut competitors: Vec<Branch<NodeId>, MAX_BEACONS_TO_COLLECT> = Vec::new();
+        for beacon in &self.context.potential_connect_beacons {
+            if Some(beacon.id) == current.map(|c| c.id()) {
+                continue;
+            }
+            // capacity matches `potential_connect_beacons`'s, so this never overflows
+            let _ = competitors.push(Branch::new(beacon.id, None, beacon.hops));
+        }
+        (current, competitors)
+    }
+}
+
+/// Convert the crate-internal, `Instant`-based action representation (see `LightningAction`) to
+/// the `Protocol::progress` boundary's plain-millisecond one.
+fn to_protocol_action(action: LightningAction) -> ProtocolAction {
+    match action {
+        Action::None => Action::None,
+        Action::Wait { end } => Action::Wait {
+            end: end.as_millis(),
+        },
+        Action::Receive { end, channel } => Action::Receive {
+            end: end.as_millis(),
+            channel,
+        },
+        Action::Transmit {
+            channel,
+            message,
+            delay,
+        } => Action::Transmit {
+            channel,
+            message,
+            delay: delay.map(Instant::as_millis),
+        },
+    }
+}
+
+impl<S: EventSink> Lightning<S> {
+    /// Shared implementation of `Protocol::progress` and `notify_transmit_failed`: both just pick
+    /// a `transmit_failed` value and otherwise go through the same state/event bookkeeping.
+    fn progress_impl<T: RngCore>(
+        &mut self,
+        time: TimeMs,
+        message: Option<Message>,
+        rx_snr_db: Option<i8>,
+        transmit_failed: bool,
+        rng: T,
+    ) -> (ProtocolAction, Option<OwnAndChildData>) {
         if let Some(message) = &message {
-            event_log_msg!(time, self.id, message);
+            self.sink.emit(time, self.id, Event::Message { message });
         };
 
-        let next_state = self.next(time, message, rng);
-        event_log_state!(time, self.id, &next_state);
+        let next_state = self.next(
+            Instant::from_millis(time),
+            message,
+            rx_snr_db,
+            transmit_failed,
+            rng,
+        );
+        self.sink.emit(
+            time,
+            self.id,
+            Event::StateChange {
+                state: next_state.state_as_string(),
+            },
+        );
         self.state = next_state;
 
         let uplink_data = if self.is_sink && !self.context.child_data.is_empty() {
@@ -79,82 +175,61 @@ impl protocol_api::Protocol for Lightning {
             None
         };
 
-        let action = self.state.get_action();
-        event_log_action!(time, self.id, DisplayableAction(&action, time));
+        let action = to_protocol_action(self.state.get_action());
+        self.sink.emit(time, self.id, Event::Action { action: &action });
         (action, uplink_data)
     }
 
-    fn id(&self) -> Self::NodeId {
-        self.id
-    }
-
-    fn set_is_sink(&mut self, is_sink: bool) {
-        self.is_sink = is_sink;
-    }
-
-    fn is_sink(&self) -> bool {
-        self.is_sink
+    /// Tell the state machine that the `Action::Transmit` returned by the previous `progress`
+    /// call never actually made it onto the air (the channel stayed busy through carrier sense,
+    /// or the radio faulted), instead of calling `progress` again as if it had. Call this in place
+    /// of `progress` for the tick right after a failed transmit.
+    ///
+    /// A failed `Connect` is already retried by `ListenForConnectAck`'s back-off, and a failed
+    /// `Data` leaves `ListenForDataAck` without an ack, which resets the node. The fire-and-forget
+    /// sends (`Beacon`, `ConnectAck`, `DataAck`) have neither and would otherwise silently consume
+    /// the window they were scheduled in; this retries that window instead. A no-op (identical to
+    /// calling `progress` with no message) for any other state.
+    pub fn notify_transmit_failed<T: RngCore>(
+        &mut self,
+        time: TimeMs,
+        rng: T,
+    ) -> (ProtocolAction, Option<OwnAndChildData>) {
+        self.progress_impl(time, None, None, true, rng)
     }
 
-    fn set_payload(&mut self, payload: Self::Payload) {
-        self.payload.replace(payload);
+    pub fn next_data_transmission(&self) -> TimeMs {
+        self.context
+            .windows
+            .next_kind(WindowKind::Parent)
+            .unwrap()
+            .as_millis()
     }
 
-    fn has_payload(&self) -> bool {
-        self.payload.is_some()
+    /// This node's own spreading factor/TX power toward its parent, as last recommended by the
+    /// parent (most robust configuration until a recommendation has been received).
+    pub fn own_adr(&self) -> AdrCommand {
+        self.context.own_adr
     }
-}
 
-struct DisplayableAction<'a>(&'a LightningAction, TimeMs);
-
-/// action as JSON to make it parseable
-macro_rules! action_to_json_string {
-    ($fmt:expr,$write:tt,$action:expr,$time:expr) => {
-        match $action {
-            Action::None => $write!($fmt, "{{\"kind\":\"none\"}}"),
-            Action::Wait { end } => {
-                $write!(
-                    $fmt,
-                    "{{\"kind\":\"wait\",\"duration\":{}}}",
-                    *end as i64 - $time as i64
-                )
-            }
-            Action::Receive { end, channel } => $write!(
-                $fmt,
-                "{{\"kind\":\"receive\",\"duration\":{},\"channel\":{}}}",
-                *end as i64 - $time as i64,
-                channel,
-            ),
-            Action::Transmit {
-                channel,
-                delay,
-                message: _,
-            } => $write!(
-                $fmt,
-                "{{\"kind\":\"transmit\",\"channel\":{},\"delay_ms\":{}}}",
-                channel,
-                delay.unwrap_or(0),
-            ),
-        }
-    };
-}
-
-impl core::fmt::Display for DisplayableAction<'_> {
-    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        action_to_json_string!(f, write, self.0, self.1)
+    /// Write this node's local view of the network (parent, if known, and children) as a
+    /// complete Graphviz `digraph { ... }`/`graph { ... }`. To visualize the whole discovered
+    /// topology, dump this for every node in a simulation and merge the statements inside the
+    /// braces into one graph.
+    pub fn fmt_dot(&self, kind: Kind, f: &mut impl core::fmt::Write) -> core::fmt::Result {
+        self.context.fmt_dot(self.id, kind, f)
     }
-}
 
-#[cfg(feature = "defmt")]
-impl defmt::Format for DisplayableAction<'_> {
-    fn format(&self, fmt: defmt::Formatter) {
-        use defmt::write;
-        action_to_json_string!(fmt, write, self.0, self.1)
+    /// Install a `ReconnectContext` snapshot, e.g. one loaded from non-volatile storage at boot,
+    /// so this node attempts a fast reconnect to its last known parent instead of the full
+    /// beacon-discovery cycle on its first `Reset`.
+    pub fn seed_reconnect_snapshot(&mut self, snapshot: ReconnectContext) {
+        self.context.reconnect = Some(snapshot);
     }
-}
 
-impl Lightning {
-    pub fn next_data_transmission(&self) -> TimeMs {
-        self.context.windows.next_kind(WindowKind::Parent).unwrap()
+    /// This node's current fast-reconnect snapshot, if any, suitable for persisting to
+    /// non-volatile storage so it survives a power cycle. See `seed_reconnect_snapshot`.
+    pub fn reconnect_snapshot(&self) -> Option<ReconnectContext> {
+        self.context.reconnect
     }
 }
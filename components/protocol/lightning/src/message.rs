@@ -21,6 +21,12 @@ pub enum Message {
     Beacon {
         /// Number of hops to the next sink
         hops: Hops,
+        /// ID of the sender, so a receiver considering it as a parent can identify the resulting
+        /// `Branch` (see `protocol_api::Branch`)
+        id: NodeId,
+        /// Sender's own parent's ID, if any (`None` for a sink), continuing that branch one hop
+        /// further
+        parent_id: Option<NodeId>,
         /// Sender's children channel
         children_channel: u8,
         /// Sender's parent channel
@@ -30,6 +36,11 @@ pub enum Message {
     Connect {
         /// ID of the sender
         id: NodeId,
+        /// Random tie-breaker for simultaneous `Connect` attempts: a parent hearing several
+        /// `Connect`s during one `ListenForConnect` window only acks the highest `nonce`
+        /// (borrowed from multistream-select 1.0's simultaneous-open resolution), so at most one
+        /// of them wins instead of both colliding and resetting.
+        nonce: u32,
     },
     /// Used to acknowledge a connect message
     ConnectAck {
@@ -37,13 +48,36 @@ pub enum Message {
         next_window_min: u8,
         /// ID of the intended recipient
         id: NodeId,
+        /// Maximum number of `NodeData` entries the child may include in its next `SendData`
+        /// (flow control, see `Context::compute_credits`)
+        credits: u8,
+    },
+    /// Emitted by a node when it resets while still connected to a parent, so the parent can
+    /// reclaim this child's window immediately instead of waiting out missed-window timeouts
+    Leave {
+        /// ID of the leaving node
+        id: NodeId,
     },
     /// Data of multiple nodes
-    Data(OwnAndChildData),
+    Data {
+        /// ID of the immediate sender of this message (not necessarily the data's source)
+        immediate_sender: NodeId,
+        data: OwnAndChildData,
+        /// Merkle Mountain Range root over `data`, see `accumulator`. Lets the eventual sink
+        /// verify the provenance and completeness of everything it received, even though it's
+        /// re-aggregated at every hop.
+        root: u32,
+    },
     /// Used to acknowledge data messages
     DataAck {
         /// Time offset in minutes at which receiver is expected to send a message
         next_window_min: u8,
+        /// Recommended spreading factor/TX power for this link, when the parent has enough SNR
+        /// history to make a recommendation
+        adr: Option<AdrCommand>,
+        /// Maximum number of `NodeData` entries the child may include in its next `SendData`
+        /// (flow control, see `Context::compute_credits`)
+        credits: u8,
     },
     Nack,
 }
@@ -54,36 +88,56 @@ macro_rules! message_to_json_string {
         match $message {
             Message::Beacon {
                 hops,
+                id,
+                parent_id,
                 children_channel,
                 parent_channel,
             } => {
                 $write!(
                     $fmt,
-                    "{{\"kind\":\"beacon\",\"hops\":{},\"children_channel\":{}",
+                    "{{\"kind\":\"beacon\",\"hops\":{},\"id\":{},\"children_channel\":{}",
                     hops,
+                    id,
                     children_channel
                 )?;
+                if let Some(parent_id) = parent_id {
+                    $write!($fmt, ",\"parent_id\":{}", parent_id)?;
+                }
                 if let Some(parent_channel) = parent_channel {
                     $write!($fmt, ",\"parent_channel\":{}", parent_channel)?;
                 }
                 $write!($fmt, "}}")
             }
-            Message::Connect { id } => {
-                $write!($fmt, "{{\"kind\":\"connect\",\"id\":{}}}", id)
+            Message::Connect { id, nonce } => {
+                $write!($fmt, "{{\"kind\":\"connect\",\"id\":{},\"nonce\":{}}}", id, nonce)
             }
             Message::ConnectAck {
                 next_window_min,
                 id,
+                credits,
             } => {
                 $write!(
                     $fmt,
-                    "{{\"kind\":\"ack\",\"next_window_min\":{},\"id\":{}}}",
+                    "{{\"kind\":\"ack\",\"next_window_min\":{},\"id\":{},\"credits\":{}}}",
                     next_window_min,
-                    id
+                    id,
+                    credits
                 )
             }
-            Message::Data(data) => {
-                $write!($fmt, "{{\"kind\":\"data\",\"data\":[")?;
+            Message::Leave { id } => {
+                $write!($fmt, "{{\"kind\":\"leave\",\"id\":{}}}", id)
+            }
+            Message::Data {
+                immediate_sender,
+                data,
+                root,
+            } => {
+                $write!(
+                    $fmt,
+                    "{{\"kind\":\"data\",\"immediate_sender\":{},\"root\":{},\"data\":[",
+                    immediate_sender,
+                    root
+                )?;
                 let mut iter = data.iter();
                 let mut next = iter.next();
                 while let Some(NodeData { source, payload }) = next {
@@ -95,12 +149,26 @@ macro_rules! message_to_json_string {
                 }
                 $write!($fmt, "]}}")
             }
-            Message::DataAck { next_window_min } => {
+            Message::DataAck {
+                next_window_min,
+                adr,
+                credits,
+            } => {
                 $write!(
                     $fmt,
-                    "{{\"kind\":\"ack\",\"next_window_min\":{}}}",
-                    next_window_min
-                )
+                    "{{\"kind\":\"ack\",\"next_window_min\":{},\"credits\":{}",
+                    next_window_min,
+                    credits
+                )?;
+                if let Some(adr) = adr {
+                    $write!(
+                        $fmt,
+                        ",\"adr_sf\":{},\"adr_tx_power_dbm\":{}",
+                        adr.spreading_factor,
+                        adr.tx_power_dbm
+                    )?;
+                }
+                $write!($fmt, "}}")
             }
             Message::Nack => {
                 $write!($fmt, "{{\"kind\":\"nack\"}}")
@@ -141,3 +209,12 @@ impl protocol_api::ProtocolData<Lightning> for NodeData {
         self.payload
     }
 }
+
+impl protocol_api::accumulator::Hashable for NodeData {
+    fn hash_leaf(&self) -> u32 {
+        let mut bytes = [0u8; 6];
+        bytes[..4].copy_from_slice(&self.source.to_le_bytes());
+        bytes[4..].copy_from_slice(&self.payload.to_le_bytes());
+        protocol_api::accumulator::fnv1a(&bytes)
+    }
+}
@@ -0,0 +1,37 @@
+//  _____       ______   ____
+// |_   _|     |  ____|/ ____|  Institute of Embedded Systems
+//   | |  _ __ | |__  | (___    Zurich University of Applied Sciences
+//   | | | '_ \|  __|  \___ \   8401 Winterthur, Switzerland
+//  _| |_| | | | |____ ____) |
+// |_____|_| |_|______|_____/
+//
+// Copyright 2025 Institute of Embedded Systems at Zurich University of Applied Sciences.
+// All rights reserved.
+// SPDX-License-Identifier: MIT
+
+use crate::*;
+
+/// Snapshot of the minimum state needed to attempt a fast reconnect to the same parent after a
+/// `Reset`, instead of redoing the full beacon-discovery cycle (`WaitBeforeFindingParent` →
+/// `ListenForBeacons` → `WaitForBestBeacon` → `DelayConnect`). `Context::reset` captures one of
+/// these whenever it tears down an established parent connection; `Lightning::seed_reconnect_snapshot`
+/// lets a caller install one loaded from non-volatile storage at boot, so a power-cycled node can
+/// also take the fast path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ReconnectContext {
+    /// Channel the parent listens for `Connect`s on.
+    pub parent_channel: Channel,
+    /// Our parent's own parent channel, so our children channel selection keeps avoiding it.
+    pub parents_parent_channel: Option<Channel>,
+    /// Hop count to the sink via this parent.
+    pub hops_to_sink: u8,
+    /// ID of this parent, as advertised in its beacons (see `Message::Beacon::id`).
+    pub parent_id: NodeId,
+    /// Our parent's own parent's ID, if any, see `Context::parents_parent_id`.
+    pub parents_parent_id: Option<NodeId>,
+    /// Offset, in minutes, to the parent data window that was scheduled when this snapshot was
+    /// taken. Informational only: the real window is re-established from the `ConnectAck` the
+    /// reconnect attempt gets back.
+    pub next_parent_window_min: u8,
+}
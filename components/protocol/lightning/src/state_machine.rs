@@ -12,47 +12,81 @@
 use crate::*;
 use heapless::Vec;
 
-impl Lightning {
+impl<S: EventSink> Lightning<S> {
     /// Get next state
     ///
     /// Each state's exit functionality is implemented here.
     #[must_use]
     pub(crate) fn next(
         &mut self,
-        time: TimeMs,
+        time: Instant,
         message: Option<Message>,
+        rx_snr_db: Option<i8>,
+        transmit_failed: bool,
         mut rng: impl RngCore,
     ) -> State {
+        if transmit_failed {
+            // a failed `Connect` is already retried by `ListenForConnectAck`'s back-off and a
+            // failed `Data` ends in a reset from `ListenForDataAck`, so a failure report is only
+            // acted on for the fire-and-forget sends below, which have neither: without this, the
+            // `(State::SendXxx { .. }, None)` arms further down would advance as if the transmit
+            // had gone out, silently losing the window it was scheduled in.
+            if matches!(
+                self.state,
+                State::SendBeacon { .. } | State::SendConnectAck { .. } | State::SendDataAck { .. }
+            ) {
+                warn!("transmit failed in {}, retrying the same window", self.state);
+                self.sink.emit(
+                    time.as_millis(),
+                    self.id,
+                    Event::TransmitFailed { state: self.state.state_as_string() },
+                );
+                return self.state.clone();
+            }
+        }
+
         match (&mut self.state, message) {
             (State::Reset, None) => {
-                event_log_reset!(time, self.id, self.is_sink);
-                self.context.reset();
-                if self.is_sink {
-                    self.context.hops_to_sink = Some(0);
-                    self.context.channels.set_random_children_channel(&mut rng);
-                    self.context.windows.push(Window {
-                        kind: WindowKind::Beacon,
-                        start: time + rng.next_u32() as TimeMs % BEACON_INTERVAL_MS,
-                    });
-                    State::Idle {
-                        end: self.context.windows.next(),
+                self.sink.emit(
+                    time.as_millis(),
+                    self.id,
+                    Event::Reset {
+                        is_sink: self.is_sink,
+                    },
+                );
+                match self.context.channels.parent {
+                    // tell our parent we're leaving so it can reclaim our window immediately,
+                    // instead of waiting out its missed-window timeout
+                    Some(parent_channel) => {
+                        let id = self.id;
+                        self.context.reset(time);
+                        State::SendLeave {
+                            channel: parent_channel,
+                            id,
+                        }
                     }
-                } else {
-                    State::WaitBeforeFindingParent {
-                        end: time + rng.next_u32() as TimeMs % BEACON_INTERVAL_MS,
+                    None => {
+                        self.context.reset(time);
+                        self.reset_into_idle_or_wait(time, rng)
                     }
                 }
             }
 
+            (State::SendLeave { .. }, None) => self.reset_into_idle_or_wait(time, rng),
+
             (State::WaitBeforeFindingParent { .. }, None) => State::ListenForBeacons {
                 channel: self.context.channels.public,
                 end: time + BEACON_INTERVAL_MS,
             },
 
-            (State::ListenForBeacons { end, channel, .. }, Some(Message::Beacon { hops, .. })) => {
+            (
+                State::ListenForBeacons { end, channel, .. },
+                Some(Message::Beacon { hops, id, .. }),
+            ) => {
                 if hops == 0 {
                     State::WaitForBestBeacon {
                         best_beacon_hops: hops,
+                        best_beacon_id: id,
                         end: time + adjust_for_clock_inaccuracies_sub(BEACON_INTERVAL_MS),
                     }
                 } else {
@@ -60,6 +94,7 @@ impl Lightning {
                         .potential_connect_beacons
                         .push(BeaconInfo {
                             hops,
+                            id,
                             time_seen: time,
                         })
                         .unwrap();
@@ -74,15 +109,18 @@ impl Lightning {
                     State::WaitBeforeFindingParent {
                         end: time
                             + BEACON_INTERVAL_MS / 2
-                            + rng.next_u32() as TimeMs % BEACON_INTERVAL_MS,
+                            + jitter(&mut rng, BEACON_INTERVAL_MS),
                     }
                 } else {
+                    // mirrors a longest/best-chain fork choice, but minimizes hop count instead
+                    // of maximizing it, with a deterministic tie-break on the lowest `NodeId` so
+                    // two candidates at the same depth don't flip-flop between runs
                     let best_beacon = self
                         .context
                         .potential_connect_beacons
                         .iter()
                         .reduce(|best_beacon, beacon| {
-                            if beacon.hops < best_beacon.hops {
+                            if (beacon.hops, beacon.id) < (best_beacon.hops, best_beacon.id) {
                                 beacon
                             } else {
                                 best_beacon
@@ -91,6 +129,7 @@ impl Lightning {
                         .unwrap();
                     State::WaitForBestBeacon {
                         best_beacon_hops: best_beacon.hops,
+                        best_beacon_id: best_beacon.id,
                         end: best_beacon.time_seen
                             + adjust_for_clock_inaccuracies_sub(BEACON_INTERVAL_MS),
                     }
@@ -106,11 +145,14 @@ impl Lightning {
 
             (
                 State::WaitForBestBeacon {
-                    best_beacon_hops, ..
+                    best_beacon_hops,
+                    best_beacon_id,
+                    ..
                 },
                 None,
             ) => State::ListenForBestBeacon {
                 best_beacon_hops: *best_beacon_hops,
+                best_beacon_id: *best_beacon_id,
                 end: time + BEST_BEACON_LISTEN_TIME,
                 channel: self.context.channels.public,
             },
@@ -118,20 +160,24 @@ impl Lightning {
             (
                 State::ListenForBestBeacon {
                     best_beacon_hops,
+                    best_beacon_id,
                     end,
                     channel,
                 },
                 Some(Message::Beacon {
                     hops,
+                    id,
+                    parent_id,
                     children_channel: parents_children_channel,
                     parent_channel: parents_parent_channel,
                 }),
             ) => {
-                if hops != *best_beacon_hops {
+                if hops != *best_beacon_hops || id != *best_beacon_id {
                     warn!("received wrong beacon");
                     // wrong beacon
                     State::ListenForBestBeacon {
                         best_beacon_hops: *best_beacon_hops,
+                        best_beacon_id: *best_beacon_id,
                         end: *end,
                         channel: *channel,
                     }
@@ -140,13 +186,16 @@ impl Lightning {
                         Some(hops) => self.context.hops_to_sink = Some(hops),
                         None => panic!("hop count too large"),
                     }
+                    self.context.parent_id = Some(id);
+                    self.context.parents_parent_id = parent_id;
                     self.context.channels.parent = Some(parents_children_channel);
                     self.context.channels.parents_parent_channel = parents_parent_channel;
                     State::DelayConnect {
-                        end: time + rng.next_u32() as TimeMs % RANDOM_CONNECT_RANGE_MS,
+                        end: time + jitter(&mut rng, RANDOM_CONNECT_RANGE_MS),
                         connect_ack_listen_time: time
                             + RANDOM_CONNECT_RANGE_MS
                             + CONNECT_RESPONSE_DELAY_MS,
+                        retry: 0,
                     }
                 }
             }
@@ -155,12 +204,13 @@ impl Lightning {
                 State::WaitBeforeFindingParent {
                     end: time
                         + BEACON_INTERVAL_MS / 2
-                        + rng.next_u32() as TimeMs % BEACON_INTERVAL_MS,
+                        + jitter(&mut rng, BEACON_INTERVAL_MS),
                 }
             }
             (
                 State::ListenForBestBeacon {
                     best_beacon_hops,
+                    best_beacon_id,
                     end,
                     channel,
                 },
@@ -169,6 +219,7 @@ impl Lightning {
                 // ignore non beacon messages
                 State::ListenForBestBeacon {
                     best_beacon_hops: *best_beacon_hops,
+                    best_beacon_id: *best_beacon_id,
                     end: *end,
                     channel: *channel,
                 }
@@ -177,6 +228,7 @@ impl Lightning {
             (
                 State::DelayConnect {
                     connect_ack_listen_time,
+                    retry,
                     ..
                 },
                 None,
@@ -184,24 +236,29 @@ impl Lightning {
                 channel: self.context.channels.parent.unwrap(),
                 id: self.id,
                 connect_ack_listen_time: *connect_ack_listen_time,
+                nonce: rng.next_u32(),
+                retry: *retry,
             },
 
             (
                 State::SendConnect {
                     id,
                     connect_ack_listen_time,
+                    retry,
                     ..
                 },
                 None,
             ) => State::WaitForConnectAck {
                 end: *connect_ack_listen_time,
                 id: *id,
+                retry: *retry,
             },
 
-            (State::WaitForConnectAck { id, .. }, None) => State::ListenForConnectAck {
+            (State::WaitForConnectAck { id, retry, .. }, None) => State::ListenForConnectAck {
                 channel: self.context.channels.parent.unwrap(),
                 end: time + RESPONSE_LISTEN_DURATION_MS,
                 id: *id,
+                retry: *retry,
             },
 
             (
@@ -209,29 +266,66 @@ impl Lightning {
                 Some(Message::ConnectAck {
                     next_window_min,
                     id: ack_id,
+                    credits,
                 }),
             ) if *id == ack_id => {
                 info!("successfully connected to parent");
-                self.context.channels.set_random_children_channel(&mut rng);
-                self.context.windows.push(Window {
-                    kind: WindowKind::Parent,
-                    start: time
-                        + adjust_for_clock_inaccuracies(next_window_min as TimeMs * MS_PER_MIN),
-                });
-                self.context.windows.push(Window {
-                    kind: WindowKind::Beacon,
-                    // add some randomness to reduce the probability of being in sync with siblings
-                    start: time
-                        + BEACON_INTERVAL_MS
-                        + rng.next_u32() as TimeMs % BEACON_INTERVAL_MS,
-                });
-                State::Idle {
-                    end: self.context.windows.next(),
-                }
+                self.on_connected(time, next_window_min, credits, rng)
             }
-            (State::ListenForConnectAck { id, .. }, message) => {
+            (State::ListenForConnectAck { id, retry, .. }, message) => {
                 warn!("expected connect ack for id {:x}, got: {:?}", id, message);
-                State::Reset
+                if *retry < MAX_CONNECT_RETRIES {
+                    // we lost (or got no reply at all): back off with a fresh, larger random
+                    // offset and try again, instead of a full reset
+                    let retry = *retry + 1;
+                    let range = RANDOM_CONNECT_RANGE_MS * (retry as u64 + 1);
+                    State::DelayConnect {
+                        end: time + jitter(&mut rng, range),
+                        connect_ack_listen_time: time + range + CONNECT_RESPONSE_DELAY_MS,
+                        retry,
+                    }
+                } else {
+                    error!("gave up connecting to parent after {} retries", retry);
+                    State::Reset
+                }
+            }
+
+            (State::SendReconnect { channel, id, .. }, None) => State::WaitForReconnectAck {
+                end: time + CONNECT_RESPONSE_DELAY_MS,
+                channel: *channel,
+                id: *id,
+            },
+
+            (State::WaitForReconnectAck { channel, id, .. }, None) => {
+                State::ListenForReconnectAck {
+                    channel: *channel,
+                    end: time + RESPONSE_LISTEN_DURATION_MS,
+                    id: *id,
+                }
+            }
+
+            (
+                State::ListenForReconnectAck { id, .. },
+                Some(Message::ConnectAck {
+                    next_window_min,
+                    id: ack_id,
+                    credits,
+                }),
+            ) if *id == ack_id => {
+                info!("successfully reconnected to parent");
+                self.on_connected(time, next_window_min, credits, rng)
+            }
+            (State::ListenForReconnectAck { .. }, message) => {
+                warn!(
+                    "expected reconnect ack, got: {:?}, falling back to full discovery",
+                    message
+                );
+                self.context.channels.parent = None;
+                self.context.channels.parents_parent_channel = None;
+                self.context.hops_to_sink = None;
+                self.context.parent_id = None;
+                self.context.parents_parent_id = None;
+                self.reset_into_idle_or_wait(time, rng)
             }
 
             (State::Idle { .. }, None) => {
@@ -248,23 +342,34 @@ impl Lightning {
                     } => State::SendBeacon {
                         channel: self.context.channels.public,
                         hops: self.context.hops_to_sink.unwrap(),
+                        id: self.id,
+                        parent_id: self.context.parent_id,
                         children_channel: self.context.channels.children.unwrap(),
                         parent_channel: self.context.channels.parent,
                     },
                     Window {
-                        kind: WindowKind::Child,
+                        kind: WindowKind::Child(child_id),
                         start: _,
                     } => State::ListenForData {
                         channel: self.context.channels.children.unwrap(),
                         end: time + DATA_RECEIVE_WINDOW,
+                        child_id,
                     },
                     Window {
                         kind: WindowKind::Parent,
                         start: _,
                     } => {
+                        // reserve one credit for this node's own payload, if it has one queued,
+                        // and defer anything beyond our granted credits into the next parent
+                        // window instead of dropping it
+                        let reserve_own = usize::from(self.payload.is_some());
+                        let take = (self.context.credits as usize)
+                            .saturating_sub(reserve_own)
+                            .min(self.context.child_data.len());
                         let mut data: OwnAndChildData =
-                            Vec::from_slice(self.context.child_data.as_slice()).unwrap();
-                        self.context.child_data.clear();
+                            Vec::from_slice(&self.context.child_data[..take]).unwrap();
+                        self.context.child_data = Vec::from_slice(&self.context.child_data[take..])
+                            .unwrap();
                         if let Some(d) = self.payload.take() {
                             data.push(NodeData {
                                 source: self.id,
@@ -277,40 +382,83 @@ impl Lightning {
                         State::SendData {
                             channel: self.context.channels.parent.unwrap(),
                             data,
+                            immediate_sender: self.id,
                         }
                     }
                 }
             }
 
             (State::SendBeacon { .. }, None) => {
-                self.context.windows.push(Window {
-                    kind: WindowKind::Beacon,
-                    start: time + BEACON_INTERVAL_MS,
-                });
+                let public_channel = self.context.channels.public;
+                self.context.windows.push(
+                    Window {
+                        kind: WindowKind::Beacon,
+                        start: time + BEACON_INTERVAL_MS,
+                    },
+                    public_channel,
+                );
                 State::ListenForConnect {
                     channel: self.context.channels.children.unwrap(),
                     end: time + adjust_for_clock_inaccuracies(RANDOM_CONNECT_RANGE_MS + SEND_DELAY),
+                    best: None,
                 }
             }
 
-            (State::ListenForConnect { end, .. }, Some(Message::Connect { id })) => {
-                event_log_new_child!(time, self.id, id);
-                // Delay sending connect ack to after end of listening for connect window to avoid
-                // collisions with other potential connect messages.
-                State::DelayConnectAck {
-                    end: *end + adjust_for_clock_inaccuracies(CONNECT_RESPONSE_DELAY_MS),
-                    id,
+            // Remember the highest-nonce `Connect` seen so far instead of acking the first one we
+            // hear, so two children that collide within the same window get a deterministic
+            // winner (see `Message::Connect`) rather than both going unacked.
+            (
+                State::ListenForConnect { end, channel, best },
+                Some(Message::Connect { id, nonce }),
+            ) => {
+                let best = match *best {
+                    Some((best_nonce, _)) if best_nonce >= nonce => *best,
+                    _ => Some((nonce, id)),
+                };
+                State::ListenForConnect {
+                    end: *end,
+                    channel: *channel,
+                    best,
                 }
             }
-            (State::ListenForConnect { .. }, None) => State::Idle {
-                end: self.context.windows.next(),
+            (State::ListenForConnect { best, .. }, None) => match *best {
+                Some((_, id)) => {
+                    self.sink.emit(time.as_millis(), self.id, Event::NewChild { child_id: id });
+                    // Delay sending connect ack to after end of listening for connect window to
+                    // avoid collisions with other potential connect messages.
+                    State::DelayConnectAck {
+                        end: time + adjust_for_clock_inaccuracies(CONNECT_RESPONSE_DELAY_MS),
+                        id,
+                    }
+                }
+                None => State::Idle {
+                    end: self.context.windows.next(),
+                },
             },
-            (State::ListenForConnect { end, channel }, Some(message)) => {
+            (State::ListenForConnect { end, channel, best }, Some(Message::Leave { id })) => {
+                if self.context.windows.pop_child(id).is_some() {
+                    self.context.clear_missed_child_window(id);
+                    self.sink.emit(time.as_millis(), self.id, Event::ChildLost { child_id: id });
+                }
+                if *end > time {
+                    State::ListenForConnect {
+                        end: *end,
+                        channel: *channel,
+                        best: *best,
+                    }
+                } else {
+                    State::Idle {
+                        end: self.context.windows.next(),
+                    }
+                }
+            }
+            (State::ListenForConnect { end, channel, best }, Some(message)) => {
                 warn!("expected connect, got: {:?}", message);
                 if *end > time {
                     State::ListenForConnect {
                         end: *end,
                         channel: *channel,
+                        best: *best,
                     }
                 } else {
                     State::Idle {
@@ -321,16 +469,38 @@ impl Lightning {
 
             (State::DelayConnectAck { id, .. }, None) => {
                 let mut child_window = Window {
-                    kind: WindowKind::Child,
-                    start: time + CHILD_DATA_INTERVAL_MIN as TimeMs * MS_PER_MIN,
+                    kind: WindowKind::Child(*id),
+                    start: time + CHILD_DATA_INTERVAL,
                 };
                 child_window.delay(&self.context.windows, WindowDelayIncrement::Minutes);
+
+                // `delay` above only resolves overlaps with windows already queued for the next
+                // cycle; it doesn't know this window will recur forever, so it can still hand out
+                // a slot that only collides with our own beacon or another child several cycles
+                // down the line. Run the same hyperperiod feasibility sweep `on_connected` runs
+                // for our own parent window before we promise `id` this slot.
+                if !self.context.windows.is_feasible(&child_window, &WINDOW_PERIODS) {
+                    warn!(
+                        "no feasible recurring window for child {}, rejecting connect",
+                        id
+                    );
+                    return State::Idle {
+                        end: self.context.windows.next(),
+                    };
+                }
+
                 let next_child_window_min = child_window.get_offset_min(time) as u8;
+                // +1: this about-to-connect child doesn't have a window yet to be counted by
+                // `child_count`
+                let credits = self
+                    .context
+                    .compute_credits(self.context.windows.child_count() + 1);
                 State::SendConnectAck {
                     child_window,
                     channel: self.context.channels.children.unwrap(),
                     next_child_window_min,
                     id: *id,
+                    credits,
                 }
             }
 
@@ -343,8 +513,9 @@ impl Lightning {
                 None,
             ) => {
                 // adjust window start time to compensate for message time on air
-                child_window.start = time + *next_child_window_min as TimeMs * MS_PER_MIN;
-                self.context.windows.push(child_window.clone());
+                child_window.start = time + minutes(*next_child_window_min);
+                let children_channel = self.context.channels.children.unwrap();
+                self.context.windows.push(child_window.clone(), children_channel);
                 if self.context.windows.is_full() {
                     self.context.windows.pop_kind(WindowKind::Beacon);
                 }
@@ -358,13 +529,31 @@ impl Lightning {
                 end: time + RESPONSE_LISTEN_DURATION_MS,
             },
 
-            (State::ListenForDataAck { .. }, Some(Message::DataAck { next_window_min })) => {
+            (
+                State::ListenForDataAck { .. },
+                Some(Message::DataAck {
+                    next_window_min,
+                    adr,
+                    credits,
+                }),
+            ) => {
                 info!("parent acked data");
-                self.context.windows.push(Window {
-                    kind: WindowKind::Parent,
-                    start: time
-                        + adjust_for_clock_inaccuracies(next_window_min as TimeMs * MS_PER_MIN),
-                });
+                self.context.credits = credits;
+                if let Some(adr) = adr {
+                    info!(
+                        "adr: sf {} tx power {} dBm",
+                        adr.spreading_factor, adr.tx_power_dbm
+                    );
+                    self.context.own_adr = adr;
+                }
+                let parent_channel = self.context.channels.parent.unwrap();
+                self.context.windows.push(
+                    Window {
+                        kind: WindowKind::Parent,
+                        start: time + adjust_for_clock_inaccuracies(minutes(next_window_min)),
+                    },
+                    parent_channel,
+                );
                 State::Idle {
                     end: self.context.windows.next(),
                 }
@@ -375,28 +564,79 @@ impl Lightning {
                 State::Reset
             }
 
-            (State::ListenForData { .. }, Some(Message::Data(child_data))) => {
-                // TODO: handle case where child data buffer is not big enough
-                self.context
-                    .child_data
-                    .extend_from_slice(child_data.as_slice())
-                    .expect("child data buffer not big enough");
-                // info!("new child data: {:?}", child_data);
+            (
+                State::ListenForData { .. },
+                Some(Message::Data {
+                    immediate_sender,
+                    data: child_data,
+                    root,
+                }),
+            ) => {
+                self.context.clear_missed_child_window(immediate_sender);
+                if protocol_api::accumulator::verify_root::<ACC_PEAKS>(&child_data, root) {
+                    // a well-behaved child stays within the credits we granted it, so this should
+                    // never run out of room; if it does (e.g. a stale/misbehaving child), drop the
+                    // overflow instead of panicking
+                    for entry in child_data {
+                        if self.context.child_data.push(entry).is_err() {
+                            warn!(
+                                "child data buffer full, dropping entries from {:x}",
+                                immediate_sender
+                            );
+                            break;
+                        }
+                    }
+                } else {
+                    // records were lost, duplicated, or injected somewhere between `immediate_sender`
+                    // and us: don't let a tampered/corrupted batch poison what we aggregate upward
+                    error!("data from {:x} failed MMR root check, dropping batch", immediate_sender);
+                    self.sink.emit(
+                        time.as_millis(),
+                        self.id,
+                        Event::DataIntegrityCheckFailed { child_id: immediate_sender },
+                    );
+                }
+                let adr =
+                    rx_snr_db.map(|snr| self.context.recommend_child_adr(immediate_sender, snr));
                 let mut child_window = Window {
-                    kind: WindowKind::Child,
-                    start: time + CHILD_DATA_INTERVAL_MIN as TimeMs * MS_PER_MIN,
+                    kind: WindowKind::Child(immediate_sender),
+                    start: time + CHILD_DATA_INTERVAL,
                 };
                 child_window.delay(&self.context.windows, WindowDelayIncrement::Minutes);
                 let next_child_window_min = child_window.get_offset_min(time) as u8;
+                let credits = self
+                    .context
+                    .compute_credits(self.context.windows.child_count());
                 State::SendDataAck {
                     child_window,
                     channel: self.context.channels.children.unwrap(),
                     next_child_window_min,
+                    child_id: immediate_sender,
+                    adr,
+                    credits,
                 }
             }
-            (State::ListenForData { .. }, message) => {
+            (State::ListenForData { child_id, .. }, Some(Message::Leave { id })) if *child_id == id => {
+                if self.context.windows.pop_child(id).is_some() {
+                    self.context.clear_missed_child_window(id);
+                    self.sink.emit(time.as_millis(), self.id, Event::ChildLost { child_id: id });
+                }
+                State::Idle {
+                    end: self.context.windows.next(),
+                }
+            }
+            (State::ListenForData { child_id, .. }, message) => {
                 error!("expected data, got: {:?}", message);
-                error!("child gone");
+                warn!("child {:x} missed its window", child_id);
+                let missed = self.context.record_missed_child_window(*child_id);
+                if missed >= MAX_MISSED_CHILD_WINDOWS {
+                    error!("child gone");
+                    if self.context.windows.pop_child(*child_id).is_some() {
+                        self.sink
+                            .emit(time.as_millis(), self.id, Event::ChildLost { child_id: *child_id });
+                    }
+                    self.context.clear_missed_child_window(*child_id);
+                }
                 State::Idle {
                     end: self.context.windows.next(),
                 }
@@ -411,8 +651,9 @@ impl Lightning {
                 None,
             ) => {
                 // adjust window start time to compensate for message time on air
-                child_window.start = time + *next_child_window_min as TimeMs * MS_PER_MIN;
-                self.context.windows.push(child_window.clone());
+                child_window.start = time + minutes(*next_child_window_min);
+                let children_channel = self.context.channels.children.unwrap();
+                self.context.windows.push(child_window.clone(), children_channel);
                 State::Idle {
                     end: self.context.windows.next(),
                 }
@@ -433,6 +674,81 @@ impl Lightning {
             (State::Reset, Some(_)) => unreachable!(),
             (State::DelayConnectAck { .. }, Some(_)) => unreachable!(),
             (State::WaitForConnectAck { .. }, Some(_)) => unreachable!(),
+            (State::SendLeave { .. }, Some(_)) => unreachable!(),
+            (State::SendReconnect { .. }, Some(_)) => unreachable!(),
+            (State::WaitForReconnectAck { .. }, Some(_)) => unreachable!(),
+        }
+    }
+
+    /// Finish connecting to a parent (fresh or via a fast reconnect): grant the credits it offered,
+    /// pick our own children channel, and schedule our parent and beacon windows.
+    fn on_connected(
+        &mut self,
+        time: Instant,
+        next_window_min: u8,
+        credits: u8,
+        mut rng: impl RngCore,
+    ) -> State {
+        let parent_window = Window {
+            kind: WindowKind::Parent,
+            start: time + adjust_for_clock_inaccuracies(minutes(next_window_min)),
+        };
+        if !self.context.windows.is_feasible(&parent_window, &WINDOW_PERIODS) {
+            error!("parent's proposed window schedule is infeasible, giving up");
+            return State::Reset;
+        }
+        self.context.credits = credits;
+        self.context.channels.set_random_children_channel(&mut rng);
+        let parent_channel = self.context.channels.parent.unwrap();
+        self.context.windows.push(parent_window, parent_channel);
+        let public_channel = self.context.channels.public;
+        self.context.windows.push(
+            Window {
+                kind: WindowKind::Beacon,
+                // add some randomness to reduce the probability of being in sync with siblings
+                start: time + BEACON_INTERVAL_MS + jitter(&mut rng, BEACON_INTERVAL_MS),
+            },
+            public_channel,
+        );
+        State::Idle {
+            end: self.context.windows.next(),
+        }
+    }
+
+    /// Shared tail of the reset flow: start a sink's beacon schedule, attempt a fast reconnect if
+    /// we have a `ReconnectContext` snapshot for a non-sink, or fall back to looking for a parent
+    /// from scratch. Used both for a fresh `Reset` with no prior parent and after `SendLeave` has
+    /// notified a prior parent.
+    fn reset_into_idle_or_wait(&mut self, time: Instant, mut rng: impl RngCore) -> State {
+        if self.is_sink {
+            self.context.hops_to_sink = Some(0);
+            self.context.channels.set_random_children_channel(&mut rng);
+            let public_channel = self.context.channels.public;
+            self.context.windows.push(
+                Window {
+                    kind: WindowKind::Beacon,
+                    start: time + jitter(&mut rng, BEACON_INTERVAL_MS),
+                },
+                public_channel,
+            );
+            State::Idle {
+                end: self.context.windows.next(),
+            }
+        } else if let Some(snapshot) = self.context.reconnect.take() {
+            self.context.channels.parent = Some(snapshot.parent_channel);
+            self.context.channels.parents_parent_channel = snapshot.parents_parent_channel;
+            self.context.hops_to_sink = Some(snapshot.hops_to_sink);
+            self.context.parent_id = Some(snapshot.parent_id);
+            self.context.parents_parent_id = snapshot.parents_parent_id;
+            State::SendReconnect {
+                channel: snapshot.parent_channel,
+                id: self.id,
+                nonce: rng.next_u32(),
+            }
+        } else {
+            State::WaitBeforeFindingParent {
+                end: time + jitter(&mut rng, BEACON_INTERVAL_MS),
+            }
         }
     }
 }
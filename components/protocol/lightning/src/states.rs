@@ -9,82 +9,126 @@
 // All rights reserved.
 // SPDX-License-Identifier: MIT
 
+use serde::{Deserialize, Serialize};
+
 use crate::*;
 
 /// Protocol state
 ///
 /// Content of a state is what is required to produce the state's action or information for the
 /// following state.
-#[derive(Debug, Clone, PartialEq, Default)]
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
 pub(crate) enum State {
     #[default]
     Reset,
     ListenForBeacons {
-        end: TimeMs,
+        end: Instant,
         channel: Channel,
     },
     WaitBeforeFindingParent {
-        end: TimeMs,
+        end: Instant,
     },
     WaitForBestBeacon {
         best_beacon_hops: Hops,
-        end: TimeMs,
+        /// ID of the chosen candidate branch's tip, see `Message::Beacon::id`.
+        best_beacon_id: NodeId,
+        end: Instant,
     },
     ListenForBestBeacon {
         best_beacon_hops: Hops,
-        end: TimeMs,
+        best_beacon_id: NodeId,
+        end: Instant,
         channel: Channel,
     },
     DelayConnect {
-        end: TimeMs,
-        connect_ack_listen_time: TimeMs,
+        end: Instant,
+        connect_ack_listen_time: Instant,
+        /// Number of prior losing/un-acked `Connect` attempts, see `MAX_CONNECT_RETRIES`.
+        retry: u8,
     },
     SendConnect {
         channel: Channel,
         id: NodeId,
-        connect_ack_listen_time: TimeMs,
+        connect_ack_listen_time: Instant,
+        /// Tie-breaker sent along with this `Connect`, see `Message::Connect`.
+        nonce: u32,
+        retry: u8,
     },
     WaitForConnectAck {
-        end: TimeMs,
+        end: Instant,
         id: NodeId,
+        retry: u8,
     },
     ListenForConnectAck {
-        end: TimeMs,
+        end: Instant,
         channel: Channel,
         id: NodeId,
+        retry: u8,
     },
     Idle {
-        end: TimeMs,
+        end: Instant,
     },
     SendBeacon {
         channel: Channel,
         hops: Hops,
+        id: NodeId,
+        parent_id: Option<NodeId>,
         children_channel: Channel,
         parent_channel: Option<Channel>,
     },
     ListenForData {
-        end: TimeMs,
+        end: Instant,
+        channel: Channel,
+        /// ID of the child expected to send data in this window
+        child_id: NodeId,
+    },
+    /// Tell our own parent we're leaving, e.g. when resetting while connected
+    SendLeave {
+        channel: Channel,
+        id: NodeId,
+    },
+    /// Attempt a single targeted reconnect to a previously known parent (from a
+    /// `ReconnectContext` snapshot), skipping the full beacon-discovery cycle.
+    SendReconnect {
+        channel: Channel,
+        id: NodeId,
+        nonce: u32,
+    },
+    WaitForReconnectAck {
+        end: Instant,
         channel: Channel,
+        id: NodeId,
+    },
+    ListenForReconnectAck {
+        end: Instant,
+        channel: Channel,
+        id: NodeId,
     },
     SendDataAck {
         child_window: Window,
         channel: Channel,
         next_child_window_min: u8,
+        child_id: NodeId,
+        adr: Option<AdrCommand>,
+        credits: u8,
     },
     SendData {
         channel: Channel,
         data: OwnAndChildData,
+        immediate_sender: NodeId,
     },
     ListenForDataAck {
-        end: TimeMs,
+        end: Instant,
         channel: Channel,
     },
     ListenForConnect {
-        end: TimeMs,
+        end: Instant,
         channel: Channel,
+        /// `(nonce, id)` of the best (highest-nonce) `Connect` seen so far this window, if any.
+        best: Option<(u32, NodeId)>,
     },
     DelayConnectAck {
-        end: TimeMs,
+        end: Instant,
         id: NodeId,
     },
     SendConnectAck {
@@ -92,6 +136,7 @@ pub(crate) enum State {
         channel: Channel,
         next_child_window_min: u8,
         id: NodeId,
+        credits: u8,
     },
 }
 
@@ -108,11 +153,13 @@ impl State {
             State::WaitForBestBeacon {
                 end,
                 best_beacon_hops: _,
+                best_beacon_id: _,
             } => Action::Wait { end: *end },
             State::ListenForBestBeacon {
                 end,
                 channel,
                 best_beacon_hops: _,
+                best_beacon_id: _,
             } => Action::Receive {
                 end: *end,
                 channel: *channel,
@@ -121,16 +168,26 @@ impl State {
                 channel,
                 id,
                 connect_ack_listen_time: _,
+                nonce,
+                retry: _,
             } => Action::Transmit {
                 channel: *channel,
-                message: Message::Connect { id: *id },
-                delay: Some(SEND_DELAY),
+                message: Message::Connect {
+                    id: *id,
+                    nonce: *nonce,
+                },
+                delay: Some(send_delay_action()),
             },
-            State::WaitForConnectAck { end, id: _ } => Action::Wait { end: *end },
+            State::WaitForConnectAck {
+                end,
+                id: _,
+                retry: _,
+            } => Action::Wait { end: *end },
             State::ListenForConnectAck {
                 channel,
                 end,
                 id: _,
+                retry: _,
             } => Action::Receive {
                 end: *end,
                 channel: *channel,
@@ -139,18 +196,26 @@ impl State {
             State::SendBeacon {
                 channel,
                 hops,
+                id,
+                parent_id,
                 children_channel,
                 parent_channel,
             } => Action::Transmit {
                 channel: *channel,
                 message: Message::Beacon {
                     hops: *hops,
+                    id: *id,
+                    parent_id: *parent_id,
                     children_channel: *children_channel,
                     parent_channel: *parent_channel,
                 },
-                delay: Some(SEND_DELAY),
+                delay: Some(send_delay_action()),
             },
-            State::ListenForConnect { channel, end } => Action::Receive {
+            State::ListenForConnect {
+                channel,
+                end,
+                best: _,
+            } => Action::Receive {
                 end: *end,
                 channel: *channel,
             },
@@ -159,38 +224,76 @@ impl State {
                 channel,
                 next_child_window_min: next_window_min,
                 id,
+                credits,
             } => Action::Transmit {
                 channel: *channel,
                 message: Message::ConnectAck {
                     next_window_min: *next_window_min,
                     id: *id,
+                    credits: *credits,
                 },
-                delay: Some(SEND_DELAY),
+                delay: Some(send_delay_action()),
             },
             State::DelayConnectAck { end, id: _ } => Action::Wait { end: *end },
-            State::ListenForData { channel, end } => Action::Receive {
+            State::ListenForData {
+                channel,
+                end,
+                child_id: _,
+            } => Action::Receive {
                 end: *end,
                 channel: *channel,
             },
-            State::SendData { channel, data } => Action::Transmit {
+            State::SendLeave { channel, id } => Action::Transmit {
                 channel: *channel,
-                message: Message::Data(data.clone()),
-                delay: Some(SEND_DELAY),
+                message: Message::Leave { id: *id },
+                delay: Some(send_delay_action()),
+            },
+            State::SendReconnect { channel, id, nonce } => Action::Transmit {
+                channel: *channel,
+                message: Message::Connect {
+                    id: *id,
+                    nonce: *nonce,
+                },
+                delay: Some(send_delay_action()),
+            },
+            State::WaitForReconnectAck { end, .. } => Action::Wait { end: *end },
+            State::ListenForReconnectAck { end, channel, .. } => Action::Receive {
+                end: *end,
+                channel: *channel,
+            },
+            State::SendData {
+                channel,
+                data,
+                immediate_sender,
+            } => Action::Transmit {
+                channel: *channel,
+                message: Message::Data {
+                    immediate_sender: *immediate_sender,
+                    data: data.clone(),
+                    root: protocol_api::accumulator::root::<ACC_PEAKS>(data).unwrap_or(0),
+                },
+                delay: Some(send_delay_action()),
             },
             State::DelayConnect {
                 end,
                 connect_ack_listen_time: _,
+                retry: _,
             } => Action::Wait { end: *end },
             State::SendDataAck {
                 child_window: _,
                 channel,
                 next_child_window_min: next_window_min,
+                child_id: _,
+                adr,
+                credits,
             } => Action::Transmit {
                 channel: *channel,
                 message: Message::DataAck {
                     next_window_min: *next_window_min,
+                    adr: *adr,
+                    credits: *credits,
                 },
-                delay: Some(SEND_DELAY),
+                delay: Some(send_delay_action()),
             },
             State::ListenForDataAck { channel, end } => Action::Receive {
                 channel: *channel,
@@ -199,8 +302,10 @@ impl State {
         }
     }
 
-    /// state as JSON to make it parseable
-    fn state_as_string(&self) -> &str {
+    /// This state's variant name, for human-readable logging (`LogSink`) and `Display`/`defmt`.
+    /// Drops every field, so it cannot round-trip a state; see `trace::TraceRecord` for a fully
+    /// serializable record of a transition.
+    pub(crate) fn state_as_string(&self) -> &'static str {
         match self {
             State::DelayConnect { .. } => "DelayConnect",
             State::DelayConnectAck { .. } => "DelayConnectAck",
@@ -217,6 +322,10 @@ impl State {
             State::SendConnectAck { .. } => "SendConnectAck",
             State::SendData { .. } => "SendData",
             State::SendDataAck { .. } => "SendDataAck",
+            State::SendLeave { .. } => "SendLeave",
+            State::SendReconnect { .. } => "SendReconnect",
+            State::WaitForReconnectAck { .. } => "WaitForReconnectAck",
+            State::ListenForReconnectAck { .. } => "ListenForReconnectAck",
             State::WaitBeforeFindingParent { .. } => "WaitBeforeFindingParent",
             State::WaitForBestBeacon { .. } => "WaitForBestBeacon",
             State::WaitForConnectAck { .. } => "WaitForConnectAck",
@@ -0,0 +1,212 @@
+//  _____       ______   ____
+// |_   _|     |  ____|/ ____|  Institute of Embedded Systems
+//   | |  _ __ | |__  | (___    Zurich University of Applied Sciences
+//   | | | '_ \|  __|  \___ \   8401 Winterthur, Switzerland
+//  _| |_| | | | |____ ____) |
+// |_____|_| |_|______|_____/
+//
+// Copyright 2025 Institute of Embedded Systems at Zurich University of Applied Sciences.
+// All rights reserved.
+// SPDX-License-Identifier: MIT
+
+//! Typed replacements for the bare milliseconds-as-`u64` this crate used to thread everywhere:
+//! [`Instant`] for an absolute point in time, [`Duration`] for a span between two of them. Kept as
+//! distinct types so the compiler rejects adding two instants together or mixing a duration into a
+//! comparison that expects an absolute time, which a raw `u64` never could.
+//!
+//! A build with the `embassy-time` feature reuses `embassy_time`'s own `Instant`/`Duration`
+//! directly, so `Lightning::progress` can eventually be driven straight from the embassy timer
+//! queue on STM32WL targets. Everything else (including `cargo test`, which stays `std`) falls
+//! back to a thin shim with the same millisecond-granularity API, except under `cfg(test)`, where
+//! `Instant`/`Duration` are plain `u64` aliases so the existing `Windows` unit tests keep
+//! constructing windows from plain integer literals.
+
+#[cfg(test)]
+mod imp {
+    pub(crate) type Instant = u64;
+    pub(crate) type Duration = u64;
+}
+
+/// `Instant`/`Duration` are plain `u64` aliases under `cfg(test)` (see `imp` above), so unlike the
+/// `embassy-time`/shim builds they have no inherent `from_millis`/`as_millis` of their own. This
+/// gives them one, so window/state-machine code doesn't need `cfg(test)` branches of its own just
+/// to convert to and from milliseconds.
+#[cfg(test)]
+pub(crate) trait TimeValue: Sized {
+    fn from_millis(millis: u64) -> Self;
+    fn as_millis(self) -> u64;
+}
+
+#[cfg(test)]
+impl TimeValue for u64 {
+    fn from_millis(millis: u64) -> Self {
+        millis
+    }
+
+    fn as_millis(self) -> u64 {
+        self
+    }
+}
+
+#[cfg(all(not(test), feature = "embassy-time"))]
+mod imp {
+    pub(crate) use embassy_time::{Duration, Instant};
+}
+
+/// `embassy_time::Instant` has no `saturating_sub`; this fills the gap via its tick count so the
+/// rest of the crate doesn't need an embassy-specific branch just for this.
+#[cfg(all(not(test), feature = "embassy-time"))]
+pub(crate) trait InstantSaturatingSub {
+    fn saturating_sub(self, other: Self) -> Duration;
+}
+
+#[cfg(all(not(test), feature = "embassy-time"))]
+impl InstantSaturatingSub for Instant {
+    fn saturating_sub(self, other: Instant) -> Duration {
+        Duration::from_ticks(self.as_ticks().saturating_sub(other.as_ticks()))
+    }
+}
+
+/// `embassy_time::Duration` has no `saturating_sub` either; same fix as `InstantSaturatingSub`,
+/// via tick counts.
+#[cfg(all(not(test), feature = "embassy-time"))]
+pub(crate) trait DurationSaturatingSub {
+    fn saturating_sub(self, other: Self) -> Duration;
+}
+
+#[cfg(all(not(test), feature = "embassy-time"))]
+impl DurationSaturatingSub for Duration {
+    fn saturating_sub(self, other: Duration) -> Duration {
+        Duration::from_ticks(self.as_ticks().saturating_sub(other.as_ticks()))
+    }
+}
+
+#[cfg(all(not(test), not(feature = "embassy-time")))]
+mod imp {
+    use core::fmt;
+    use core::ops::{Add, Mul, Sub};
+
+    use serde::{Deserialize, Serialize};
+
+    /// An absolute point in time, as milliseconds since an arbitrary epoch (node boot, or
+    /// simulation start).
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+    pub(crate) struct Instant(u64);
+
+    impl Instant {
+        pub(crate) const fn from_millis(millis: u64) -> Self {
+            Self(millis)
+        }
+
+        pub(crate) const fn as_millis(self) -> u64 {
+            self.0
+        }
+
+        /// `self - other`, clamped to zero instead of underflowing/panicking.
+        pub(crate) fn saturating_sub(self, other: Instant) -> Duration {
+            Duration::from_millis(self.0.saturating_sub(other.0))
+        }
+    }
+
+    impl Add<Duration> for Instant {
+        type Output = Instant;
+        fn add(self, rhs: Duration) -> Instant {
+            Instant(self.0 + rhs.0)
+        }
+    }
+
+    impl Sub<Duration> for Instant {
+        type Output = Instant;
+        fn sub(self, rhs: Duration) -> Instant {
+            Instant(self.0 - rhs.0)
+        }
+    }
+
+    impl Sub<Instant> for Instant {
+        type Output = Duration;
+        fn sub(self, rhs: Instant) -> Duration {
+            Duration(self.0 - rhs.0)
+        }
+    }
+
+    impl fmt::Display for Instant {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{}", self.0)
+        }
+    }
+
+    /// A span of time, as milliseconds.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+    pub(crate) struct Duration(u64);
+
+    impl Duration {
+        pub(crate) const fn from_millis(millis: u64) -> Self {
+            Self(millis)
+        }
+
+        pub(crate) const fn as_millis(self) -> u64 {
+            self.0
+        }
+
+        /// `self - other`, clamped to zero instead of underflowing/panicking.
+        pub(crate) fn saturating_sub(self, other: Duration) -> Duration {
+            Duration(self.0.saturating_sub(other.0))
+        }
+    }
+
+    impl Add for Duration {
+        type Output = Duration;
+        fn add(self, rhs: Duration) -> Duration {
+            Duration(self.0 + rhs.0)
+        }
+    }
+
+    impl Mul<u64> for Duration {
+        type Output = Duration;
+        fn mul(self, rhs: u64) -> Duration {
+            Duration(self.0 * rhs)
+        }
+    }
+
+    impl core::ops::Div<u64> for Duration {
+        type Output = Duration;
+        fn div(self, rhs: u64) -> Duration {
+            Duration(self.0 / rhs)
+        }
+    }
+
+    impl fmt::Display for Duration {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{}", self.0)
+        }
+    }
+}
+
+pub(crate) use imp::{Duration, Instant};
+
+/// `Duration::from_millis`/`Duration::as_millis`, but callable from a `const fn` (and `const { ... }`
+/// block) in every cfg branch, including `cfg(test)`: there, `Duration` is a bare `u64` alias, so its
+/// `from_millis`/`as_millis` come from the `TimeValue` trait above rather than an inherent `const
+/// fn`, and trait methods can't be `const` on stable Rust. The crate's compile-time `Duration`
+/// constants (e.g. `BEACON_INTERVAL_MS`) and the ones derived from them go through these instead.
+#[cfg(test)]
+pub(crate) const fn duration_from_millis(millis: u64) -> Duration {
+    millis
+}
+
+#[cfg(test)]
+pub(crate) const fn duration_as_millis(duration: Duration) -> u64 {
+    duration
+}
+
+#[cfg(not(test))]
+pub(crate) const fn duration_from_millis(millis: u64) -> Duration {
+    Duration::from_millis(millis)
+}
+
+#[cfg(not(test))]
+pub(crate) const fn duration_as_millis(duration: Duration) -> u64 {
+    duration.as_millis()
+}
@@ -0,0 +1,394 @@
+//  _____       ______   ____
+// |_   _|     |  ____|/ ____|  Institute of Embedded Systems
+//   | |  _ __ | |__  | (___    Zurich University of Applied Sciences
+//   | | | '_ \|  __|  \___ \   8401 Winterthur, Switzerland
+//  _| |_| | | | |____ ____) |
+// |_____|_| |_|______|_____/
+//
+// Copyright 2025 Institute of Embedded Systems at Zurich University of Applied Sciences.
+// All rights reserved.
+// SPDX-License-Identifier: MIT
+
+//! Record-and-replay of state-machine transitions and window-scheduler decisions, for
+//! deterministic regression tests and offline debugging of a simulated mesh run without
+//! re-executing radio I/O.
+//!
+//! `State`, `LightningAction` and `Message` all derive `serde::Serialize`/`Deserialize`, so a
+//! [`TraceRecord`] can be written out (and read back) as one newline-delimited JSON object per
+//! `Lightning::next` transition. `replay` re-derives each recorded transition's action from its
+//! recorded `to_state` and checks it against the recorded `action`, so a trace captured before a
+//! refactor of `State::get_action` keeps asserting that refactor didn't change behavior.
+//!
+//! [`WindowEvent`] does the same for `Windows`: it records each `push`/`pop`/`pop_kind`/
+//! `pop_child` call, and `Windows::from_event_log` replays a log of them to reconstruct the exact
+//! queue state, so a field-captured log can be diffed (via `Windows::snapshot`) against a later
+//! protocol version's behavior on the same input.
+
+use serde::{Deserialize, Serialize};
+
+use crate::*;
+
+/// One recorded `Lightning::next` transition, as `{ time, node_id, from_state, action, to_state }`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct TraceRecord {
+    pub(crate) time: TimeMs,
+    pub(crate) node_id: NodeId,
+    pub(crate) from_state: State,
+    pub(crate) action: LightningAction,
+    pub(crate) to_state: State,
+}
+
+impl<S: EventSink> Lightning<S> {
+    /// Like `next`, but also returns a `TraceRecord` of the transition, for a test harness to
+    /// collect into a trace log.
+    pub(crate) fn next_traced(
+        &mut self,
+        time: TimeMs,
+        message: Option<Message>,
+        rx_snr_db: Option<i8>,
+        rng: impl RngCore,
+    ) -> TraceRecord {
+        let from_state = self.state.clone();
+        self.state = self.next(time, message, rx_snr_db, false, rng);
+        let to_state = self.state.clone();
+        TraceRecord {
+            time,
+            node_id: self.id,
+            action: to_state.get_action(),
+            from_state,
+            to_state,
+        }
+    }
+}
+
+/// One recorded mutation of a `Windows<P>` queue — `push`/`pop`/`pop_kind`/`pop_child` — in call
+/// order. `Windows`'s overlap-resolution and duty-cycle logic is itself deterministic given the
+/// same inputs, so replaying these through `from_event_log` against a freshly constructed
+/// `Windows<P>` (same `clearance`/`policy`/`duty_cycle_limit`) reconstructs the exact queue state
+/// at any point in the log, letting a captured field log be diffed against a later protocol
+/// version offline.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) enum WindowEvent {
+    Push { window: Window, channel: Channel },
+    Pop,
+    PopKind { kind: WindowKind },
+    PopChild { id: NodeId },
+}
+
+impl<P: WindowPolicy> Windows<P> {
+    /// Like `push`, but also returns the `WindowEvent` for a harness to collect into a log.
+    pub(crate) fn push_traced(&mut self, window: Window, channel: Channel) -> WindowEvent {
+        self.push(window.clone(), channel);
+        WindowEvent::Push { window, channel }
+    }
+
+    /// Like `pop`, but also returns the `WindowEvent` for a harness to collect into a log.
+    pub(crate) fn pop_traced(&mut self) -> (Window, WindowEvent) {
+        (self.pop(), WindowEvent::Pop)
+    }
+
+    /// Like `pop_kind`, but also returns the `WindowEvent` for a harness to collect into a log.
+    pub(crate) fn pop_kind_traced(&mut self, kind: WindowKind) -> (Option<Window>, WindowEvent) {
+        (self.pop_kind(kind), WindowEvent::PopKind { kind })
+    }
+
+    /// Like `pop_child`, but also returns the `WindowEvent` for a harness to collect into a log.
+    pub(crate) fn pop_child_traced(&mut self, id: NodeId) -> (Option<Window>, WindowEvent) {
+        (self.pop_child(id), WindowEvent::PopChild { id })
+    }
+
+    /// Reconstruct the exact `Windows` queue state produced by replaying `log` (see `WindowEvent`)
+    /// against a fresh `Windows<P>` built with the same `clearance`/`policy`/`duty_cycle_limit`.
+    pub(crate) fn from_event_log(
+        clearance: Duration,
+        policy: P,
+        duty_cycle_limit: DutyCycleLimit,
+        log: &[WindowEvent],
+    ) -> Self {
+        let mut windows = Self::new(clearance, policy, duty_cycle_limit);
+        for event in log {
+            match event.clone() {
+                WindowEvent::Push { window, channel } => windows.push(window, channel),
+                WindowEvent::Pop => {
+                    windows.pop();
+                }
+                WindowEvent::PopKind { kind } => {
+                    windows.pop_kind(kind);
+                }
+                WindowEvent::PopChild { id } => {
+                    windows.pop_child(id);
+                }
+            }
+        }
+        windows
+    }
+}
+
+/// Error produced by `replay`: the transition at index `record` (0-based, in recorded order) no
+/// longer produces its recorded action.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ReplayMismatch {
+    pub(crate) record: usize,
+    pub(crate) expected: LightningAction,
+    pub(crate) actual: LightningAction,
+}
+
+/// Re-feed a previously recorded trace through `State::get_action` and check that it still matches
+/// the recorded `action`, catching any regression introduced by a later change to `get_action`
+/// (the state machine's transition logic is not re-run, since several transitions consume RNG
+/// draws, e.g. `Connect` nonces, that a recorded trace does not capture bit-for-bit).
+pub(crate) fn replay(records: &[TraceRecord]) -> Result<(), ReplayMismatch> {
+    for (index, record) in records.iter().enumerate() {
+        let actual = record.to_state.get_action();
+        if actual != record.action {
+            return Err(ReplayMismatch {
+                record: index,
+                expected: record.action.clone(),
+                actual,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Length-prefixed binary frame encoding (via `postcard`) for a seekable trace log, plus a
+/// trailing index so a reader can jump to a given time without scanning the whole file.
+///
+/// Layout: `frame* index_entry_count:u64 index_entry* footer_offset:u64`, where `footer_offset`
+/// points at `index_entry_count`. Each `index_entry` is `(time_ms: u64, file_offset: u64)`; the
+/// entries are the same `(time, offset)` pairs `write_indexed_log` recorded for each frame, laid
+/// out as an implicit complete binary tree in breadth-first order (node `i`'s children are at
+/// `2i+1`/`2i+2`) so `find_at_or_before` can binary-search it with `O(log n)` seeks instead of
+/// reading it all in.
+mod indexed_log {
+    use std::io::{Read, Seek, SeekFrom, Write};
+
+    use serde::de::DeserializeOwned;
+
+    use super::*;
+
+    /// A length-prefixed frame that can be appended to an indexed trace log.
+    pub(crate) trait ToWriter {
+        fn to_writer(&self, w: &mut impl Write) -> std::io::Result<()>;
+    }
+
+    /// The `FromReader` half of `ToWriter`: reads back one length-prefixed frame.
+    pub(crate) trait FromReader: Sized {
+        fn from_reader(r: &mut impl Read) -> std::io::Result<Self>;
+    }
+
+    impl<T: Serialize> ToWriter for T {
+        fn to_writer(&self, w: &mut impl Write) -> std::io::Result<()> {
+            let bytes =
+                postcard::to_allocvec(self).expect("TraceRecord has no non-serializable fields");
+            w.write_all(&(bytes.len() as u32).to_le_bytes())?;
+            w.write_all(&bytes)
+        }
+    }
+
+    impl<T: DeserializeOwned> FromReader for T {
+        fn from_reader(r: &mut impl Read) -> std::io::Result<Self> {
+            let mut len = [0u8; 4];
+            r.read_exact(&mut len)?;
+            let mut bytes = std::vec![0u8; u32::from_le_bytes(len) as usize];
+            r.read_exact(&mut bytes)?;
+            postcard::from_bytes(&bytes)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+        }
+    }
+
+    /// Write `records` as length-prefixed frames followed by a binary-search-tree index over
+    /// their `time`s, so `find_at_or_before` can later seek directly to the frame at or before a
+    /// given time. Requires `records` to be sorted by `time` (true of any trace as recorded by
+    /// `Lightning::next_traced`).
+    pub(crate) fn write_indexed_log<W: Write + Seek>(
+        records: &[TraceRecord],
+        w: &mut W,
+    ) -> std::io::Result<()> {
+        let mut by_time = std::vec::Vec::with_capacity(records.len());
+        for record in records {
+            let offset = w.stream_position()?;
+            record.to_writer(w)?;
+            by_time.push((record.time, offset));
+        }
+
+        let mut tree = std::vec![(0u64, 0u64); by_time.len()];
+        fill_bst(&by_time, &mut tree, 0);
+
+        let footer_offset = w.stream_position()?;
+        w.write_all(&(tree.len() as u64).to_le_bytes())?;
+        for (time, offset) in &tree {
+            w.write_all(&time.to_le_bytes())?;
+            w.write_all(&offset.to_le_bytes())?;
+        }
+        w.write_all(&footer_offset.to_le_bytes())
+    }
+
+    /// Recursively place the element of `sorted` that splits it like a complete binary tree (left
+    /// part as large as a complete tree's left subtree) at `tree[index]`, then the left/right
+    /// parts at `tree`'s children of `index`, so the top-level call fills exactly
+    /// `tree[..sorted.len()]` and an in-order walk of the tree yields `sorted` again.
+    fn fill_bst(sorted: &[(TimeMs, u64)], tree: &mut [(u64, u64)], index: usize) {
+        if sorted.is_empty() {
+            return;
+        }
+        let n = sorted.len();
+        let height = n.ilog2();
+        let full = (1usize << height) - 1;
+        let mid = full / 2 + (n - full).min((1usize << height) >> 1);
+        tree[index] = (sorted[mid].0, sorted[mid].1);
+        fill_bst(&sorted[..mid], tree, 2 * index + 1);
+        fill_bst(&sorted[mid + 1..], tree, 2 * index + 2);
+    }
+
+    /// Seek to the last frame at or before `target_time`, following `write_indexed_log`'s index
+    /// with `O(log n)` seeks, and return its file offset (suitable for `FromReader::from_reader`),
+    /// or `None` if the log is empty or every frame is after `target_time`.
+    pub(crate) fn find_at_or_before<R: Read + Seek>(
+        r: &mut R,
+        target_time: TimeMs,
+    ) -> std::io::Result<Option<u64>> {
+        r.seek(SeekFrom::End(-8))?;
+        let footer_offset = read_u64(r)?;
+
+        r.seek(SeekFrom::Start(footer_offset))?;
+        let mut count_bytes = [0u8; 8];
+        r.read_exact(&mut count_bytes)?;
+        let count = u64::from_le_bytes(count_bytes) as usize;
+        let entries_start = footer_offset + 8;
+
+        let mut index = 0usize;
+        let mut best_offset = None;
+        while index < count {
+            r.seek(SeekFrom::Start(entries_start + index as u64 * 16))?;
+            let time = read_u64(r)?;
+            let offset = read_u64(r)?;
+            if time <= target_time {
+                best_offset = Some(offset);
+                index = 2 * index + 2;
+            } else {
+                index = 2 * index + 1;
+            }
+        }
+        Ok(best_offset)
+    }
+
+    fn read_u64(r: &mut impl Read) -> std::io::Result<u64> {
+        let mut bytes = [0u8; 8];
+        r.read_exact(&mut bytes)?;
+        Ok(u64::from_le_bytes(bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_ndjson(ndjson: &str) -> std::vec::Vec<TraceRecord> {
+        ndjson
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    fn to_ndjson(records: &[TraceRecord]) -> std::string::String {
+        records
+            .iter()
+            .map(|record| serde_json::to_string(record).unwrap())
+            .collect::<std::vec::Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn trace_round_trips_through_ndjson_and_replays_clean() {
+        let mut node = Lightning::<LogSink>::new(1);
+
+        let mut records = heapless::Vec::<TraceRecord, 4>::new();
+        for _ in 0..3 {
+            let record = node.next_traced(0, None, None, rand::rngs::OsRng);
+            records.push(record).unwrap();
+        }
+
+        let ndjson = to_ndjson(records.as_slice());
+        let parsed = parse_ndjson(&ndjson);
+        assert_eq!(parsed.as_slice(), records.as_slice());
+        assert_eq!(replay(&parsed), Ok(()));
+    }
+
+    #[test]
+    fn window_event_log_reconstructs_queue_state() {
+        let mut windows = Windows::new(50, DefaultPolicy, DUTY_CYCLE_LIMIT);
+        let mut log: std::vec::Vec<WindowEvent> = std::vec::Vec::new();
+
+        log.push(windows.push_traced(
+            Window {
+                kind: WindowKind::Beacon,
+                start: 0,
+            },
+            0,
+        ));
+        log.push(windows.push_traced(
+            Window {
+                kind: WindowKind::Parent,
+                start: 1000,
+            },
+            0,
+        ));
+        let (_, event) = windows.pop_traced();
+        log.push(event);
+
+        let reconstructed = Windows::from_event_log(50, DefaultPolicy, DUTY_CYCLE_LIMIT, &log);
+        assert_eq!(reconstructed.snapshot(), windows.snapshot());
+    }
+
+    #[test]
+    fn replay_reports_the_mismatching_record() {
+        let mut node = Lightning::<LogSink>::new(1);
+        let mut record = node.next_traced(0, None, None, rand::rngs::OsRng);
+        // `Reset` always transitions into a `Wait`/`Transmit` state, never `None`, so this can
+        // never coincidentally match.
+        record.action = Action::None;
+
+        assert_eq!(
+            replay(core::slice::from_ref(&record)),
+            Err(ReplayMismatch {
+                record: 0,
+                expected: record.action.clone(),
+                actual: record.to_state.get_action(),
+            })
+        );
+    }
+
+    #[test]
+    fn indexed_log_finds_frame_at_or_before_target_time() {
+        use std::io::Cursor;
+
+        use indexed_log::{find_at_or_before, write_indexed_log, FromReader};
+
+        let records: std::vec::Vec<TraceRecord> = (0..5)
+            .map(|i| TraceRecord {
+                time: i as TimeMs * 1000,
+                node_id: 1,
+                from_state: State::default(),
+                action: Action::None,
+                to_state: State::default(),
+            })
+            .collect();
+
+        let mut log = Cursor::new(std::vec::Vec::new());
+        write_indexed_log(&records, &mut log).unwrap();
+
+        for target in [0, 500, 1000, 4500, 10_000] {
+            let offset = find_at_or_before(&mut log, target).unwrap();
+            let expected = records.iter().map(|r| r.time).filter(|t| *t <= target).max();
+            match expected {
+                Some(expected) => {
+                    log.set_position(offset.unwrap());
+                    assert_eq!(TraceRecord::from_reader(&mut log).unwrap().time, expected);
+                }
+                None => assert_eq!(offset, None),
+            }
+        }
+    }
+}
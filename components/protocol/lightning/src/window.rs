@@ -11,14 +11,18 @@
 
 use core::mem;
 use heapless::sorted_linked_list;
+use heapless::Deque;
+use serde::{Deserialize, Serialize};
 
 use crate::*;
 
-#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub(crate) enum WindowKind {
     Beacon,
-    Child,
+    /// Carries the child's `NodeId` so a specific child's window can be reclaimed (on graceful
+    /// `Leave` or after too many missed windows) without disturbing other children's windows.
+    Child(NodeId),
     Parent,
 }
 
@@ -28,25 +32,25 @@ impl WindowKind {
     /// This duration does not contain the message time on air. The `MIN_WINDOW_CLEARANCE` parameter
     /// makes sure that there is enough time for the message time on air.
     #[cfg(not(test))]
-    fn duration(&self) -> TimeMs {
+    fn duration(&self) -> Duration {
         match self {
             WindowKind::Beacon => {
                 RANDOM_CONNECT_RANGE_MS
                     + CONNECT_RESPONSE_DELAY_MS
                     + RESPONSE_LISTEN_DURATION_MS
-                    + 2 * SEND_DELAY
+                    + SEND_DELAY * 2
             }
             WindowKind::Parent => RESPONSE_LISTEN_DURATION_MS + SEND_DELAY,
-            WindowKind::Child => DATA_RECEIVE_WINDOW + SEND_DELAY,
+            WindowKind::Child(_) => DATA_RECEIVE_WINDOW + SEND_DELAY,
         }
     }
 }
 
-#[derive(Debug, Clone, Eq)]
+#[derive(Debug, Clone, Eq, Serialize, Deserialize)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub(crate) struct Window {
     pub(crate) kind: WindowKind,
-    pub(crate) start: TimeMs,
+    pub(crate) start: Instant,
 }
 
 impl Window {
@@ -54,7 +58,7 @@ impl Window {
     ///
     /// The window is delayed in integer multiples of the given increment. The delayed window keeps
     /// a distance of `windows.clearance` to adjacent windows.
-    pub(crate) fn delay(&mut self, windows: &Windows, increment: WindowDelayIncrement) {
+    pub(crate) fn delay(&mut self, windows: &Windows<impl WindowPolicy>, increment: WindowDelayIncrement) {
         let mut iter = windows.queue.iter();
 
         let first_window = match iter.next() {
@@ -66,11 +70,12 @@ impl Window {
             return;
         }
 
-        let increment = increment.as_ms();
+        let increment = increment.as_duration();
 
         // increment start of window such that it ends up after `time`
-        let delay_window = |time: TimeMs| {
-            self.start + increment * time.saturating_sub(self.start).div_ceil(increment)
+        let delay_window = |time: Instant| {
+            let steps = time.saturating_sub(self.start).as_millis().div_ceil(increment.as_millis());
+            self.start + increment * steps
         };
 
         // iterate over window gaps
@@ -96,17 +101,17 @@ impl Window {
     /// Returns offset of window to given time in minutes
     ///
     /// Panics when offset contains fractional minutes.
-    pub(crate) fn get_offset_min(&self, time: TimeMs) -> usize {
-        let offset = self.start - time;
+    pub(crate) fn get_offset_min(&self, time: Instant) -> usize {
+        let offset = (self.start - time).as_millis();
         assert_eq!(offset % MS_PER_MIN, 0);
         (offset / MS_PER_MIN) as usize
     }
 
-    fn duration(&self) -> TimeMs {
+    fn duration(&self) -> Duration {
         self.kind.duration()
     }
 
-    fn end(&self) -> TimeMs {
+    fn end(&self) -> Instant {
         self.start + self.duration()
     }
 }
@@ -129,73 +134,249 @@ impl PartialEq for Window {
     }
 }
 
+/// Recurrence period of each `WindowKind`, used by [`Windows::is_feasible`] to reason about
+/// windows that are not yet queued but will recur indefinitely once accepted.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct WindowPeriods {
+    pub(crate) beacon: Duration,
+    pub(crate) parent: Duration,
+    pub(crate) child: Duration,
+}
+
+impl WindowPeriods {
+    fn of(&self, kind: WindowKind) -> Duration {
+        match kind {
+            WindowKind::Beacon => self.beacon,
+            WindowKind::Parent => self.parent,
+            WindowKind::Child(_) => self.child,
+        }
+    }
+}
+
+/// Regulatory transmit duty-cycle limit for one channel (e.g. an EU868 sub-band allowing 1% or
+/// 10% airtime), enforced per-channel by `Windows::push` via `DutyCycleBudget`.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct DutyCycleLimit {
+    /// Percentage of `period` a channel may spend transmitting, e.g. `1` for a 1% sub-band.
+    pub(crate) percent: u8,
+    /// Rolling period over which `percent` is enforced, e.g. one hour.
+    pub(crate) period: Duration,
+}
+
+impl DutyCycleLimit {
+    fn allowance(&self) -> Duration {
+        self.period / 100 * self.percent as u64
+    }
+}
+
+/// Upper bound on transmissions tracked per channel within one `DutyCycleLimit::period`. Sized for
+/// a beacon recurring every `BEACON_INTERVAL_MS` over a one-hour period (the busiest case; a
+/// child's own data window recurs far less often, every `CHILD_DATA_INTERVAL`); old entries are
+/// pruned lazily by `DutyCycleBudget::remaining_airtime`.
+const MAX_DUTY_CYCLE_SAMPLES: usize = 128;
+
+/// Upper bound on distinct channels this node tracks a duty-cycle budget for at once. Only
+/// `Beacon` windows (on `public`) and `Child` windows (on `children`) are budgeted, so in practice
+/// two channels are tracked; this grows on demand (like `Context::child_adr`) instead of reserving a `DutyCycleBudget` per
+/// possible `Channel` value up front.
+const MAX_DUTY_CYCLE_CHANNELS: usize = 4;
+
+/// Tracks airtime spent transmitting on one channel over a sliding `DutyCycleLimit::period`, so
+/// `Windows::push` can refuse or postpone a window that would exceed the regulatory budget.
+///
+/// Airtime is reserved at push time rather than when the window actually fires, so a window that
+/// is dropped after its reservation (by the `WindowPolicy`, `pop_kind` or `pop_child`) still
+/// counts against the budget.
+#[derive(Debug)]
+struct DutyCycleBudget {
+    limit: DutyCycleLimit,
+    /// `(start, airtime)` of each reservation within the last `limit.period`, oldest first.
+    samples: Deque<(Instant, Duration), MAX_DUTY_CYCLE_SAMPLES>,
+}
+
+impl DutyCycleBudget {
+    fn new(limit: DutyCycleLimit) -> Self {
+        DutyCycleBudget {
+            limit,
+            samples: Deque::new(),
+        }
+    }
+
+    /// Reserve `airtime` worth of transmission starting at `start`.
+    fn record(&mut self, start: Instant, airtime: Duration) {
+        if self.samples.push_back((start, airtime)).is_err() {
+            // sample buffer full: drop the oldest reservation rather than lose this one, erring
+            // toward undercounting used airtime only in this (practically unreachable) edge case
+            self.samples.pop_front();
+            let _ = self.samples.push_back((start, airtime));
+        }
+    }
+
+    /// Airtime still available in `limit.period` ending at `now`.
+    fn remaining_airtime(&mut self, now: Instant) -> Duration {
+        while let Some((start, _)) = self.samples.front() {
+            if now.saturating_sub(*start) > self.limit.period {
+                self.samples.pop_front();
+            } else {
+                break;
+            }
+        }
+        let used = self
+            .samples
+            .iter()
+            .fold(Duration::from_millis(0), |acc, (_, airtime)| acc + *airtime);
+        self.limit.allowance().saturating_sub(used)
+    }
+}
+
+fn gcd(a: TimeMs, b: TimeMs) -> TimeMs {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+fn lcm(a: TimeMs, b: TimeMs) -> TimeMs {
+    a / gcd(a, b) * b
+}
+
+/// Upper bound on the number of interval endpoints `Windows::is_feasible` sweeps over across the
+/// two hyperperiods it checks (see `first_collision`). Sized for `MAX_WINDOWS` queued windows plus
+/// the candidate, each recurring up to ~16 times per hyperperiod of `BEACON_INTERVAL_MS` and
+/// `CHILD_DATA_INTERVAL_MIN`.
+const MAX_SCHEDULE_POINTS: usize = 512;
+
 pub(crate) enum WindowDelayIncrement {
     Milliseconds,
     Minutes,
 }
 
 impl WindowDelayIncrement {
-    fn as_ms(&self) -> TimeMs {
+    fn as_duration(&self) -> Duration {
         match self {
-            WindowDelayIncrement::Milliseconds => 1,
-            WindowDelayIncrement::Minutes => MS_PER_MIN,
+            WindowDelayIncrement::Milliseconds => Duration::from_millis(1),
+            WindowDelayIncrement::Minutes => minutes(1),
+        }
+    }
+}
+
+/// How a [`WindowPolicy`] wants a conflict between an incoming window and an already-queued
+/// (existing) overlapping window resolved.
+pub(crate) enum Resolution {
+    /// Delay the incoming window by the given increment until it clears the existing one.
+    DelayIncoming(WindowDelayIncrement),
+    /// Delay the existing window by the given increment until it clears the incoming one.
+    DelayExisting(WindowDelayIncrement),
+    /// Drop the incoming window, keeping the existing one in place.
+    DropIncoming,
+    /// Drop the existing window, making room for the incoming one.
+    DropExisting,
+}
+
+/// Conflict-resolution policy for overlapping windows, supplied to `Windows::new`.
+///
+/// `Windows::push` walks overlapping windows one at a time and asks the policy what to do with
+/// each (`incoming`, `existing`) pair, rather than hard-coding kind-pair semantics. This lets an
+/// application prioritize, say, data delivery over beaconing, or add its own `WindowKind`
+/// variants without touching the scheduler core.
+pub(crate) trait WindowPolicy {
+    fn resolve(&self, incoming: WindowKind, existing: WindowKind) -> Resolution;
+}
+
+/// The policy this crate shipped before `WindowPolicy` existed: beacons always yield to whatever
+/// they overlap, and a parent/child conflict always drops the child.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct DefaultPolicy;
+
+impl WindowPolicy for DefaultPolicy {
+    fn resolve(&self, incoming: WindowKind, existing: WindowKind) -> Resolution {
+        match (incoming, existing) {
+            (_, WindowKind::Beacon) => Resolution::DelayExisting(WindowDelayIncrement::Milliseconds),
+            (WindowKind::Beacon, _) => Resolution::DelayIncoming(WindowDelayIncrement::Milliseconds),
+            (WindowKind::Child(_), WindowKind::Parent) => Resolution::DropIncoming,
+            (WindowKind::Parent, WindowKind::Child(_)) => Resolution::DropExisting,
+            (WindowKind::Parent, WindowKind::Parent) => unreachable!(),
+            (WindowKind::Child(_), WindowKind::Child(_)) => unreachable!(),
         }
     }
 }
 
+/// Upper bound on postponements `Windows::reserve_duty_cycle` will try, in whole-minute steps,
+/// before giving up and letting a window through over budget rather than delaying it forever.
+const MAX_DUTY_CYCLE_POSTPONEMENTS: usize = 60;
+
 #[derive(Debug)]
-pub(crate) struct Windows {
+pub(crate) struct Windows<P: WindowPolicy = DefaultPolicy> {
     queue: sorted_linked_list::SortedLinkedList<
         Window,
         sorted_linked_list::LinkedIndexU8,
         sorted_linked_list::Min,
         MAX_WINDOWS,
     >,
-    clearance: TimeMs,
+    clearance: Duration,
+    policy: P,
+    duty_cycle_limit: DutyCycleLimit,
+    /// Per-channel regulatory transmit budget, keyed by `Channel` and grown on demand (see
+    /// `duty_cycle_budget`) rather than indexed by every possible `Channel` value up front.
+    duty_cycle: heapless::Vec<(Channel, DutyCycleBudget), MAX_DUTY_CYCLE_CHANNELS>,
 }
 
-impl Windows {
-    pub(crate) fn new(clearance: TimeMs) -> Self {
+impl<P: WindowPolicy> Windows<P> {
+    pub(crate) fn new(clearance: Duration, policy: P, duty_cycle_limit: DutyCycleLimit) -> Self {
         Windows {
             queue: sorted_linked_list::SortedLinkedList::new_u8(),
             clearance,
+            policy,
+            duty_cycle_limit,
+            duty_cycle: heapless::Vec::new(),
         }
     }
 
-    pub(crate) fn clearance(&self) -> TimeMs {
+    pub(crate) fn clearance(&self) -> Duration {
         self.clearance
     }
 
-    /// Add window to queue
-    pub(crate) fn push(&mut self, mut new_window: Window) {
+    /// Add `new_window` to the queue, transmitting on `channel`.
+    ///
+    /// Before resolving overlaps, a `Beacon`/`Child` window is first postponed if needed to
+    /// respect `channel`'s regulatory duty-cycle budget; see `reserve_duty_cycle`. A `Parent`
+    /// window, whose start the parent's ack dictates, is neither postponed nor counted against
+    /// the budget, although this node sends its `Data` in it.
+    pub(crate) fn push(&mut self, mut new_window: Window, channel: Channel) {
+        if matches!(new_window.kind, WindowKind::Beacon | WindowKind::Child(_)) {
+            new_window.start = self.reserve_duty_cycle(channel, new_window.kind, new_window.start);
+        }
         // resolve overlapping windows
         // loop needed as window might overlap with multiple windows
         loop {
             if let Some(mut overlapping_window) = self.pop_overlapping_window(&new_window) {
-                match (new_window.kind, overlapping_window.kind) {
-                    (_, WindowKind::Beacon) => {
-                        // beacon can just be delayed
+                match self.policy.resolve(new_window.kind, overlapping_window.kind) {
+                    Resolution::DelayExisting(increment) => {
                         overlapping_window.start = new_window.end() + self.clearance();
-                        overlapping_window.delay(self, WindowDelayIncrement::Milliseconds);
+                        overlapping_window.delay(self, increment);
                         self.queue.push(overlapping_window).unwrap();
                     }
-                    (WindowKind::Beacon, _) => {
-                        // beacon can just be delayed
+                    Resolution::DelayIncoming(increment) => {
                         self.queue.push(overlapping_window).unwrap();
-                        new_window.delay(self, WindowDelayIncrement::Milliseconds);
+                        new_window.delay(self, increment);
                         self.queue.push(new_window).unwrap();
                         break;
                     }
-                    (WindowKind::Child, WindowKind::Parent) => {
-                        warn!("child window conflicts with parent window: dropping child");
+                    Resolution::DropIncoming => {
+                        warn!(
+                            "{:?} window conflicts with {:?} window: dropping incoming",
+                            new_window.kind, overlapping_window.kind
+                        );
                         self.queue.push(overlapping_window).unwrap();
                         break;
                     }
-                    (WindowKind::Parent, WindowKind::Child) => {
-                        warn!("child window conflicts with parent window: dropping child");
+                    Resolution::DropExisting => {
+                        warn!(
+                            "{:?} window conflicts with {:?} window: dropping existing",
+                            new_window.kind, overlapping_window.kind
+                        );
                     }
-                    (WindowKind::Parent, WindowKind::Parent) => unreachable!(),
-                    (WindowKind::Child, WindowKind::Child) => unreachable!(),
                 }
             } else {
                 self.queue.push(new_window).unwrap();
@@ -206,6 +387,62 @@ impl Windows {
         // warn!("{}", self);
     }
 
+    /// Postpone `start` by whole-minute steps (`WindowDelayIncrement::Minutes`) until `channel`'s
+    /// duty-cycle budget has room for `kind`'s airtime, reserving it once found. Gives up after
+    /// `MAX_DUTY_CYCLE_POSTPONEMENTS` steps and lets the window through over budget rather than
+    /// postponing it forever; also lets it through unchecked if `channel` can't be tracked at all
+    /// (see `duty_cycle_budget`).
+    fn reserve_duty_cycle(&mut self, channel: Channel, kind: WindowKind, start: Instant) -> Instant {
+        let airtime = adjust_for_clock_inaccuracies(kind.duration());
+        let mut candidate = start;
+        for attempt in 0..MAX_DUTY_CYCLE_POSTPONEMENTS {
+            let Some(budget) = self.duty_cycle_budget(channel) else {
+                return candidate;
+            };
+            if budget.remaining_airtime(candidate) >= airtime {
+                if attempt > 0 {
+                    warn!(
+                        "{:?} window on channel {} postponed {} minute(s) to respect duty-cycle budget",
+                        kind, channel, attempt
+                    );
+                }
+                budget.record(candidate, airtime);
+                return candidate;
+            }
+            candidate = candidate + WindowDelayIncrement::Minutes.as_duration();
+        }
+        warn!(
+            "{:?} window on channel {} still exceeds duty-cycle budget after {} minute(s), sending anyway",
+            kind, channel, MAX_DUTY_CYCLE_POSTPONEMENTS
+        );
+        if let Some(budget) = self.duty_cycle_budget(channel) {
+            budget.record(candidate, airtime);
+        }
+        candidate
+    }
+
+    /// This channel's `DutyCycleBudget`, creating one (seeded from `duty_cycle_limit`) on first
+    /// use. Returns `None` if `MAX_DUTY_CYCLE_CHANNELS` distinct channels are already tracked and
+    /// `channel` isn't one of them.
+    fn duty_cycle_budget(&mut self, channel: Channel) -> Option<&mut DutyCycleBudget> {
+        if let Some(pos) = self.duty_cycle.iter().position(|(c, _)| *c == channel) {
+            return Some(&mut self.duty_cycle[pos].1);
+        }
+        let limit = self.duty_cycle_limit;
+        if self
+            .duty_cycle
+            .push((channel, DutyCycleBudget::new(limit)))
+            .is_err()
+        {
+            warn!(
+                "too many channels to track duty-cycle budget for, not enforcing it on channel {}",
+                channel
+            );
+            return None;
+        }
+        self.duty_cycle.last_mut().map(|(_, budget)| budget)
+    }
+
     /// Remove next window from the queue
     pub(crate) fn pop(&mut self) -> Window {
         self.queue.pop().unwrap()
@@ -219,12 +456,12 @@ impl Windows {
     }
 
     /// Return start of next window
-    pub(crate) fn next(&mut self) -> TimeMs {
+    pub(crate) fn next(&mut self) -> Instant {
         self.queue.peek().unwrap().start
     }
 
     /// Return start of next window with given kind
-    pub(crate) fn next_kind(&self, kind: WindowKind) -> Option<TimeMs> {
+    pub(crate) fn next_kind(&self, kind: WindowKind) -> Option<Instant> {
         for window in self.queue.iter() {
             if mem::discriminant(&window.kind) == mem::discriminant(&kind) {
                 return Some(window.start);
@@ -233,13 +470,109 @@ impl Windows {
         None
     }
 
-    /// Check if this node can no longer accept children
-    pub(crate) fn is_full(&self) -> bool {
+    /// Number of children currently holding a data window.
+    pub(crate) fn child_count(&self) -> usize {
         self.queue
             .iter()
-            .filter(|window| matches!(window.kind, WindowKind::Child { .. }))
+            .filter(|window| matches!(window.kind, WindowKind::Child(_)))
             .count()
-            == MAX_CHILDREN
+    }
+
+    /// Check if this node can no longer accept children
+    pub(crate) fn is_full(&self) -> bool {
+        self.child_count() == MAX_CHILDREN
+    }
+
+    /// Remove `id`'s data window specifically (on graceful `Leave` or after too many missed
+    /// windows), unlike `pop_kind` which only matches on the window's kind and would remove an
+    /// arbitrary child's window.
+    pub(crate) fn pop_child(&mut self, id: NodeId) -> Option<Window> {
+        self.queue
+            .find_mut(|w| matches!(w.kind, WindowKind::Child(child_id) if child_id == id))
+            .map(|w| w.pop())
+    }
+
+    /// Proactive admission check: would accepting `candidate` as a recurring window ever collide
+    /// with the other recurring windows, at any repetition?
+    ///
+    /// `Windows::push`/`pop_overlapping_window` only reason about windows already queued for the
+    /// next cycle, so a beacon, parent, or child window that recurs on its own period can drift
+    /// into a collision many cycles down the line that is only discovered once it's too late to
+    /// avoid dropping something. This instead expands every active window (plus `candidate`) into
+    /// all of its occurrences over one hyperperiod, pads each by `clearance()`, and sweeps for any
+    /// point where more than one padded occurrence is active — the radio can only do one thing at
+    /// a time.
+    pub(crate) fn is_feasible(&self, candidate: &Window, periods: &WindowPeriods) -> bool {
+        match self.first_collision(candidate, periods) {
+            Some(instant) => {
+                warn!(
+                    "candidate {:?} window is infeasible: collides at t={}",
+                    candidate.kind, instant
+                );
+                false
+            }
+            None => true,
+        }
+    }
+
+    /// Returns the earliest point at which two padded occurrences overlap, were every window to
+    /// recur forever at its period: the candidate's with a queued window's, or two queued
+    /// windows'. The point is an offset into the swept hyperperiods, not an absolute time. If the
+    /// occurrences do not fit in `MAX_SCHEDULE_POINTS`, returns the start of the window that
+    /// overflowed.
+    ///
+    /// The sweep below works in plain millisecond counts rather than `Instant`/`Duration`: periods
+    /// wrap around the hyperperiod via `%`, which an absolute `Instant` has no business supporting.
+    fn first_collision(&self, candidate: &Window, periods: &WindowPeriods) -> Option<Instant> {
+        let mut hyperperiod = periods.of(candidate.kind).as_millis();
+        for window in self.queue.iter() {
+            hyperperiod = lcm(hyperperiod, periods.of(window.kind).as_millis());
+        }
+
+        let mut points: heapless::Vec<(TimeMs, i8), MAX_SCHEDULE_POINTS> = heapless::Vec::new();
+        let clearance = self.clearance().as_millis();
+
+        // occurrences are enumerated over two hyperperiods so that an occurrence padded interval
+        // straddling the `0`/`hyperperiod` seam is represented whole (never artificially split),
+        // since the pattern in `[hyperperiod, 2 * hyperperiod)` is just a repeat of `[0,
+        // hyperperiod)`
+        let mut push_occurrences = |kind: WindowKind, start: Instant| -> bool {
+            let period = periods.of(kind).as_millis();
+            let duration = adjust_for_clock_inaccuracies(kind.duration()).as_millis();
+            let mut occurrence = start.as_millis() % period;
+            while occurrence < 2 * hyperperiod {
+                let padded_start = occurrence.saturating_sub(clearance);
+                let padded_end = occurrence + duration + clearance;
+                if points.push((padded_start, 1)).is_err() || points.push((padded_end, -1)).is_err()
+                {
+                    // ran out of room to track occurrences: treat as a collision rather than
+                    // silently declaring a schedule we couldn't fully check feasible
+                    return false;
+                }
+                occurrence += period;
+            }
+            true
+        };
+
+        if !push_occurrences(candidate.kind, candidate.start) {
+            return Some(candidate.start);
+        }
+        for window in self.queue.iter() {
+            if !push_occurrences(window.kind, window.start) {
+                return Some(window.start);
+            }
+        }
+
+        points.sort_unstable_by_key(|(time, _)| *time);
+
+        let mut active: i8 = 0;
+        for (time, delta) in points.iter() {
+            active += delta;
+            if active > 1 {
+                return Some(Instant::from_millis(*time));
+            }
+        }
+        None
     }
 
     fn pop_overlapping_window(&mut self, new_window: &Window) -> Option<Window> {
@@ -251,6 +584,14 @@ impl Windows {
             })
             .map(|w| w.pop())
     }
+
+    /// Snapshot the queue as `(kind, start, end)` tuples, in the same order `windows_to_string!`
+    /// prints them — the serializable format a `Windows::from_event_log` reconstruction is
+    /// diffed against.
+    #[cfg(test)]
+    pub(crate) fn snapshot(&self) -> heapless::Vec<(WindowKind, Instant, Instant), MAX_WINDOWS> {
+        heapless::Vec::from_iter(self.queue.iter().map(|w| (w.kind, w.start, w.end())))
+    }
 }
 
 macro_rules! windows_to_string {
@@ -264,14 +605,14 @@ macro_rules! windows_to_string {
             for _ in 0..i {
                 $write!($fmt, "                  ")?;
             }
-            if window.duration() == 0 {
+            if window.duration().as_millis() == 0 {
                 $write!($fmt, "    ")?;
             }
             $write!(
                 $fmt,
                 "{} - {} ",
                 window.start,
-                window.start + window.duration() as TimeMs
+                window.start + window.duration()
             )?;
         }
         Ok(())
@@ -285,16 +626,16 @@ impl core::fmt::Display for Window {
     }
 }
 
-impl core::fmt::Display for Windows {
+impl<P: WindowPolicy> core::fmt::Display for Windows<P> {
     fn fmt(&self, fmt: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         windows_to_string!(fmt, write, self)
     }
 }
 
 #[cfg(feature = "defmt")]
-impl defmt::Format for Windows {
+impl<P: WindowPolicy> defmt::Format for Windows<P> {
     fn format(&self, fmt: defmt::Formatter) {
-        fn wrapper(msg: &Windows, fmt: defmt::Formatter) -> core::fmt::Result {
+        fn wrapper<P: WindowPolicy>(msg: &Windows<P>, fmt: defmt::Formatter) -> core::fmt::Result {
             windows_to_string!(fmt, defmt_write_wrapper, msg)
         }
         let _ = wrapper(self, fmt);
@@ -305,27 +646,38 @@ impl defmt::Format for Windows {
 mod tests {
     use crate::{WindowKind::*, *};
 
+    const TEST_DUTY_CYCLE_LIMIT: DutyCycleLimit = DutyCycleLimit {
+        percent: 100,
+        period: 10_000,
+    };
+
     impl WindowKind {
         pub(crate) fn duration(&self) -> TimeMs {
             match self {
                 Beacon => 200,
                 Parent => 100,
-                Child => 100,
+                Child(_) => 100,
             }
         }
     }
 
     #[test]
     fn basic() {
-        let mut windows = Windows::new(50);
-        windows.push(Window {
-            kind: Parent,
-            start: 10,
-        });
-        windows.push(Window {
-            kind: Beacon,
-            start: 0,
-        });
+        let mut windows = Windows::new(50, DefaultPolicy, TEST_DUTY_CYCLE_LIMIT);
+        windows.push(
+            Window {
+                kind: Parent,
+                start: 10,
+            },
+            0,
+        );
+        windows.push(
+            Window {
+                kind: Beacon,
+                start: 0,
+            },
+            0,
+        );
         // println!("{}", windows);
         assert_eq!(
             windows.pop(),
@@ -345,18 +697,24 @@ mod tests {
 
     #[test]
     fn delay_beacon_when_parent_overlap() {
-        let mut windows = Windows::new(50);
-        windows.push(Window {
-            kind: Beacon,
-            start: 100,
-        });
+        let mut windows = Windows::new(50, DefaultPolicy, TEST_DUTY_CYCLE_LIMIT);
+        windows.push(
+            Window {
+                kind: Beacon,
+                start: 100,
+            },
+            0,
+        );
         let mut beacon_window = windows.pop_kind(WindowKind::Beacon).unwrap();
-        windows.push(Window {
-            kind: Parent,
-            start: 100,
-        });
+        windows.push(
+            Window {
+                kind: Parent,
+                start: 100,
+            },
+            0,
+        );
         beacon_window.delay(&windows, WindowDelayIncrement::Milliseconds);
-        windows.push(beacon_window);
+        windows.push(beacon_window, 0);
         // println!("{}", windows);
         assert_eq!(
             windows.pop(),
@@ -377,15 +735,21 @@ mod tests {
     #[test]
     fn parent_child_conflict() {
         // window conflict cannot be resolved, expect child window to be removed
-        let mut windows = Windows::new(10);
-        windows.push(Window {
-            kind: Child,
-            start: 100,
-        });
-        windows.push(Window {
-            kind: Parent,
-            start: 150,
-        });
+        let mut windows = Windows::new(10, DefaultPolicy, TEST_DUTY_CYCLE_LIMIT);
+        windows.push(
+            Window {
+                kind: Child(1),
+                start: 100,
+            },
+            0,
+        );
+        windows.push(
+            Window {
+                kind: Parent,
+                start: 150,
+            },
+            0,
+        );
         // println!("{}", windows);
         assert_eq!(
             windows.pop(),
@@ -399,23 +763,29 @@ mod tests {
 
     #[test]
     fn delay() {
-        let windows = &mut Windows::new(50);
+        let windows = &mut Windows::new(50, DefaultPolicy, TEST_DUTY_CYCLE_LIMIT);
 
-        windows.push(Window {
-            kind: Beacon,
-            start: 1000,
-        });
-        windows.push(Window {
-            kind: Child,
-            start: 1000,
-        });
+        windows.push(
+            Window {
+                kind: Beacon,
+                start: 1000,
+            },
+            0,
+        );
+        windows.push(
+            Window {
+                kind: Child(1),
+                start: 1000,
+            },
+            0,
+        );
 
         println!("{windows}");
 
         assert_eq!(
             windows.pop(),
             Window {
-                kind: Child,
+                kind: Child(1),
                 start: 1000
             }
         );
@@ -427,4 +797,53 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn is_feasible_accepts_non_colliding_recurrence() {
+        let mut windows = Windows::new(50, DefaultPolicy, TEST_DUTY_CYCLE_LIMIT);
+        windows.push(
+            Window {
+                kind: Beacon,
+                start: 0,
+            },
+            0,
+        );
+        let periods = WindowPeriods {
+            beacon: 1000,
+            parent: 1000,
+            child: 1000,
+        };
+        // beacon occupies [0, 250) every 1000ms; a child at 500 repeating every 1000ms occupies
+        // [450, 650), clear of every beacon occurrence
+        let candidate = Window {
+            kind: Child(1),
+            start: 500,
+        };
+        assert!(windows.is_feasible(&candidate, &periods));
+    }
+
+    #[test]
+    fn is_feasible_rejects_collision_with_next_recurrence() {
+        let mut windows = Windows::new(50, DefaultPolicy, TEST_DUTY_CYCLE_LIMIT);
+        windows.push(
+            Window {
+                kind: Beacon,
+                start: 0,
+            },
+            0,
+        );
+        let periods = WindowPeriods {
+            beacon: 1000,
+            parent: 1000,
+            child: 1000,
+        };
+        // the candidate doesn't overlap the beacon's first occurrence ([0, 250)), but recurring
+        // every 1000ms it lands at 900, 1900, ... which collides with the beacon's *next*
+        // occurrence at 1000 ([950, 1250))
+        let candidate = Window {
+            kind: Child(1),
+            start: 900,
+        };
+        assert!(!windows.is_feasible(&candidate, &periods));
+    }
 }
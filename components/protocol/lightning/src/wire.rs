@@ -0,0 +1,397 @@
+//  _____       ______   ____
+// |_   _|     |  ____|/ ____|  Institute of Embedded Systems
+//   | |  _ __ | |__  | (___    Zurich University of Applied Sciences
+//   | | | '_ \|  __|  \___ \   8401 Winterthur, Switzerland
+//  _| |_| | | | |____ ____) |
+// |_____|_| |_|______|_____/
+//
+// Copyright 2025 Institute of Embedded Systems at Zurich University of Applied Sciences.
+// All rights reserved.
+// SPDX-License-Identifier: MIT
+
+//! Compact, type-tagged binary encoding for `Message`, for actually putting it on air.
+//!
+//! The JSON produced by `Display`/`defmt::Format` is for logging; every byte of it would be
+//! airtime on a LoRa-class link. This instead emits one discriminant byte per message followed by
+//! its fields packed tightly, with `NodeId`/`Payload` as LEB128 varints so small, common values
+//! stay small on the wire.
+
+use crate::*;
+
+const TAG_BEACON: u8 = 0;
+const TAG_CONNECT: u8 = 1;
+const TAG_CONNECT_ACK: u8 = 2;
+const TAG_DATA: u8 = 3;
+const TAG_DATA_ACK: u8 = 4;
+const TAG_NACK: u8 = 5;
+const TAG_LEAVE: u8 = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum EncodeError {
+    /// `buf` was too small to hold the encoded message.
+    BufferTooSmall,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DecodeError {
+    /// `buf` ended before a complete message could be decoded.
+    UnexpectedEof,
+    /// the discriminant byte did not match any `Message` variant.
+    InvalidTag(u8),
+    /// a varint ran past 5 bytes or overflowed a `u32`; overlong but in-range encodings are
+    /// accepted.
+    InvalidVarint,
+}
+
+struct Writer<'a> {
+    buf: &'a mut [u8],
+    pos: usize,
+}
+
+impl<'a> Writer<'a> {
+    fn new(buf: &'a mut [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    fn put_u8(&mut self, byte: u8) -> Result<(), EncodeError> {
+        let dst = self.buf.get_mut(self.pos).ok_or(EncodeError::BufferTooSmall)?;
+        *dst = byte;
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn put_varint(&mut self, mut value: u32) -> Result<(), EncodeError> {
+        loop {
+            let mut byte = (value & 0x7f) as u8;
+            value >>= 7;
+            if value != 0 {
+                byte |= 0x80;
+            }
+            self.put_u8(byte)?;
+            if value == 0 {
+                return Ok(());
+            }
+        }
+    }
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    fn get_u8(&mut self) -> Result<u8, DecodeError> {
+        let byte = *self.buf.get(self.pos).ok_or(DecodeError::UnexpectedEof)?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    fn get_varint(&mut self) -> Result<u32, DecodeError> {
+        let mut value: u32 = 0;
+        for i in 0..5 {
+            let byte = self.get_u8()?;
+            let payload = (byte & 0x7f) as u32;
+            if i == 4 && payload & !0xf != 0 {
+                // a 5th continuation byte can only contribute 4 more bits to a u32
+                return Err(DecodeError::InvalidVarint);
+            }
+            value |= payload << (7 * i);
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(DecodeError::InvalidVarint)
+    }
+}
+
+/// Encode `message` into `buf`, returning the number of bytes written.
+pub fn encode(message: &Message, buf: &mut [u8]) -> Result<usize, EncodeError> {
+    let mut w = Writer::new(buf);
+    match message {
+        Message::Beacon {
+            hops,
+            id,
+            parent_id,
+            children_channel,
+            parent_channel,
+        } => {
+            w.put_u8(TAG_BEACON)?;
+            w.put_u8(*hops)?;
+            w.put_varint(*id)?;
+            match parent_id {
+                Some(parent_id) => {
+                    w.put_u8(1)?;
+                    w.put_varint(*parent_id)?;
+                }
+                None => w.put_u8(0)?,
+            }
+            w.put_u8(*children_channel)?;
+            match parent_channel {
+                Some(channel) => {
+                    w.put_u8(1)?;
+                    w.put_u8(*channel)?;
+                }
+                None => w.put_u8(0)?,
+            }
+        }
+        Message::Connect { id, nonce } => {
+            w.put_u8(TAG_CONNECT)?;
+            w.put_varint(*id)?;
+            w.put_varint(*nonce)?;
+        }
+        Message::ConnectAck {
+            next_window_min,
+            id,
+            credits,
+        } => {
+            w.put_u8(TAG_CONNECT_ACK)?;
+            w.put_u8(*next_window_min)?;
+            w.put_varint(*id)?;
+            w.put_u8(*credits)?;
+        }
+        Message::Data {
+            immediate_sender,
+            data,
+            root,
+        } => {
+            w.put_u8(TAG_DATA)?;
+            w.put_varint(*immediate_sender)?;
+            w.put_varint(*root)?;
+            w.put_varint(data.len() as u32)?;
+            for NodeData { source, payload } in data {
+                w.put_varint(*source)?;
+                w.put_varint(*payload as u32)?;
+            }
+        }
+        Message::DataAck {
+            next_window_min,
+            adr,
+            credits,
+        } => {
+            w.put_u8(TAG_DATA_ACK)?;
+            w.put_u8(*next_window_min)?;
+            match adr {
+                Some(adr) => {
+                    w.put_u8(1)?;
+                    w.put_u8(adr.spreading_factor)?;
+                    w.put_u8(adr.tx_power_dbm as u8)?;
+                }
+                None => w.put_u8(0)?,
+            }
+            w.put_u8(*credits)?;
+        }
+        Message::Nack => {
+            w.put_u8(TAG_NACK)?;
+        }
+        Message::Leave { id } => {
+            w.put_u8(TAG_LEAVE)?;
+            w.put_varint(*id)?;
+        }
+    }
+    Ok(w.pos)
+}
+
+/// Decode a `Message` from the start of `buf`, returning it along with the number of bytes
+/// consumed so frames can be pipelined in a larger buffer.
+pub fn decode(buf: &[u8]) -> Result<(Message, usize), DecodeError> {
+    let mut r = Reader::new(buf);
+    let message = match r.get_u8()? {
+        TAG_BEACON => {
+            let hops = r.get_u8()?;
+            let id = r.get_varint()?;
+            let parent_id = match r.get_u8()? {
+                0 => None,
+                _ => Some(r.get_varint()?),
+            };
+            let children_channel = r.get_u8()?;
+            let parent_channel = match r.get_u8()? {
+                0 => None,
+                _ => Some(r.get_u8()?),
+            };
+            Message::Beacon {
+                hops,
+                id,
+                parent_id,
+                children_channel,
+                parent_channel,
+            }
+        }
+        TAG_CONNECT => Message::Connect {
+            id: r.get_varint()?,
+            nonce: r.get_varint()?,
+        },
+        TAG_CONNECT_ACK => {
+            let next_window_min = r.get_u8()?;
+            let id = r.get_varint()?;
+            let credits = r.get_u8()?;
+            Message::ConnectAck {
+                next_window_min,
+                id,
+                credits,
+            }
+        }
+        TAG_DATA => {
+            let immediate_sender = r.get_varint()?;
+            let root = r.get_varint()?;
+            let count = r.get_varint()?;
+            let mut data = OwnAndChildData::new();
+            for _ in 0..count {
+                let source = r.get_varint()?;
+                let payload = r.get_varint()? as Payload;
+                // silently drop entries beyond our own capacity; a well-behaved sender never
+                // exceeds MAX_DESCENDANTS + 1. Dropped entries are still read, so the consumed
+                // length returned below stays correct
+                let _ = data.push(NodeData { source, payload });
+            }
+            Message::Data {
+                immediate_sender,
+                data,
+                root,
+            }
+        }
+        TAG_DATA_ACK => {
+            let next_window_min = r.get_u8()?;
+            let adr = match r.get_u8()? {
+                0 => None,
+                _ => Some(AdrCommand {
+                    spreading_factor: r.get_u8()?,
+                    tx_power_dbm: r.get_u8()? as i8,
+                }),
+            };
+            let credits = r.get_u8()?;
+            Message::DataAck {
+                next_window_min,
+                adr,
+                credits,
+            }
+        }
+        TAG_NACK => Message::Nack,
+        TAG_LEAVE => Message::Leave {
+            id: r.get_varint()?,
+        },
+        tag => return Err(DecodeError::InvalidTag(tag)),
+    };
+    Ok((message, r.pos))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip(message: Message) {
+        let mut buf = [0u8; 64];
+        let len = encode(&message, &mut buf).unwrap();
+        let (decoded, consumed) = decode(&buf[..len]).unwrap();
+        assert_eq!(decoded, message);
+        assert_eq!(consumed, len);
+    }
+
+    #[test]
+    fn beacon_without_parent_channel() {
+        roundtrip(Message::Beacon {
+            hops: 3,
+            id: 1,
+            parent_id: None,
+            children_channel: 5,
+            parent_channel: None,
+        });
+    }
+
+    #[test]
+    fn beacon_with_parent_channel() {
+        roundtrip(Message::Beacon {
+            hops: 0,
+            id: 0x1234_5678,
+            parent_id: Some(0x8765_4321),
+            children_channel: 2,
+            parent_channel: Some(7),
+        });
+    }
+
+    #[test]
+    fn connect_and_connect_ack() {
+        roundtrip(Message::Connect {
+            id: 0x1234_5678,
+            nonce: 0xdead_beef,
+        });
+        roundtrip(Message::ConnectAck {
+            next_window_min: 9,
+            id: 42,
+            credits: 3,
+        });
+    }
+
+    #[test]
+    fn data_with_multiple_entries() {
+        let mut data = OwnAndChildData::new();
+        data.push(NodeData {
+            source: 1,
+            payload: 0,
+        })
+        .unwrap();
+        data.push(NodeData {
+            source: 0xffff_ffff,
+            payload: 0xffff,
+        })
+        .unwrap();
+        roundtrip(Message::Data {
+            immediate_sender: 7,
+            data,
+            root: 0xdead_beef,
+        });
+    }
+
+    #[test]
+    fn data_ack_with_and_without_adr() {
+        roundtrip(Message::DataAck {
+            next_window_min: 1,
+            adr: None,
+            credits: 5,
+        });
+        roundtrip(Message::DataAck {
+            next_window_min: 1,
+            adr: Some(AdrCommand {
+                spreading_factor: 7,
+                tx_power_dbm: -17,
+            }),
+            credits: 0,
+        });
+    }
+
+    #[test]
+    fn nack() {
+        roundtrip(Message::Nack);
+    }
+
+    #[test]
+    fn leave() {
+        roundtrip(Message::Leave { id: 99 });
+    }
+
+    #[test]
+    fn decode_rejects_unknown_tag() {
+        assert_eq!(decode(&[0xaa]), Err(DecodeError::InvalidTag(0xaa)));
+    }
+
+    #[test]
+    fn decode_rejects_short_buffer() {
+        // Connect's tag with no id bytes following
+        assert_eq!(decode(&[TAG_CONNECT]), Err(DecodeError::UnexpectedEof));
+    }
+
+    #[test]
+    fn encode_rejects_buffer_too_small() {
+        let mut buf = [0u8; 1];
+        assert_eq!(
+            encode(&Message::Connect { id: 1, nonce: 0 }, &mut buf),
+            Err(EncodeError::BufferTooSmall)
+        );
+    }
+}
@@ -0,0 +1,66 @@
+//  _____       ______   ____
+// |_   _|     |  ____|/ ____|  Institute of Embedded Systems
+//   | |  _ __ | |__  | (___    Zurich University of Applied Sciences
+//   | | | '_ \|  __|  \___ \   8401 Winterthur, Switzerland
+//  _| |_| | | | |____ ____) |
+// |_____|_| |_|______|_____/
+//
+// Copyright 2025 Institute of Embedded Systems at Zurich University of Applied Sciences.
+// All rights reserved.
+// SPDX-License-Identifier: MIT
+
+#![cfg_attr(not(test), no_std)]
+
+//! Transport abstraction for sending/receiving a `Protocol::Message`, split into a blocking and a
+//! non-blocking tier so the same protocol state machine can be driven from an RTOS task (blocking
+//! radio calls) or from a discrete-event simulator/async executor (poll-based) without the
+//! protocol crate itself depending on either.
+
+/// Errors common to both transport tiers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TransportError {
+    /// No message arrived before the deadline/within the polling window.
+    Timeout,
+    /// Retried `send_and_confirm` until giving up without a successful delivery.
+    MaxRetriesExceeded,
+    /// The peer explicitly rejected the message (protocol-level `Nack`).
+    Nack,
+    /// The underlying PHY reported an error.
+    Phy,
+}
+
+/// Blocking transport: calls only return once they have a result.
+///
+/// `send_and_confirm` owns the retry/backoff loop itself (resending on `Nack` or timeout up to an
+/// implementation-defined attempt limit), so callers get a single fallible call instead of having
+/// to reimplement retry logic around `AsyncRadio::try_send`.
+pub trait SyncRadio<Message, TimeMs> {
+    /// Send `message`, retrying on `Nack`/timeout, until it is confirmed delivered or retries are
+    /// exhausted (`TransportError::MaxRetriesExceeded`).
+    fn send_and_confirm(&mut self, message: &Message) -> Result<(), TransportError>;
+
+    /// Block until a message arrives or `timeout` elapses (`TransportError::Timeout`).
+    fn recv(&mut self, timeout: TimeMs) -> Result<Message, TransportError>;
+}
+
+/// Non-blocking transport: calls report readiness immediately instead of waiting.
+///
+/// Unlike `SyncRadio`, retrying is the caller's responsibility (e.g. the protocol state machine's
+/// own wait/retransmit states) since there is no blocking call here to hang a retry loop off of.
+pub trait AsyncRadio<Message> {
+    /// Enqueue `message` for transmission if the transport has room; `Ok(false)` means try again
+    /// later rather than an error.
+    fn try_send(&mut self, message: &Message) -> Result<bool, TransportError>;
+
+    /// Return a received message if one is ready, or `Ok(None)` if none is available yet.
+    fn poll_recv(&mut self) -> Result<Option<Message>, TransportError>;
+}
+
+/// A transport that supports both tiers, for code that wants to pick a style per call site.
+pub trait Radio<Message, TimeMs>: SyncRadio<Message, TimeMs> + AsyncRadio<Message> {}
+
+impl<T, Message, TimeMs> Radio<Message, TimeMs> for T where
+    T: SyncRadio<Message, TimeMs> + AsyncRadio<Message>
+{
+}
@@ -0,0 +1,51 @@
+//  _____       ______   ____
+// |_   _|     |  ____|/ ____|  Institute of Embedded Systems
+//   | |  _ __ | |__  | (___    Zurich University of Applied Sciences
+//   | | | '_ \|  __|  \___ \   8401 Winterthur, Switzerland
+//  _| |_| | | | |____ ____) |
+// |_____|_| |_|______|_____/
+//
+// Copyright 2025 Institute of Embedded Systems at Zurich University of Applied Sciences.
+// All rights reserved.
+// SPDX-License-Identifier: MIT
+
+//! Thin entry point; the actual harness lives in `simulator_fuzz::full_stack` so it can be driven
+//! by cargo-fuzz (libfuzzer), AFL, or a plain file argument interchangeably, same as
+//! rust-lightning's fuzz targets.
+
+use simulator_fuzz::full_stack::do_test;
+
+#[cfg(feature = "afl_fuzz")]
+#[macro_use]
+extern crate afl;
+#[cfg(feature = "afl_fuzz")]
+fn main() {
+    fuzz!(|data: &[u8]| {
+        do_test(data);
+    });
+}
+
+#[cfg(feature = "libfuzzer_fuzz")]
+use libfuzzer_sys::fuzz_target;
+#[cfg(feature = "libfuzzer_fuzz")]
+fuzz_target!(|data: &[u8]| {
+    do_test(data);
+});
+
+#[cfg(feature = "stdin_fuzz")]
+fn main() {
+    let mut data = Vec::new();
+    std::io::Read::read_to_end(&mut std::io::stdin(), &mut data).unwrap();
+    do_test(&data);
+}
+
+#[cfg(not(any(feature = "afl_fuzz", feature = "libfuzzer_fuzz", feature = "stdin_fuzz")))]
+fn main() {
+    // run once per file passed on the command line, for quick manual reproduction of a saved
+    // crash without pulling in afl/libfuzzer
+    for argument in std::env::args().skip(1) {
+        let data = std::fs::read(&argument).unwrap_or_else(|e| panic!("can't read {argument}: {e}"));
+        println!("running {argument} ({} bytes)", data.len());
+        do_test(&data);
+    }
+}
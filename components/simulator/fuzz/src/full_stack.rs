@@ -0,0 +1,251 @@
+//  _____       ______   ____
+// |_   _|     |  ____|/ ____|  Institute of Embedded Systems
+//   | |  _ __ | |__  | (___    Zurich University of Applied Sciences
+//   | | | '_ \|  __|  \___ \   8401 Winterthur, Switzerland
+//  _| |_| | | | |____ ____) |
+// |_____|_| |_|______|_____/
+//
+// Copyright 2025 Institute of Embedded Systems at Zurich University of Applied Sciences.
+// All rights reserved.
+// SPDX-License-Identifier: MIT
+
+//! Drives `ProtocolWrapper::progress`/`forward_message` through an arbitrary byte stream, in the
+//! spirit of rust-lightning's `full_stack_target` fuzz harness. Unlike `main::run`'s event loop,
+//! which only ever feeds a node whatever message its protocol partners legitimately produced, this
+//! harness also occasionally substitutes an adversarially-chosen [`Message`] for what's actually
+//! queued, so cargo-fuzz/AFL can search for inputs that violate the invariants below instead of
+//! only the inputs a well-behaved network would ever produce.
+//!
+//! Nodes are also handed a fuzzed `ProtocolWrapper::protocol_version` (see `MAX_PROTOCOL_VERSIONS`),
+//! so a run regularly mixes nodes that can't decode each other's frames, exercising
+//! `forward_message`'s version-gated delivery alongside its capture-effect resolution.
+//!
+//! Checked invariants (an assertion failure here is a fuzz find, a `panic!` anywhere in
+//! `simulator`/`lightning` during the run is too):
+//! - event times are monotonic as popped from `SortedLinkedList<Event>`
+//! - `is_corrupt` is only ever set on a frame that actually overlapped another transmission on the
+//!   same channel, see `channel::ChannelModel::resolve`
+//! - a receive-timeout cancellation (`event_queue.retain` in `forward_message`) never drops a
+//!   delivered message: the event queue always has exactly one entry per node
+//! - no internal `panic!` (e.g. `ProtocolWrapper::progress`'s non-sink-returned-uplink-data check)
+//!   fires
+
+use arbitrary::Unstructured;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use sorted_linked_list::SortedLinkedList;
+
+use lightning::{AdrCommand, Lightning, NodeData, OwnAndChildData};
+use protocol_api::{Action, Protocol};
+use simulator::*;
+
+const MAX_NODES: usize = 6;
+const MAX_STEPS: usize = 256;
+const MAX_CHANNELS: u8 = 8;
+const MAX_COORDINATE: i64 = 100;
+/// Number of distinct `ProtocolWrapper::protocol_version` tags handed out across the fuzzed node
+/// set, so a run regularly exercises version-incompatible nodes coexisting on the same channels.
+const MAX_PROTOCOL_VERSIONS: u8 = 3;
+
+fn arbitrary_node_data(u: &mut Unstructured) -> arbitrary::Result<NodeData> {
+    Ok(NodeData { source: u.arbitrary()?, payload: u.arbitrary()? })
+}
+
+fn arbitrary_message(u: &mut Unstructured) -> arbitrary::Result<Message> {
+    Ok(match u.int_in_range(0..=6u8)? {
+        0 => Message::Beacon {
+            hops: u.arbitrary()?,
+            id: u.arbitrary()?,
+            parent_id: u.arbitrary()?,
+            children_channel: u.int_in_range(0..=MAX_CHANNELS - 1)?,
+            parent_channel: if u.arbitrary()? {
+                Some(u.int_in_range(0..=MAX_CHANNELS - 1)?)
+            } else {
+                None
+            },
+        },
+        1 => Message::Connect { id: u.arbitrary()?, nonce: u.arbitrary()? },
+        2 => Message::ConnectAck {
+            next_window_min: u.arbitrary()?,
+            id: u.arbitrary()?,
+            credits: u.arbitrary()?,
+        },
+        3 => Message::Leave { id: u.arbitrary()? },
+        4 => {
+            let mut data: OwnAndChildData = OwnAndChildData::new();
+            for _ in 0..u.int_in_range(0..=4u8)? {
+                if data.push(arbitrary_node_data(u)?).is_err() {
+                    break;
+                }
+            }
+            Message::Data { immediate_sender: u.arbitrary()?, data, root: u.arbitrary()? }
+        }
+        5 => Message::DataAck {
+            next_window_min: u.arbitrary()?,
+            adr: if u.arbitrary()? {
+                Some(AdrCommand {
+                    spreading_factor: u.arbitrary()?,
+                    tx_power_dbm: u.arbitrary()?,
+                })
+            } else {
+                None
+            },
+            credits: u.arbitrary()?,
+        },
+        _ => Message::Nack,
+    })
+}
+
+/// Entry point called by every `fuzz_targets/*.rs` binary for this target.
+pub fn do_test(data: &[u8]) {
+    let _ = run(&mut Unstructured::new(data));
+}
+
+fn run(u: &mut Unstructured) -> arbitrary::Result<()> {
+    let seed: u64 = u.arbitrary()?;
+    let num_nodes = u.int_in_range(2..=MAX_NODES)?;
+    let range = u.int_in_range(10u32..=150)?;
+
+    let channel_model = ChannelModel {
+        path_loss: Box::new(LogDistancePathLoss {
+            tx_power_db: 14.0,
+            path_loss_exponent: u.int_in_range(10i32..=40)? as f32 / 10.0,
+        }),
+        capture_margin_db: u.int_in_range(0i32..=12)? as f32,
+        noise_floor_db: 14.0 - u.int_in_range(60i32..=160)? as f32,
+        preamble_ms: u.int_in_range(0..=(TIME_ON_AIR as u32))? as TimeMs,
+    };
+
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+
+    let mut nodes: Vec<ProtocolWrapper> = Vec::with_capacity(num_nodes);
+    for id in 0..num_nodes {
+        let x = u.int_in_range(0..=MAX_COORDINATE)?;
+        let y = u.int_in_range(0..=MAX_COORDINATE)?;
+        let mut protocol: Lightning = Protocol::new(id as NodeId);
+        // node 0 is always the sink, same as `main::tests::create_nodes`'s usual callers
+        protocol.set_is_sink(id == 0);
+        let protocol_version = u.int_in_range(0..=MAX_PROTOCOL_VERSIONS - 1)?;
+        nodes.push(ProtocolWrapper::new(protocol, Coordinates { x, y }, protocol_version));
+    }
+
+    let mut event_queue: SortedLinkedList<Event> = SortedLinkedList::new();
+    for node in &nodes {
+        let startup_delay = u.int_in_range(0u32..=5000)? as TimeMs;
+        event_queue.push(Event::new(startup_delay, node.id(), None));
+    }
+
+    let mut sink = TextEventSink;
+    let mut last_time: TimeMs = 0;
+    // (channel, departure_time, sender) of every frame dispatched, so a later `is_corrupt` receive
+    // can be checked against an actual overlapping same-channel transmission from another sender
+    // instead of trusting `forward_message` blindly.
+    let mut transmissions: Vec<(Channel, TimeMs, NodeId)> = Vec::new();
+
+    for _ in 0..MAX_STEPS {
+        if event_queue.is_empty() {
+            break;
+        }
+        assert_eq!(
+            event_queue.len(),
+            nodes.len(),
+            "bug: receive-timeout cancellation dropped a delivered message"
+        );
+
+        let event = event_queue.pop().unwrap();
+        assert!(event.time >= last_time, "event times must be monotonic as popped from the queue");
+        last_time = event.time;
+
+        if let Some(MessageWrapper {
+            kind: MessageKind::Transmit,
+            channel,
+            ref message,
+            sender: _,
+            protocol_version,
+            is_corrupt: _,
+        }) = event.message
+        {
+            transmissions.push((channel, event.time, event.node_id));
+            forward_message(
+                event.time,
+                event.node_id,
+                channel,
+                message,
+                protocol_version,
+                &mut event_queue,
+                &nodes,
+                None,
+                |a, b| check_visibility_based_on_distance(a, b, range),
+                None,
+                &channel_model,
+                &mut rng,
+                &mut sink,
+            );
+            event_queue.push(Event::new(event.time + TIME_ON_AIR, event.node_id, None));
+            continue;
+        }
+
+        let received_message = match event.message {
+            Some(MessageWrapper {
+                kind: MessageKind::Receive,
+                channel: _,
+                message,
+                sender: _,
+                protocol_version: _,
+                is_corrupt,
+            }) if !is_corrupt => Some(message),
+            Some(MessageWrapper { kind: MessageKind::Receive, channel, sender, is_corrupt: true, .. }) => {
+                assert!(
+                    transmissions.iter().any(|&(tx_channel, tx_time, tx_sender)| {
+                        tx_channel == channel
+                            && tx_sender != sender
+                            && event.time.max(tx_time) - event.time.min(tx_time) < 2 * TIME_ON_AIR
+                    }),
+                    "is_corrupt set without an overlapping same-channel transmission"
+                );
+                None
+            }
+            _ => None,
+        };
+
+        // occasionally feed an adversarial message instead of what was actually queued, to
+        // exercise `progress`'s handling of a byzantine/corrupted peer rather than only what
+        // `forward_message` would ever legitimately deliver
+        let received_message = if u.ratio(1, 8).unwrap_or(false) {
+            arbitrary_message(u).ok()
+        } else {
+            received_message
+        };
+
+        let (action, uplink_data) =
+            nodes[event.node_id as usize].progress(event.time, received_message, &mut rng, &mut sink);
+        // a non-sink node returning uplink data is caught by `ProtocolWrapper::progress`'s own
+        // `panic!`; nothing further to check here.
+        drop(uplink_data);
+
+        match action {
+            Action::Wait { end } | Action::Receive { end, .. } => {
+                assert!(end >= event.time, "bug: end of action is in the past");
+                event_queue.push(Event::new(end, event.node_id, None));
+            }
+            Action::Transmit { channel, message, delay } => {
+                event_queue.push(Event::new(
+                    event.time + delay.unwrap_or(0),
+                    event.node_id,
+                    Some(MessageWrapper::new(
+                        MessageKind::Transmit,
+                        message,
+                        channel,
+                        event.node_id,
+                        nodes[event.node_id as usize].protocol_version(),
+                    )),
+                ));
+            }
+            Action::None => {
+                event_queue.push(Event::new(event.time, event.node_id, None));
+            }
+        }
+    }
+
+    Ok(())
+}
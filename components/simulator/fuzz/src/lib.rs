@@ -0,0 +1,15 @@
+//  _____       ______   ____
+// |_   _|     |  ____|/ ____|  Institute of Embedded Systems
+//   | |  _ __ | |__  | (___    Zurich University of Applied Sciences
+//   | | | '_ \|  __|  \___ \   8401 Winterthur, Switzerland
+//  _| |_| | | | |____ ____) |
+// |_____|_| |_|______|_____/
+//
+// Copyright 2025 Institute of Embedded Systems at Zurich University of Applied Sciences.
+// All rights reserved.
+// SPDX-License-Identifier: MIT
+
+//! Fuzz targets for the `simulator` crate. One module per target, named after its
+//! `fuzz_targets/*.rs` binary, mirroring how rust-lightning's `lightning-fuzz` crate is laid out.
+
+pub mod full_stack;
@@ -0,0 +1,354 @@
+//  _____       ______   ____
+// |_   _|     |  ____|/ ____|  Institute of Embedded Systems
+//   | |  _ __ | |__  | (___    Zurich University of Applied Sciences
+//   | | | '_ \|  __|  \___ \   8401 Winterthur, Switzerland
+//  _| |_| | | | |____ ____) |
+// |_____|_| |_|______|_____/
+//
+// Copyright 2025 Institute of Embedded Systems at Zurich University of Applied Sciences.
+// All rights reserved.
+// SPDX-License-Identifier: MIT
+
+//! Pluggable simulation engines, selected by the caller instead of hard-coded into `main`.
+//!
+//! [`SerialEngine`] is the original single-threaded event loop (`crate::run`), unchanged, and stays
+//! the default. [`ParallelEngine`] targets large node counts, where a node's own
+//! `ProtocolWrapper::progress` call (its protocol state machine advancing) dominates runtime and is
+//! independent of every other node's: it only reads/writes that one node's own state. So
+//! `ParallelEngine` partitions nodes by spatial grid cell (see [`partition_assignment`]) and gives
+//! each partition a long-lived worker thread; whenever several nodes are due to `progress()` at the
+//! same timestamp, their jobs are dispatched to their owning partition's worker over a
+//! `crossbeam_channel` and joined before the coordinator looks at the event queue again. Collision
+//! and recipient resolution (`forward_message`/`get_recipients`) stay on the coordinator thread
+//! against the single authoritative event queue [`SerialEngine`] also uses: splitting that queue
+//! per partition would need their cross-partition overlap detection duplicated per worker, which
+//! isn't worth risking a silent divergence from the serial engine's already-relied-upon behavior.
+//! Only the dominant per-node cost is parallelized.
+//!
+//! Each node gets its own deterministic RNG substream (see [`derive_node_seed`]), derived from the
+//! `rng` the caller passes in rather than from a shared stream advanced in dispatch order, so two
+//! `ParallelEngine` runs given the same inputs always produce the same result regardless of how the
+//! OS scheduler interleaves the worker threads.
+
+use std::{collections::HashMap, thread};
+
+use crossbeam_channel::unbounded;
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::*;
+
+/// Runs a simulation to completion. Implement this instead of calling `crate::run` directly so
+/// callers (`main`, in particular) can pick an engine by config.
+pub trait SimEngine {
+    #[allow(clippy::too_many_arguments)]
+    fn run(
+        &mut self,
+        nodes: Vec<ProtocolWrapper>,
+        minutes: usize,
+        rng: &mut dyn RngCore,
+        graph: Option<&NetworkGraph>,
+        check_visibility: &mut dyn FnMut(&ProtocolWrapper, &ProtocolWrapper) -> bool,
+        channel_model: &ChannelModel,
+        sim_event_sink: &mut dyn EventSink,
+    ) -> (Vec<Data>, Vec<ProtocolWrapper>);
+}
+
+/// The original single-threaded engine. Default; see module docs for when [`ParallelEngine`] is
+/// worth the extra complexity instead.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SerialEngine;
+
+impl SimEngine for SerialEngine {
+    fn run(
+        &mut self,
+        nodes: Vec<ProtocolWrapper>,
+        minutes: usize,
+        rng: &mut dyn RngCore,
+        graph: Option<&NetworkGraph>,
+        check_visibility: &mut dyn FnMut(&ProtocolWrapper, &ProtocolWrapper) -> bool,
+        channel_model: &ChannelModel,
+        sim_event_sink: &mut dyn EventSink,
+    ) -> (Vec<Data>, Vec<ProtocolWrapper>) {
+        crate::run(nodes, minutes, rng, graph, check_visibility, channel_model, sim_event_sink)
+    }
+}
+
+/// One node's `progress()` call, dispatched to its owning partition's worker. Ownership of `node`
+/// and `rng` travels there and back over the channels so the coordinator's `nodes` vector never has
+/// two threads touching the same element at once, without needing a lock.
+struct ProgressJob {
+    node_id: NodeId,
+    node: ProtocolWrapper,
+    rng: ChaCha8Rng,
+    time: TimeMs,
+    message: Option<Message>,
+}
+
+/// The outcome of a [`ProgressJob`], handed back to the coordinator.
+struct ProgressResult {
+    node_id: NodeId,
+    node: ProtocolWrapper,
+    rng: ChaCha8Rng,
+    action: Action<TimeMs, Message, Channel>,
+    uplink_data: Option<Vec<Data>>,
+    events: Vec<SimEvent>,
+}
+
+/// Buffers the `SimEvent`s a worker's `ProtocolWrapper::progress` call emits, so the coordinator can
+/// replay them into the real sink in a fixed order once every job in a batch has returned (see
+/// [`ParallelEngine::run`]) instead of however the worker threads happened to finish.
+#[derive(Debug, Clone, Default)]
+struct CollectingSink(Vec<SimEvent>);
+
+impl EventSink for CollectingSink {
+    fn emit(&mut self, event: SimEvent) {
+        self.0.push(event);
+    }
+}
+
+/// Derive a node's own deterministic RNG seed from the run's master seed (a splitmix64-style mix,
+/// so adjacent node ids don't end up correlated), so every node gets a reproducible substream
+/// independent of which worker thread happens to process it first.
+fn derive_node_seed(master_seed: u64, node_id: NodeId) -> u64 {
+    let mut z = master_seed ^ (node_id as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// Assign each node (indexed by its `NodeId`) to one of `num_partitions` buckets by spatial grid
+/// cell spanning the nodes' bounding box, so a worker's nodes tend to also be each other's radio
+/// neighbors (the nodes whose deliveries most often involve each other).
+fn partition_assignment(nodes: &[ProtocolWrapper], num_partitions: usize) -> Vec<usize> {
+    if nodes.is_empty() {
+        return Vec::new();
+    }
+
+    let num_partitions = num_partitions.max(1);
+    let cols = (num_partitions as f64).sqrt().ceil().max(1.0) as usize;
+    let rows = num_partitions.div_ceil(cols).max(1);
+
+    let (min_x, max_x, min_y, max_y) = nodes.iter().map(ProtocolWrapper::location).fold(
+        (i64::MAX, i64::MIN, i64::MAX, i64::MIN),
+        |(min_x, max_x, min_y, max_y), c| (min_x.min(c.x), max_x.max(c.x), min_y.min(c.y), max_y.max(c.y)),
+    );
+    let cell_w = ((max_x - min_x).max(1) as f64 / cols as f64).max(1.0);
+    let cell_h = ((max_y - min_y).max(1) as f64 / rows as f64).max(1.0);
+
+    nodes
+        .iter()
+        .map(|node| {
+            let col = (((node.location().x - min_x) as f64 / cell_w) as usize).min(cols - 1);
+            let row = (((node.location().y - min_y) as f64 / cell_h) as usize).min(rows - 1);
+            row * cols + col
+        })
+        .collect()
+}
+
+/// Engine for large node counts: partitions nodes across worker threads by spatial grid cell, see
+/// module docs for the division of labor between the workers and the coordinator.
+pub struct ParallelEngine {
+    /// Requested number of worker threads. The partition grid has `rows * cols` cells, which may be
+    /// slightly more than requested; one worker is spawned per cell up to the highest-numbered cell
+    /// that holds a node (see [`partition_assignment`]).
+    pub num_partitions: usize,
+}
+
+impl SimEngine for ParallelEngine {
+    fn run(
+        &mut self,
+        mut nodes: Vec<ProtocolWrapper>,
+        minutes: usize,
+        rng: &mut dyn RngCore,
+        graph: Option<&NetworkGraph>,
+        check_visibility: &mut dyn FnMut(&ProtocolWrapper, &ProtocolWrapper) -> bool,
+        channel_model: &ChannelModel,
+        sim_event_sink: &mut dyn EventSink,
+    ) -> (Vec<Data>, Vec<ProtocolWrapper>) {
+        if nodes.is_empty() {
+            return (Vec::new(), nodes);
+        }
+
+        let master_seed = rng.next_u64();
+
+        let partition_of = partition_assignment(&nodes, self.num_partitions);
+        let num_partitions = partition_of.iter().copied().max().map_or(1, |m| m + 1);
+
+        let (result_tx, result_rx) = unbounded::<ProgressResult>();
+        let mut job_txs = Vec::with_capacity(num_partitions);
+        let mut worker_handles = Vec::with_capacity(num_partitions);
+        for _ in 0..num_partitions {
+            let (job_tx, job_rx) = unbounded::<ProgressJob>();
+            job_txs.push(job_tx);
+            let result_tx = result_tx.clone();
+            worker_handles.push(thread::spawn(move || {
+                for ProgressJob { node_id, mut node, mut rng, time, message } in job_rx.iter() {
+                    let mut sink = CollectingSink::default();
+                    let (action, uplink_data) = node.progress(time, message, &mut rng, &mut sink);
+                    let result = ProgressResult { node_id, node, rng, action, uplink_data, events: sink.0 };
+                    if result_tx.send(result).is_err() {
+                        break;
+                    }
+                }
+            }));
+        }
+        drop(result_tx);
+
+        let mut rngs: HashMap<NodeId, ChaCha8Rng> = (0..nodes.len() as NodeId)
+            .map(|id| (id, ChaCha8Rng::seed_from_u64(derive_node_seed(master_seed, id))))
+            .collect();
+        let mut coordinator_rng = ChaCha8Rng::seed_from_u64(master_seed);
+
+        let mut event_queue = SortedLinkedList::new();
+        let mut data = Vec::default();
+        let mut time: TimeMs = 0;
+
+        for node in &nodes {
+            let startup_delay = coordinator_rng.next_u32() as TimeMs % STARTUP_DELAY_RANGE_MS;
+            event_queue.push(Event::new(startup_delay, node.id(), None));
+        }
+
+        loop {
+            assert!(
+                event_queue.len() == nodes.len(),
+                "bug: invalid number of elements in event queue: {} (!= {})\n{:#?}",
+                event_queue.len(),
+                nodes.len(),
+                event_queue
+            );
+
+            let event = event_queue.pop().unwrap();
+            assert!(event.time >= time, "bug: time cannot go backwards");
+            time = event.time;
+
+            if let Some(MessageWrapper {
+                kind: MessageKind::Transmit,
+                channel,
+                ref message,
+                sender: _,
+                protocol_version,
+                is_corrupt: _,
+            }) = event.message
+            {
+                // stays serial: needs the single authoritative event queue to detect overlapping
+                // receives, see module docs
+                forward_message(
+                    time,
+                    event.node_id,
+                    channel,
+                    message,
+                    protocol_version,
+                    &mut event_queue,
+                    &nodes,
+                    graph,
+                    &mut *check_visibility,
+                    PACKET_ERROR_RATE_PPT,
+                    channel_model,
+                    &mut coordinator_rng,
+                    sim_event_sink,
+                );
+                event_queue.push(Event::new(time + TIME_ON_AIR, event.node_id, None));
+                continue;
+            }
+
+            // every other event currently due at this exact timestamp only touches its own node's
+            // state, so batch them up and dispatch each to its owning partition's worker
+            let mut batch = vec![event];
+            while let Some(next) = event_queue.peek() {
+                let is_transmit =
+                    matches!(&next.message, Some(MessageWrapper { kind: MessageKind::Transmit, .. }));
+                if next.time != time || is_transmit {
+                    break;
+                }
+                batch.push(event_queue.pop().unwrap());
+            }
+
+            for batch_event in &batch {
+                let node_id = batch_event.node_id;
+                let message = match &batch_event.message {
+                    Some(MessageWrapper {
+                        kind: MessageKind::Receive,
+                        message,
+                        is_corrupt: false,
+                        ..
+                    }) => Some(message.clone()),
+                    _ => None,
+                };
+                // swap the real node out for a throwaway placeholder while it's checked out to a
+                // worker, so `nodes` stays the contiguous `Vec<ProtocolWrapper>` `forward_message`
+                // expects; safe because forward_message only ever runs once every job in the
+                // current batch has returned (see the `continue` above)
+                let placeholder =
+                    ProtocolWrapper::new(ProtocolImpl::new(node_id), Coordinates::default(), PROTOCOL_VERSION);
+                let node = std::mem::replace(&mut nodes[node_id as usize], placeholder);
+                let rng = rngs.remove(&node_id).expect("every node has a seeded rng");
+                job_txs[partition_of[node_id as usize]]
+                    .send(ProgressJob { node_id, node, rng, time, message })
+                    .expect("worker thread panicked");
+            }
+
+            let mut results = Vec::with_capacity(batch.len());
+            for _ in 0..batch.len() {
+                results.push(result_rx.recv().expect("worker thread panicked"));
+            }
+            // replay in a fixed order regardless of which worker happened to finish first, so the
+            // run is reproducible independent of OS scheduling
+            results.sort_by_key(|result| result.node_id);
+
+            for ProgressResult { node_id, node, rng, action, uplink_data, events } in results {
+                nodes[node_id as usize] = node;
+                rngs.insert(node_id, rng);
+                for sim_event in events {
+                    sim_event_sink.emit(sim_event);
+                }
+
+                match action {
+                    Action::Wait { end } | Action::Receive { end, .. } => {
+                        if end < time {
+                            panic!("end of action is in the past ({} < {})", end, time);
+                        }
+                    }
+                    Action::Transmit { .. } | Action::None => {}
+                }
+
+                if let Some(uplink_data) = uplink_data {
+                    data.extend(uplink_data);
+                }
+
+                match action {
+                    Action::Wait { end } | Action::Receive { end, .. } => {
+                        event_queue.push(Event::new(end, node_id, None));
+                    }
+                    Action::Transmit { channel, message, delay } => {
+                        event_queue.push(Event::new(
+                            time + delay.unwrap_or(0),
+                            node_id,
+                            Some(MessageWrapper::new(
+                                MessageKind::Transmit,
+                                message,
+                                channel,
+                                node_id,
+                                nodes[node_id as usize].protocol_version(),
+                            )),
+                        ));
+                    }
+                    Action::None => {
+                        event_queue.push(Event::new(time, node_id, None));
+                    }
+                }
+            }
+
+            if minutes <= (time / (1000 * 60)) as usize {
+                break;
+            }
+        }
+
+        drop(job_txs);
+        for handle in worker_handles {
+            handle.join().expect("worker thread panicked");
+        }
+
+        (data, nodes)
+    }
+}
@@ -0,0 +1,99 @@
+//  _____       ______   ____
+// |_   _|     |  ____|/ ____|  Institute of Embedded Systems
+//   | |  _ __ | |__  | (___    Zurich University of Applied Sciences
+//   | | | '_ \|  __|  \___ \   8401 Winterthur, Switzerland
+//  _| |_| | | | |____ ____) |
+// |_____|_| |_|______|_____/
+//
+// Copyright 2025 Institute of Embedded Systems at Zurich University of Applied Sciences.
+// All rights reserved.
+// SPDX-License-Identifier: MIT
+
+//! Structured simulator-level events, decoupled from any particular output backend.
+//!
+//! `forward_message` and `ProtocolWrapper::progress` used to report what they did purely as
+//! colored `info!`/`warn!` text, which meant a replayer or analyzer had no way to know what
+//! happened short of re-parsing log lines. Instead they now emit a single typed [`SimEvent`] to
+//! whatever [`EventSink`] the caller passes in, mirroring the `lightning` crate's own
+//! `event_sink` module one layer up: [`TextEventSink`] is the default and reproduces the
+//! previous console text and is `no_std`-compatible like the rest of this crate (`log`, like
+//! `EventSink` itself, doesn't need an allocator or an OS). The `simulator` binary crate's
+//! `io::BinaryEventSink` writes the same events as length-prefixed `postcard` frames instead, for
+//! a replayer/analyzer to load without string-parsing; that one does need a filesystem, so it
+//! lives there rather than here.
+
+use alloc::vec::Vec;
+
+#[allow(unused_imports)]
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use crate::*;
+
+/// Message-specific fields of a [`SimEvent`], set when the event concerns an actual frame rather
+/// than a node merely waiting.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SimEventMessage {
+    pub kind: MessageKind,
+    pub channel: Channel,
+    /// Whether the frame collided with another in flight, see `channel::ChannelModel::resolve`.
+    /// Always `false` here: corruption is only discovered once a later, overlapping
+    /// `forward_message` call resolves against this frame (see `MessageWrapper::is_corrupt`).
+    pub is_corrupt: bool,
+}
+
+/// One structured simulator event, emitted via `EventSink::emit` by `ProtocolWrapper::progress`
+/// (a `Wait`/`Receive`/`Transmit`/`None` decision) or by `forward_message` (a frame delivered to
+/// one recipient). Mirrors `sim::Event`'s own `Option<MessageWrapper>` shape: `message` is `None`
+/// for a plain `Wait`, which isn't about any frame.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SimEvent {
+    pub time: TimeMs,
+    pub node_id: NodeId,
+    pub location: Coordinates,
+    pub message: Option<SimEventMessage>,
+    /// Uplink application data this node (a sink) just collected, if any.
+    pub uplink_data: Vec<Data>,
+}
+
+/// Receives structured simulator events. Implement this instead of scraping `TextEventSink`'s
+/// console output to observe exactly what a run did.
+pub trait EventSink {
+    fn emit(&mut self, event: SimEvent);
+
+    /// Flush any buffered output. No-op by default; `io::BinaryEventSink` overrides this to flush
+    /// its file.
+    fn flush(&mut self) {}
+}
+
+/// Default `EventSink`, reproducing the colored console text this crate has always printed for
+/// per-node progress and message forwarding.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TextEventSink;
+
+impl EventSink for TextEventSink {
+    fn emit(&mut self, event: SimEvent) {
+        match event.message {
+            None => {}
+            Some(SimEventMessage {
+                kind: MessageKind::Transmit,
+                channel,
+                ..
+            }) => {
+                info!("node {:x} transmitting on channel {}", event.node_id, channel);
+            }
+            Some(SimEventMessage {
+                kind: MessageKind::Receive,
+                channel,
+                is_corrupt,
+            }) => {
+                info!(
+                    "node {:x} receiving on channel {}{}",
+                    event.node_id,
+                    channel,
+                    if is_corrupt { " (corrupt)" } else { "" }
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,80 @@
+//  _____       ______   ____
+// |_   _|     |  ____|/ ____|  Institute of Embedded Systems
+//   | |  _ __ | |__  | (___    Zurich University of Applied Sciences
+//   | | | '_ \|  __|  \___ \   8401 Winterthur, Switzerland
+//  _| |_| | | | |____ ____) |
+// |_____|_| |_|______|_____/
+//
+// Copyright 2025 Institute of Embedded Systems at Zurich University of Applied Sciences.
+// All rights reserved.
+// SPDX-License-Identifier: MIT
+
+//! File-writing metadata/event I/O. Not part of the `simulator` library crate: writing to a
+//! filesystem is something the host binary does on the simulated network's behalf, not something
+//! `simulator::ProtocolWrapper` needs to run itself, so none of this needs to compile under
+//! `no_std`.
+
+use std::{
+    fs::File,
+    io::{self, Write},
+};
+
+use crate::*;
+
+pub fn write_metadata_to_file(
+    nodes: &[ProtocolWrapper],
+    node_range: u32,
+    file_path: &str,
+) -> io::Result<()> {
+    let mut node_loc_file = File::create(file_path)?;
+    node_loc_file.write_all(format!("{{\n\"node_range\":{node_range},\n").as_bytes())?;
+    node_loc_file.write_all("\"nodes\":\n[\n".as_bytes())?;
+    let mut node_iter = nodes.iter();
+    let mut next = node_iter.next();
+    while let Some(node) = next {
+        node_loc_file.write_all(
+            format!(
+                "{{\"id\":{},\"location\":{{\"x\":{},\"y\":{}}}}}",
+                node.id(),
+                node.location().x,
+                node.location().y
+            )
+            .as_bytes(),
+        )?;
+        next = node_iter.next();
+        if next.is_some() {
+            node_loc_file.write_all(",".as_bytes())?;
+        }
+        node_loc_file.write_all("\n".as_bytes())?;
+    }
+    node_loc_file.write_all("]\n}\n".as_bytes())?;
+    Ok(())
+}
+
+/// Writes each event as a length-prefixed `postcard` frame: a little-endian `u32` byte length
+/// followed by the `postcard`-encoded `SimEvent`, the same framing
+/// `protocol_event_writer::file::PostcardEncoder` uses for the protocol's own event log.
+pub struct BinaryEventSink {
+    file: File,
+}
+
+impl BinaryEventSink {
+    pub fn new(output_file_path: &str) -> Self {
+        let file = File::create(output_file_path).expect("could not create event file");
+        Self { file }
+    }
+}
+
+impl EventSink for BinaryEventSink {
+    fn emit(&mut self, event: SimEvent) {
+        let bytes = postcard::to_allocvec(&event).expect("SimEvent is always serializable");
+        self.file
+            .write_all(&(bytes.len() as u32).to_le_bytes())
+            .unwrap();
+        self.file.write_all(&bytes).unwrap();
+    }
+
+    fn flush(&mut self) {
+        self.file.flush().unwrap();
+    }
+}
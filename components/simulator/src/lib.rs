@@ -0,0 +1,72 @@
+//  _____       ______   ____
+// |_   _|     |  ____|/ ____|  Institute of Embedded Systems
+//   | |  _ __ | |__  | (___    Zurich University of Applied Sciences
+//   | | | '_ \|  __|  \___ \   8401 Winterthur, Switzerland
+//  _| |_| | | | |____ ____) |
+// |_____|_| |_|______|_____/
+//
+// Copyright 2025 Institute of Embedded Systems at Zurich University of Applied Sciences.
+// All rights reserved.
+// SPDX-License-Identifier: MIT
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+//! The portable core of the simulator: [`ProtocolWrapper`] plus the handful of plain data types
+//! (`Coordinates`, `Event`, `MessageWrapper`, the [`event_sink`] schema) it's built from. Like
+//! `protocol_api`/`lightning` one layer down, this crate compiles under `no_std` with only `alloc`
+//! (following the approach rs-matter took to become `no_std`-compatible), so the exact wrapper
+//! that was driven by the simulator's event loop can also run on real hardware for
+//! hardware-in-the-loop testing: the protocol state machine doesn't need to know whether its
+//! `Action`s are being carried out by the simulator's radio model or a real one.
+//!
+//! Enable the `std` feature for [`channel`]/[`network_graph`]/collision resolution
+//! (`forward_message`/`get_recipients`), which only make sense against a simulated network and
+//! need real float transcendentals (`f32::log10`/`powf`) and `std::collections::HashMap` that
+//! `core` doesn't provide. The multithreaded engine, file-based metadata/event I/O, and colored
+//! console logging go further still (threads, a filesystem) and stay in the `simulator` binary
+//! crate instead of being feature-gated here.
+
+extern crate alloc;
+
+use protocol_api::{Action, Branch, Protocol};
+
+// TODO: use feature flags to switch between different protocol implementations
+//
+// Not `pub`: only the `Protocol` associated types below are part of this crate's public surface;
+// callers that need to construct a `ProtocolImpl` to hand to `ProtocolWrapper::new` go through
+// `lightning::Lightning` directly, same as this crate does.
+use lightning::Lightning as ProtocolImpl;
+
+pub type Channel = <ProtocolImpl as Protocol>::Channel;
+pub type Data = <ProtocolImpl as Protocol>::Data;
+pub type Message = <ProtocolImpl as Protocol>::Message;
+pub type NodeId = <ProtocolImpl as Protocol>::NodeId;
+pub type Payload = <ProtocolImpl as Protocol>::Payload;
+pub type TimeMs = <ProtocolImpl as Protocol>::TimeMs;
+
+/// Approximate time a message spends in the air.
+/// In the LoRa test network (SF8, BW 125KHz, 12 symbols preamble, 4/6 coding rate) a 10 byte payload has a time-on-air of 100 ms.
+pub const TIME_ON_AIR: TimeMs = 80;
+
+/// Default protocol/firmware revision a newly constructed node runs, see
+/// [`ProtocolWrapper::protocol_version`]/[`MessageWrapper::protocol_version`]. A node tagged with a
+/// different revision (e.g. during a staged rollout) still physically interferes with other
+/// transmissions on a shared channel, but can't decode a frame carrying a revision other than its
+/// own.
+pub const PROTOCOL_VERSION: u8 = 1;
+
+mod event_sink;
+mod sim;
+
+pub use crate::event_sink::*;
+pub use crate::sim::*;
+
+#[cfg(feature = "std")]
+mod channel;
+#[cfg(feature = "std")]
+mod network_graph;
+#[cfg(feature = "std")]
+mod routing;
+
+#[cfg(feature = "std")]
+pub use crate::{channel::*, network_graph::*, routing::*};
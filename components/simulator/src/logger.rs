@@ -14,7 +14,9 @@
 use log::{Level, Metadata, Record, SetLoggerError};
 use std::{cell::Cell, sync::Mutex};
 
-use protocol_event_writer::{ProtocolEventFileWriter, EVENT_INDICATOR_CHAR};
+use protocol_event_writer::{
+    EventEncoder, EventWriter, ProtocolEventFileWriter, EVENT_INDICATOR_CHAR,
+};
 
 const LOG_COLOR_CODE_DEFAULT: &str = "\x1B[0m";
 const LOG_COLOR_CODE_RED: &str = "\x1B[1;31m";
@@ -71,8 +73,13 @@ impl log::Log for SimLogger {
     }
 }
 
-pub fn init(max_level: Level, output_file_path: Option<&str>) -> Result<(), SetLoggerError> {
-    let event_writer = output_file_path.map(ProtocolEventFileWriter::new);
+pub fn init(
+    max_level: Level,
+    output_file_path: Option<&str>,
+    event_encoder: Box<dyn EventEncoder>,
+) -> Result<(), SetLoggerError> {
+    let event_writer =
+        output_file_path.map(|path| ProtocolEventFileWriter::new(path, event_encoder));
     let logger = Box::new(SimLogger {
         max_level,
         event_writer: event_writer.map(|f| Mutex::new(Cell::new(f))),
@@ -16,36 +16,53 @@ use std::{cmp::max, env, time::Duration};
 
 use sorted_linked_list::SortedLinkedList;
 
-use protocol_api::{Action, Protocol, ProtocolData};
+use protocol_api::{Action, Branch, Protocol, ProtocolData};
+use protocol_event_writer::{CsvEncoder, EventEncoder, NdjsonEncoder, PostcardEncoder};
 
 // TODO: use feature flags to switch between different protocol implementations
 use lightning::Lightning as ProtocolImpl;
 
-type Channel = <ProtocolImpl as Protocol>::Channel;
-type Data = <ProtocolImpl as Protocol>::Data;
-type Message = <ProtocolImpl as Protocol>::Message;
-type NodeId = <ProtocolImpl as Protocol>::NodeId;
-type Payload = <ProtocolImpl as Protocol>::Payload;
-type TimeMs = <ProtocolImpl as Protocol>::TimeMs;
-
+mod engine;
+mod io;
 mod logger;
-mod sim;
 
-use crate::sim::*;
+use crate::engine::*;
+use crate::io::*;
+use simulator::*;
 
 /// Minimum distance between nodes. Avoids overlapping nodes.
 const MIN_NODE_DISTANCE: u32 = 10;
 /// Height and width of area
 const AREA_SIZE: u32 = 100;
-/// Approximate time a message spends in the air.
-/// In the LoRa test network (SF8, BW 125KHz, 12 symbols preamble, 4/6 coding rate) a 10 byte payload has a time-on-air of 100 ms.
-const TIME_ON_AIR: TimeMs = 80;
 const STARTUP_DELAY_RANGE_MS: TimeMs = 5 * 60 * 1000;
 /// Probability of a transmission error in parts per thousand
 const PACKET_ERROR_RATE_PPT: Option<u32> = None;
 
+/// Protocol/firmware revision simulating nodes that haven't migrated to `simulator::PROTOCOL_VERSION`
+/// yet, see `ProtocolWrapper::protocol_version`.
+const LEGACY_PROTOCOL_VERSION: u8 = PROTOCOL_VERSION - 1;
+/// Fraction of newly created nodes (parts per thousand) that run `LEGACY_PROTOCOL_VERSION` instead
+/// of `PROTOCOL_VERSION`, for modeling a gradual firmware rollout across the network; `0` (the
+/// default) keeps every node on the same revision.
+const LEGACY_NODE_FRACTION_PPT: u32 = 0;
+
+/// Transmit power fed into the default log-distance path loss model, in dB (arbitrary reference,
+/// only relative power between overlapping transmissions matters to the capture effect).
+const TX_POWER_DB: f32 = 14.0;
+/// Default channel model parameters, roughly matching a LoRa radio's real-world behavior: ~6dB
+/// capture margin, a preamble on the order of `TIME_ON_AIR`'s symbols, and a deep noise floor
+/// relative to `TX_POWER_DB` so only genuinely weak links are inaudible.
+const DEFAULT_CAPTURE_MARGIN_DB: f32 = 6.0;
+const DEFAULT_PATH_LOSS_EXPONENT: f32 = 2.5;
+const DEFAULT_NOISE_FLOOR_DB: f32 = TX_POWER_DB - 120.0;
+const DEFAULT_PREAMBLE_MS: TimeMs = TIME_ON_AIR / 8;
+
 const EVENT_FILE_PATH: &str = "/tmp/protocol_events.csv";
 const SIMULATION_METADATA_FILE_PATH: &str = "/tmp/protocol_sim_meta.json";
+const SIM_EVENT_FILE_PATH: &str = "/tmp/sim_events.postcard";
+
+/// Default worker count for `--engine parallel`, see `engine::ParallelEngine`.
+const DEFAULT_ENGINE_PARTITIONS: usize = 4;
 
 fn main() {
     let args: Vec<String> = env::args().collect();
@@ -56,6 +73,13 @@ fn main() {
     let mut simulation_minutes: usize = 90;
     // Nodes that are farther apart from each other than this value are not in range of each other
     let mut range: u32 = 30;
+    let mut event_format = "csv".to_string();
+    let mut sim_event_format = "text".to_string();
+    let mut capture_margin_db = DEFAULT_CAPTURE_MARGIN_DB;
+    let mut path_loss_exponent = DEFAULT_PATH_LOSS_EXPONENT;
+    let mut noise_floor_db = DEFAULT_NOISE_FLOOR_DB;
+    let mut engine_name = "serial".to_string();
+    let mut engine_partitions = DEFAULT_ENGINE_PARTITIONS;
 
     for chunk in args[1..].chunks_exact(2) {
         let (arg, val) = (&chunk[0], &chunk[1]);
@@ -75,10 +99,54 @@ fn main() {
             "--time_min" => {
                 simulation_minutes = val.parse().expect("invalid number of simulation minutes");
             }
+            "--event_format" => {
+                event_format = val.clone();
+            }
+            "--sim_event_format" => {
+                sim_event_format = val.clone();
+            }
+            "--capture_margin_db" => {
+                capture_margin_db = val.parse().expect("invalid capture margin");
+            }
+            "--path_loss_exponent" => {
+                path_loss_exponent = val.parse().expect("invalid path loss exponent");
+            }
+            "--noise_floor_db" => {
+                noise_floor_db = val.parse().expect("invalid noise floor");
+            }
+            "--engine" => {
+                engine_name = val.clone();
+            }
+            "--engine_partitions" => {
+                engine_partitions = val.parse().expect("invalid number of engine partitions");
+            }
             _ => panic!("unknown argument: {}", arg),
         }
     }
 
+    let event_encoder: Box<dyn EventEncoder> = match event_format.as_str() {
+        "csv" => Box::new(CsvEncoder),
+        "ndjson" => Box::new(NdjsonEncoder),
+        "postcard" => Box::new(PostcardEncoder),
+        other => panic!("unknown --event_format: {other}"),
+    };
+
+    let mut sim_event_sink: Box<dyn EventSink> = match sim_event_format.as_str() {
+        "text" => Box::new(TextEventSink),
+        "binary" => Box::new(BinaryEventSink::new(SIM_EVENT_FILE_PATH)),
+        other => panic!("unknown --sim_event_format: {other}"),
+    };
+
+    let channel_model = ChannelModel {
+        path_loss: Box::new(LogDistancePathLoss {
+            tx_power_db: TX_POWER_DB,
+            path_loss_exponent,
+        }),
+        capture_margin_db,
+        noise_floor_db,
+        preamble_ms: DEFAULT_PREAMBLE_MS,
+    };
+
     let num_sinks: usize = match num_sinks {
         Some(ns) => ns,
         None => max(1, 33 * num_nodes / 100),
@@ -109,20 +177,41 @@ fn main() {
         // vector index is node id
         let mut protocol: ProtocolImpl = Protocol::new(nodes.len() as NodeId);
         protocol.set_is_sink(sinks_remaining > 0);
+        let protocol_version = if rng.next_u32() % 1000 < LEGACY_NODE_FRACTION_PPT {
+            LEGACY_PROTOCOL_VERSION
+        } else {
+            PROTOCOL_VERSION
+        };
         nodes.push(ProtocolWrapper::new(
             protocol,
             node_coordinates.remove(rng.next_u32() as usize % node_coordinates.len()),
+            protocol_version,
         ));
         sinks_remaining = sinks_remaining.saturating_sub(1);
     }
 
     write_metadata_to_file(&nodes, range, SIMULATION_METADATA_FILE_PATH).unwrap();
 
-    logger::init(log::Level::Trace, Some(EVENT_FILE_PATH)).unwrap();
+    logger::init(log::Level::Trace, Some(EVENT_FILE_PATH), event_encoder).unwrap();
+
+    let graph = NetworkGraph::new(nodes.iter().map(|node| (node.id(), node.location().clone())), range);
 
-    let data = run(nodes, simulation_minutes, rng, |a, b| {
-        check_visibility_based_on_distance(a, b, range)
-    });
+    let mut engine: Box<dyn SimEngine> = match engine_name.as_str() {
+        "serial" => Box::new(SerialEngine),
+        "parallel" => Box::new(ParallelEngine { num_partitions: engine_partitions }),
+        other => panic!("unknown --engine: {other}"),
+    };
+
+    let (data, _nodes) = engine.run(
+        nodes,
+        simulation_minutes,
+        &mut rng,
+        Some(&graph),
+        &mut |a, b| check_visibility_based_on_distance(a, b, range),
+        &channel_model,
+        sim_event_sink.as_mut(),
+    );
+    sim_event_sink.flush();
 
     let mut nodes_that_sent_data: Vec<_> = data.iter().map(|nd| nd.get_source()).collect();
     nodes_that_sent_data.sort_unstable();
@@ -135,12 +224,16 @@ fn get_rng(rng_seed: u64) -> impl RngCore {
     rand_chacha::ChaCha8Rng::seed_from_u64(rng_seed)
 }
 
+#[allow(clippy::too_many_arguments)]
 fn run(
     mut nodes: Vec<ProtocolWrapper>,
     minutes: usize,
     mut rng: impl RngCore,
+    graph: Option<&NetworkGraph>,
     mut check_visibility: impl FnMut(&ProtocolWrapper, &ProtocolWrapper) -> bool,
-) -> Vec<Data> {
+    channel_model: &ChannelModel,
+    sim_event_sink: &mut dyn EventSink,
+) -> (Vec<Data>, Vec<ProtocolWrapper>) {
     // Stores timestamps of the next time a node can make progress
     let mut event_queue = SortedLinkedList::new();
 
@@ -190,6 +283,8 @@ fn run(
             kind: MessageKind::Transmit,
             channel,
             ref message,
+            sender: _,
+            protocol_version,
             is_corrupt: _,
         }) = event.message
         {
@@ -198,11 +293,15 @@ fn run(
                 event.node_id,
                 channel,
                 message,
+                protocol_version,
                 &mut event_queue,
                 &nodes,
+                graph,
                 &mut check_visibility,
                 PACKET_ERROR_RATE_PPT,
+                channel_model,
                 &mut rng,
+                sim_event_sink,
             );
             // sender makes progress after message is sent
             event_queue.push(Event::new(time + TIME_ON_AIR, event.node_id, None));
@@ -214,13 +313,19 @@ fn run(
                 kind: MessageKind::Receive,
                 channel: _,
                 message,
+                sender: _,
+                protocol_version: _,
                 is_corrupt,
             }) if !is_corrupt => Some(message),
             _ => None,
         };
 
-        let (action, uplink_data) =
-            nodes[event.node_id as usize].progress(time, received_message.clone(), &mut rng);
+        let (action, uplink_data) = nodes[event.node_id as usize].progress(
+            time,
+            received_message.clone(),
+            &mut rng,
+            sim_event_sink,
+        );
 
         match action {
             Action::Wait { end } | Action::Receive { end, .. } => {
@@ -237,15 +342,9 @@ fn run(
 
         match action {
             Action::Wait { end } => {
-                info!("waiting for {:?}", Duration::from_millis(end - time));
                 event_queue.push(Event::new(end, event.node_id, None));
             }
-            Action::Receive { end, channel } => {
-                info!(
-                    "receiving for {:?} on channel {}",
-                    Duration::from_millis(end - time),
-                    channel
-                );
+            Action::Receive { end, .. } => {
                 event_queue.push(Event::new(end, event.node_id, None));
             }
             Action::Transmit {
@@ -253,11 +352,16 @@ fn run(
                 message,
                 delay,
             } => {
-                info!("transmitting message on channel {}", channel);
                 event_queue.push(Event::new(
                     time + delay.unwrap_or(0),
                     event.node_id,
-                    Some(MessageWrapper::new(MessageKind::Transmit, message, channel)),
+                    Some(MessageWrapper::new(
+                        MessageKind::Transmit,
+                        message,
+                        channel,
+                        event.node_id,
+                        nodes[event.node_id as usize].protocol_version(),
+                    )),
                 ));
             }
             Action::None => {
@@ -270,7 +374,7 @@ fn run(
         }
     }
 
-    data
+    (data, nodes)
 }
 
 #[cfg(test)]
@@ -307,6 +411,18 @@ mod tests {
         }
     }
 
+    fn default_channel_model() -> ChannelModel {
+        ChannelModel {
+            path_loss: Box::new(LogDistancePathLoss {
+                tx_power_db: TX_POWER_DB,
+                path_loss_exponent: DEFAULT_PATH_LOSS_EXPONENT,
+            }),
+            capture_margin_db: DEFAULT_CAPTURE_MARGIN_DB,
+            noise_floor_db: DEFAULT_NOISE_FLOOR_DB,
+            preamble_ms: DEFAULT_PREAMBLE_MS,
+        }
+    }
+
     fn create_nodes(number_of_nodes: NodeId, sink_nodes: &[NodeId]) -> Vec<ProtocolWrapper> {
         for sn_id in sink_nodes {
             assert!(*sn_id < number_of_nodes, "invalid sink node id");
@@ -315,7 +431,7 @@ mod tests {
             .map(|id| {
                 let mut protocol = ProtocolImpl::new(id);
                 protocol.set_is_sink(sink_nodes.contains(&id));
-                ProtocolWrapper::new(protocol, Coordinates::default())
+                ProtocolWrapper::new(protocol, Coordinates::default(), PROTOCOL_VERSION)
             })
             .collect()
     }
@@ -324,7 +440,15 @@ mod tests {
     fn basic() {
         // logger::init(log::Level::Trace, Some(EVENT_FILE_PATH)).unwrap();
         let nodes = create_nodes(2, &[0]);
-        let data = run(nodes, 60, get_rng(0), |_, _| true);
+        let (data, _nodes) = run(
+            nodes,
+            60,
+            get_rng(0),
+            None,
+            |_, _| true,
+            &default_channel_model(),
+            &mut TextEventSink,
+        );
         assert!(data.iter().any(|d| d.source == 1));
     }
 
@@ -333,9 +457,15 @@ mod tests {
         // logger::init(log::Level::Trace, Some(EVENT_FILE_PATH)).unwrap();
         let nodes = create_nodes(3, &[0]);
         let visibility_map = VisibilitytMap::from_array([(0, 1), (1, 2)]);
-        let data = run(nodes, 60, get_rng(0), |a, b| {
-            visibility_map.get(a.id(), b.id())
-        });
+        let (data, _nodes) = run(
+            nodes,
+            60,
+            get_rng(0),
+            None,
+            |a, b| visibility_map.get(a.id(), b.id()),
+            &default_channel_model(),
+            &mut TextEventSink,
+        );
         for n in 1..=2 {
             assert!(data.iter().any(|d| d.source == n));
         }
@@ -346,12 +476,23 @@ mod tests {
         // logger::init(log::Level::Trace, Some(EVENT_FILE_PATH)).unwrap();
         let nodes = create_nodes(4, &[0]);
         let visibility_map = VisibilitytMap::from_array([(0, 1), (1, 2), (2, 3)]);
-        let data = run(nodes, 60, get_rng(0), |a, b| {
-            visibility_map.get(a.id(), b.id())
-        });
+        let (data, nodes) = run(
+            nodes,
+            60,
+            get_rng(0),
+            None,
+            |a, b| visibility_map.get(a.id(), b.id()),
+            &default_channel_model(),
+            &mut TextEventSink,
+        );
         for n in 1..=3 {
             assert!(data.iter().any(|d| d.source == n));
         }
+        // tree should converge to the chain itself: node n's branch is n-1 hops from the sink
+        for n in 1..=3 {
+            let (branch, _) = nodes[n].branches();
+            assert_eq!(branch.unwrap().length() as usize, n - 1);
+        }
     }
 
     /// One sink with four children, all nodes see each other
@@ -360,7 +501,15 @@ mod tests {
         // logger::init(log::Level::Trace, Some(EVENT_FILE_PATH)).unwrap();
         let num_nodes = 5;
         let nodes = create_nodes(num_nodes, &[0]);
-        let data = run(nodes, 60 * 2, get_rng(0), |_, _| true);
+        let (data, _nodes) = run(
+            nodes,
+            60 * 2,
+            get_rng(0),
+            None,
+            |_, _| true,
+            &default_channel_model(),
+            &mut TextEventSink,
+        );
         for n in 1..=num_nodes - 1 {
             assert!(data.iter().any(|d| d.source == n as u32));
         }
@@ -372,7 +521,15 @@ mod tests {
         // logger::init(log::Level::Trace, Some(EVENT_FILE_PATH)).unwrap();
         let num_nodes = 9;
         let nodes = create_nodes(num_nodes, &[0]);
-        let data = run(nodes, 60 * 4, get_rng(0), |_, _| true);
+        let (data, _nodes) = run(
+            nodes,
+            60 * 4,
+            get_rng(0),
+            None,
+            |_, _| true,
+            &default_channel_model(),
+            &mut TextEventSink,
+        );
         for n in 1..=num_nodes - 1 {
             assert!(data.iter().any(|d| d.source == n as u32));
         }
@@ -385,7 +542,7 @@ mod tests {
     //     let visibility_map = VisibilitytMap::from_array([(0, 1), (1, 2), (1, 3), (2, 4), (3, 5)]);
     //     let data = run(nodes, 15_000, get_rng(1), |a, b| {
     //         visibility_map.get(a.id(), b.id())
-    //     });
+    //     }, &default_channel_model());
     //     for n in 1..=5 {
     //         assert!(data.iter().any(|d| d.source == n));
     //     }
@@ -396,11 +553,47 @@ mod tests {
         // logger::init(log::Level::Trace, Some(EVENT_FILE_PATH)).unwrap();
         let nodes = create_nodes(5, &[0]);
         let visibility_map = VisibilitytMap::from_array([(0, 1), (1, 2), (1, 3), (1, 4)]);
-        let data = run(nodes, 60, get_rng(0), |a, b| {
-            visibility_map.get(a.id(), b.id())
-        });
+        let (data, nodes) = run(
+            nodes,
+            60,
+            get_rng(0),
+            None,
+            |a, b| visibility_map.get(a.id(), b.id()),
+            &default_channel_model(),
+            &mut TextEventSink,
+        );
         for n in 1..=4 {
             assert!(data.iter().any(|d| d.source == n));
         }
+        // node 1 connects directly to the sink, nodes 2-4 only see node 1
+        let (branch, _) = nodes[1].branches();
+        assert_eq!(branch.unwrap().length(), 0);
+        for n in 2..=4 {
+            let (branch, _) = nodes[n].branches();
+            assert_eq!(branch.unwrap().length(), 1);
+        }
+    }
+
+    /// Same two-node setup as `basic`, but the child runs `LEGACY_PROTOCOL_VERSION`: it hears the
+    /// sink's beacons (they still occupy the channel) but can never decode them, so it never
+    /// connects.
+    #[test]
+    fn incompatible_protocol_version_never_connects() {
+        let mut sink = ProtocolImpl::new(0);
+        sink.set_is_sink(true);
+        let nodes = vec![
+            ProtocolWrapper::new(sink, Coordinates::default(), PROTOCOL_VERSION),
+            ProtocolWrapper::new(ProtocolImpl::new(1), Coordinates::default(), LEGACY_PROTOCOL_VERSION),
+        ];
+        let (data, _nodes) = run(
+            nodes,
+            60,
+            get_rng(0),
+            None,
+            |_, _| true,
+            &default_channel_model(),
+            &mut TextEventSink,
+        );
+        assert!(!data.iter().any(|d| d.source == 1));
     }
 }
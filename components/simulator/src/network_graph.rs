@@ -0,0 +1,152 @@
+//  _____       ______   ____
+// |_   _|     |  ____|/ ____|  Institute of Embedded Systems
+//   | |  _ __ | |__  | (___    Zurich University of Applied Sciences
+//   | | | '_ \|  __|  \___ \   8401 Winterthur, Switzerland
+//  _| |_| | | | |____ ____) |
+// |_____|_| |_|______|_____/
+//
+// Copyright 2025 Institute of Embedded Systems at Zurich University of Applied Sciences.
+// All rights reserved.
+// SPDX-License-Identifier: MIT
+
+//! Spatial index over node locations, so `get_recipients` doesn't have to scan every node in the
+//! network to find the handful actually within radio range of a sender.
+//!
+//! Nodes are bucketed into a grid of cells with side length `range`, so a node's neighbors are
+//! found by checking only the 3x3 block of cells around its own cell (anything farther away in
+//! either axis is necessarily farther than `range`) instead of every node in the network.
+//! Mirrors how rust-lightning's `NetworkGraph` keeps an adjacency store instead of re-deriving
+//! routing info from scratch on every query.
+
+use std::collections::HashMap;
+
+use crate::*;
+
+type Cell = (i64, i64);
+
+/// Incrementally maintained `node -> nodes within range` adjacency store, built from a spatial
+/// hash grid over `Coordinates`.
+#[derive(Debug, Default)]
+pub struct NetworkGraph {
+    range: u32,
+    locations: HashMap<NodeId, Coordinates>,
+    cells: HashMap<Cell, Vec<NodeId>>,
+    adjacency: HashMap<NodeId, Vec<NodeId>>,
+}
+
+impl NetworkGraph {
+    /// Build a graph over `nodes`' initial locations, so any two nodes closer than `range` are
+    /// adjacent.
+    pub fn new(nodes: impl IntoIterator<Item = (NodeId, Coordinates)>, range: u32) -> Self {
+        let mut graph = Self { range, ..Self::default() };
+        for (node, coords) in nodes {
+            graph.insert(node, coords);
+        }
+        graph
+    }
+
+    /// This node's current neighbors (nodes within `range`), if tracked.
+    pub fn neighbors(&self, node: NodeId) -> &[NodeId] {
+        self.adjacency.get(&node).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// Re-bucket `node` at its new `coords`, patching only the adjacencies that changed instead of
+    /// rebuilding the whole graph.
+    pub fn update_location(&mut self, node: NodeId, coords: Coordinates) {
+        self.remove(node);
+        self.insert(node, coords);
+    }
+
+    fn cell_of(&self, coords: &Coordinates) -> Cell {
+        let side = self.range.max(1) as i64;
+        (coords.x.div_euclid(side), coords.y.div_euclid(side))
+    }
+
+    fn remove(&mut self, node: NodeId) {
+        let Some(coords) = self.locations.remove(&node) else {
+            return;
+        };
+        let cell = self.cell_of(&coords);
+        if let Some(bucket) = self.cells.get_mut(&cell) {
+            bucket.retain(|&n| n != node);
+        }
+        for neighbor in self.adjacency.remove(&node).unwrap_or_default() {
+            if let Some(neighbor_adjacency) = self.adjacency.get_mut(&neighbor) {
+                neighbor_adjacency.retain(|&n| n != node);
+            }
+        }
+    }
+
+    fn insert(&mut self, node: NodeId, coords: Coordinates) {
+        let cell = self.cell_of(&coords);
+
+        let mut neighbors = Vec::new();
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                let Some(bucket) = self.cells.get(&(cell.0 + dx, cell.1 + dy)) else {
+                    continue;
+                };
+                for &other in bucket {
+                    if get_distance(&coords, &self.locations[&other]) < self.range as f32 {
+                        neighbors.push(other);
+                        self.adjacency.entry(other).or_default().push(node);
+                    }
+                }
+            }
+        }
+
+        self.cells.entry(cell).or_default().push(node);
+        self.adjacency.insert(node, neighbors);
+        self.locations.insert(node, coords);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn neighbors_within_range_are_adjacent() {
+        let graph = NetworkGraph::new(
+            [
+                (0, Coordinates { x: 0, y: 0 }),
+                (1, Coordinates { x: 5, y: 0 }),
+                (2, Coordinates { x: 100, y: 100 }),
+            ],
+            30,
+        );
+        assert_eq!(graph.neighbors(0), &[1]);
+        assert_eq!(graph.neighbors(1), &[0]);
+        assert_eq!(graph.neighbors(2), &[]);
+    }
+
+    #[test]
+    fn neighbors_can_be_in_an_adjacent_cell() {
+        // cell side length is 30, so 28 and 32 fall in neighboring cells despite being close
+        let graph = NetworkGraph::new(
+            [(0, Coordinates { x: 28, y: 0 }), (1, Coordinates { x: 32, y: 0 })],
+            30,
+        );
+        assert_eq!(graph.neighbors(0), &[1]);
+        assert_eq!(graph.neighbors(1), &[0]);
+    }
+
+    #[test]
+    fn update_location_patches_only_affected_adjacencies() {
+        let mut graph = NetworkGraph::new(
+            [
+                (0, Coordinates { x: 0, y: 0 }),
+                (1, Coordinates { x: 5, y: 0 }),
+                (2, Coordinates { x: 200, y: 200 }),
+            ],
+            30,
+        );
+        assert_eq!(graph.neighbors(0), &[1]);
+
+        graph.update_location(0, Coordinates { x: 200, y: 205 });
+
+        assert_eq!(graph.neighbors(0), &[2]);
+        assert_eq!(graph.neighbors(1), &[] as &[NodeId]);
+        assert_eq!(graph.neighbors(2), &[0]);
+    }
+}
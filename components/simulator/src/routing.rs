@@ -0,0 +1,214 @@
+//  _____       ______   ____
+// |_   _|     |  ____|/ ____|  Institute of Embedded Systems
+//   | |  _ __ | |__  | (___    Zurich University of Applied Sciences
+//   | | | '_ \|  __|  \___ \   8401 Winterthur, Switzerland
+//  _| |_| | | | |____ ____) |
+// |_____|_| |_|______|_____/
+//
+// Copyright 2025 Institute of Embedded Systems at Zurich University of Applied Sciences.
+// All rights reserved.
+// SPDX-License-Identifier: MIT
+
+//! Delivering a transmitted frame to the nodes around the sender. Unlike `ProtocolWrapper` (the
+//! `no_std` core a real node could run as-is), this only makes sense against a simulated network
+//! with every node's location and a `ChannelModel` to resolve collisions between them, so it's
+//! gated behind this crate's `std` feature instead of being part of the always-available `no_std`
+//! core.
+
+use rand::RngCore;
+
+#[allow(unused_imports)]
+use log::warn;
+
+use crate::*;
+
+/// Get nodes that are listening on the specified channel and that are in range of the sender.
+/// `graph`, if given, narrows the scan to `graph.neighbors(sender.id())` instead of every node in
+/// `nodes` (see `NetworkGraph`); `check_visibility` is still applied afterward, so a `graph` that
+/// doesn't match it exactly (e.g. a test's custom visibility map) only costs candidates that
+/// `check_visibility` would have rejected anyway, not correctness.
+///
+/// Deliberately version-agnostic: a candidate here may run a `MessageWrapper::protocol_version`
+/// incompatible with the sender's, but its radio still needs to be modeled as hearing (and being
+/// unable to decode) the frame, so `forward_message`'s final delivery step is what checks
+/// compatibility, not this one.
+pub fn get_recipients(
+    sender: &ProtocolWrapper,
+    channel: Channel,
+    nodes: &[ProtocolWrapper],
+    graph: Option<&NetworkGraph>,
+    mut check_visibility: impl FnMut(&ProtocolWrapper, &ProtocolWrapper) -> bool,
+) -> Vec<NodeId> {
+    let candidates: Vec<NodeId> = match graph {
+        Some(graph) => graph.neighbors(sender.id()).to_vec(),
+        None => (0..nodes.len() as NodeId).collect(),
+    };
+    candidates
+        .into_iter()
+        .filter(|&id| {
+            let node = &nodes[id as usize];
+            id != sender.id() && node.receiving_channel() == Some(channel) && check_visibility(sender, node)
+        })
+        .collect()
+}
+
+/// Forward messages to nodes that are visible to the sender. `sender_protocol_version` (the
+/// transmitting frame's `MessageWrapper::protocol_version`) gates final decoding: a recipient
+/// running a different revision is still delivered a receive event (so the event queue invariant
+/// of one entry per node holds), but with no message to decode, same as a plain timeout.
+#[allow(clippy::too_many_arguments)]
+pub fn forward_message(
+    departure_time: TimeMs,
+    sender_id: NodeId,
+    sender_channel: Channel,
+    message: &Message,
+    sender_protocol_version: u8,
+    event_queue: &mut SortedLinkedList<Event>,
+    nodes: &[ProtocolWrapper],
+    graph: Option<&NetworkGraph>,
+    mut check_visibility: impl FnMut(&ProtocolWrapper, &ProtocolWrapper) -> bool,
+    packet_error_rate_ppt: Option<u32>,
+    channel_model: &ChannelModel,
+    mut rng: impl RngCore,
+    sink: &mut dyn EventSink,
+) {
+    let mut recipients = get_recipients(
+        &nodes[sender_id as usize],
+        sender_channel,
+        nodes,
+        graph,
+        &mut check_visibility,
+    );
+
+    // check each recipient against every other transmission it's also currently due to receive,
+    // overlapping this one in time on the same channel, and resolve the capture effect between
+    // them (see `channel::ChannelModel::resolve`)
+    for event in event_queue.iter_mut() {
+        if departure_time >= event.time || departure_time + TIME_ON_AIR <= event.time - TIME_ON_AIR
+        {
+            // events don't overlap
+            // events are sorted by time so all remaining events don't overlap as well
+            break;
+        }
+
+        let recipient = event.node_id;
+        if !recipients.contains(&recipient) {
+            continue;
+        }
+
+        let Some(wrapper) = event.message.as_ref() else {
+            continue;
+        };
+        if wrapper.kind != MessageKind::Receive || wrapper.channel != sender_channel {
+            continue;
+        }
+        let other_sender = wrapper.sender;
+        let other_start = event.time - TIME_ON_AIR;
+
+        let recipient_location = nodes[recipient as usize].location();
+        let new_power = channel_model.path_loss.received_power_db(get_distance(
+            nodes[sender_id as usize].location(),
+            recipient_location,
+        ));
+        let other_power = channel_model.path_loss.received_power_db(get_distance(
+            nodes[other_sender as usize].location(),
+            recipient_location,
+        ));
+
+        let winner = channel_model.resolve(&[
+            Candidate {
+                sender: sender_id,
+                start: departure_time,
+                received_power_db: new_power,
+            },
+            Candidate {
+                sender: other_sender,
+                start: other_start,
+                received_power_db: other_power,
+            },
+        ]);
+
+        match winner {
+            Some(winner) if winner == sender_id => {
+                warn!(
+                    "capture effect at node {:x}: frame from {:x} captures over frame from {:x}",
+                    recipient, sender_id, other_sender
+                );
+                event.message.as_mut().unwrap().is_corrupt = true;
+            }
+            Some(_) => {
+                warn!(
+                    "capture effect at node {:x}: frame from {:x} captures over frame from {:x}",
+                    recipient, other_sender, sender_id
+                );
+                recipients.retain(|r| *r != recipient);
+            }
+            None => {
+                warn!(
+                    "message collision at node {:x} between frames from {:x} and {:x}",
+                    recipient, sender_id, other_sender
+                );
+                event.message.as_mut().unwrap().is_corrupt = true;
+                recipients.retain(|r| *r != recipient);
+            }
+        }
+    }
+
+    if recipients.is_empty() {
+        return;
+    }
+
+    // drop messages based on packet error rate
+    if let Some(per) = packet_error_rate_ppt {
+        recipients.retain(|_| {
+            if rng.next_u32() % 1000 < per {
+                warn!("packet error simulation: dropping message");
+                false
+            } else {
+                true
+            }
+        });
+    }
+
+    // cancel receive time out events of recipients
+    event_queue.retain(|e| !recipients.contains(&e.node_id));
+
+    for recipient in recipients {
+        let recipient_node = &nodes[recipient as usize];
+        let version_compatible = recipient_node.protocol_version() == sender_protocol_version;
+        if !version_compatible {
+            warn!(
+                "node {:x} can't decode frame from {:x}: protocol version {} != {}",
+                recipient,
+                sender_id,
+                sender_protocol_version,
+                recipient_node.protocol_version()
+            );
+        }
+
+        sink.emit(SimEvent {
+            time: departure_time + TIME_ON_AIR,
+            node_id: recipient,
+            location: recipient_node.location().clone(),
+            message: Some(SimEventMessage {
+                kind: MessageKind::Receive,
+                channel: sender_channel,
+                is_corrupt: false,
+            }),
+            uplink_data: Vec::new(),
+        });
+        event_queue.push(Event::new(
+            departure_time + TIME_ON_AIR,
+            recipient,
+            version_compatible.then(|| {
+                MessageWrapper::new(
+                    MessageKind::Receive,
+                    message.clone(),
+                    sender_channel,
+                    sender_id,
+                    sender_protocol_version,
+                )
+            }),
+        ));
+    }
+}
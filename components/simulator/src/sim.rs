@@ -9,14 +9,11 @@
 // All rights reserved.
 // SPDX-License-Identifier: MIT
 
-use std::{
-    fs::File,
-    io::{self, Write},
-};
+use alloc::vec::Vec;
+use core::cmp::Ordering;
 
-#[allow(unused_imports)]
-use log::{debug, error, info, trace, warn};
 use rand::RngCore;
+use serde::{Deserialize, Serialize};
 
 use crate::*;
 
@@ -26,14 +23,18 @@ pub struct ProtocolWrapper {
     protocol: ProtocolImpl,
     location: Coordinates,
     receiving_channel: Option<Channel>,
+    /// firmware/protocol revision this node runs; `forward_message` only lets it decode frames
+    /// tagged with the same revision, see [`MessageWrapper::protocol_version`]
+    protocol_version: u8,
 }
 
 impl ProtocolWrapper {
-    pub fn new(protocol: ProtocolImpl, location: Coordinates) -> Self {
+    pub fn new(protocol: ProtocolImpl, location: Coordinates, protocol_version: u8) -> Self {
         Self {
             protocol,
             location,
             receiving_channel: None,
+            protocol_version,
         }
     }
 
@@ -45,11 +46,20 @@ impl ProtocolWrapper {
         self.receiving_channel
     }
 
+    pub fn protocol_version(&self) -> u8 {
+        self.protocol_version
+    }
+
     #[doc(alias = "lightning::Lightning::id")]
     pub fn id(&self) -> NodeId {
         self.protocol.id()
     }
 
+    #[doc(alias = "lightning::Lightning::branches")]
+    pub fn branches(&self) -> (Option<Branch<NodeId>>, impl IntoIterator<Item = Branch<NodeId>>) {
+        self.protocol.branches()
+    }
+
     #[must_use]
     #[doc(alias = "lightning::Lightning::progress")]
     pub fn progress(
@@ -57,8 +67,11 @@ impl ProtocolWrapper {
         time: TimeMs,
         message: Option<Message>,
         mut rng: impl RngCore,
+        sink: &mut dyn EventSink,
     ) -> (Action<TimeMs, Message, Channel>, Option<Vec<Data>>) {
-        let (action, uplink_data) = self.protocol.progress(time, message, &mut rng);
+        // TODO: simulator does not model per-link SNR yet, so protocols that adapt to it (e.g.
+        // ADR) never see a signal here and stay at their most robust configuration
+        let (action, uplink_data) = self.protocol.progress(time, message, None, &mut rng);
 
         self.receiving_channel = if let Action::Receive { channel, .. } = action {
             Some(channel)
@@ -77,37 +90,76 @@ impl ProtocolWrapper {
             self.protocol.set_payload(Payload::default());
         }
 
+        sink.emit(SimEvent {
+            time,
+            node_id: self.id(),
+            location: self.location.clone(),
+            message: match &action {
+                Action::Transmit { channel, .. } => Some(SimEventMessage {
+                    kind: MessageKind::Transmit,
+                    channel: *channel,
+                    is_corrupt: false,
+                }),
+                Action::Receive { channel, .. } => Some(SimEventMessage {
+                    kind: MessageKind::Receive,
+                    channel: *channel,
+                    is_corrupt: false,
+                }),
+                Action::Wait { .. } | Action::None => None,
+            },
+            uplink_data: uplink_data.clone().unwrap_or_default(),
+        });
+
         (action, uplink_data)
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum MessageKind {
     Transmit,
     Receive,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct MessageWrapper {
     pub kind: MessageKind,
     pub channel: Channel,
     pub message: Message,
+    /// node this frame was transmitted by, so a later overlapping transmission can look up its
+    /// location for capture-effect resolution (see `channel::ChannelModel::resolve`)
+    pub sender: NodeId,
+    /// protocol/firmware revision this frame was transmitted with. `forward_message` delivers it
+    /// only to recipients running the same revision, dropping it as undecodable for everyone else
+    /// (it still occupies the channel: a decodable frame already queued at such a recipient is
+    /// resolved against it for capture-effect/collision, since a radio can't tell a frame's
+    /// protocol version before demodulating it; once queued as undecodable, it is not resolved
+    /// against later overlapping frames) — this is what lets mixed-version deployments (e.g. a
+    /// staged firmware rollout) coexist in one simulation
+    pub protocol_version: u8,
     /// whether message collided with another
     pub is_corrupt: bool,
 }
 
 impl MessageWrapper {
-    pub fn new(kind: MessageKind, message: Message, channel: Channel) -> Self {
+    pub fn new(
+        kind: MessageKind,
+        message: Message,
+        channel: Channel,
+        sender: NodeId,
+        protocol_version: u8,
+    ) -> Self {
         Self {
             kind,
             message,
             channel,
+            sender,
+            protocol_version,
             is_corrupt: false,
         }
     }
 }
 
-#[derive(Debug, Clone, Eq)]
+#[derive(Debug, Clone, Eq, Serialize, Deserialize)]
 pub struct Event {
     pub time: TimeMs,
     pub node_id: NodeId,
@@ -125,13 +177,13 @@ impl Event {
 }
 
 impl Ord for Event {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+    fn cmp(&self, other: &Self) -> Ordering {
         self.time.cmp(&other.time)
     }
 }
 
 impl PartialOrd for Event {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(self.cmp(other))
     }
 }
@@ -172,149 +224,6 @@ pub fn check_visibility_based_on_distance(
     get_distance(a.location(), b.location()) < range as f32
 }
 
-/// Get nodes that are listening on the specified channel and that are in range of the sender
-pub fn get_recipients(
-    sender: &ProtocolWrapper,
-    channel: Channel,
-    nodes: &[ProtocolWrapper],
-    mut check_visibility: impl FnMut(&ProtocolWrapper, &ProtocolWrapper) -> bool,
-) -> Vec<NodeId> {
-    nodes
-        .iter()
-        .filter(|node| {
-            node.receiving_channel() == Some(channel)
-                && check_visibility(sender, node)
-                && node.id() != sender.id()
-        })
-        .map(|node| node.id())
-        .collect()
-}
-
-/// Forward messages to nodes that are visible to the sender
-#[allow(clippy::too_many_arguments)]
-pub fn forward_message(
-    departure_time: TimeMs,
-    sender_id: NodeId,
-    sender_channel: Channel,
-    message: &Message,
-    event_queue: &mut SortedLinkedList<Event>,
-    nodes: &[ProtocolWrapper],
-    mut check_visibility: impl FnMut(&ProtocolWrapper, &ProtocolWrapper) -> bool,
-    packet_error_rate_ppt: Option<u32>,
-    mut rng: impl RngCore,
-) {
-    let mut recipients = get_recipients(
-        &nodes[sender_id as usize],
-        sender_channel,
-        nodes,
-        &mut check_visibility,
-    );
-
-    // check for collisions with messages on the same channel from nodes that are visible to the
-    // potential recipient
-    for event in event_queue.iter_mut() {
-        if departure_time >= event.time || departure_time + TIME_ON_AIR <= event.time - TIME_ON_AIR
-        {
-            // events don't overlap
-            // events are sorted by time so all remaining events don't overlap as well
-            break;
-        }
-
-        let event_message = match event.message.as_mut() {
-            Some(message) => message,
-            None => continue,
-        };
-
-        let channel = match event_message {
-            MessageWrapper {
-                kind: MessageKind::Receive,
-                channel,
-                ..
-            } => channel,
-            _ => continue,
-        };
-
-        if sender_channel != *channel {
-            continue;
-        }
-
-        recipients.retain(|r| {
-            if check_visibility(&nodes[sender_id as usize], &nodes[*r as usize]) {
-                warn!(
-                    "message collision at node {:x}:\nmessage from node {:x}: {}\nmessage from node {:x}: {}",
-                    *r, sender_id, message, event.node_id, event_message.message
-                );
-                event_message.is_corrupt = true;
-                false
-            } else {
-                true
-            }
-        })
-    }
-
-    if recipients.is_empty() {
-        return;
-    }
-
-    info!(
-        "forwarding message from {:x} to {:x?}",
-        sender_id, recipients
-    );
-
-    // drop messages based on packet error rate
-    if let Some(per) = packet_error_rate_ppt {
-        recipients.retain(|_| {
-            if rng.next_u32() % 1000 < per {
-                warn!("packet error simulation: dropping message");
-                false
-            } else {
-                true
-            }
-        });
-    }
-
-    // cancel receive time out events of recipients
-    event_queue.retain(|e| !recipients.contains(&e.node_id));
-
-    for recipient in recipients {
-        event_queue.push(Event::new(
-            departure_time + TIME_ON_AIR,
-            recipient,
-            Some(MessageWrapper::new(
-                MessageKind::Receive,
-                message.clone(),
-                sender_channel,
-            )),
-        ));
-    }
-}
-
-pub fn write_metadata_to_file(
-    nodes: &[ProtocolWrapper],
-    node_range: u32,
-    file_path: &str,
-) -> io::Result<()> {
-    let mut node_loc_file = File::create(file_path)?;
-    node_loc_file.write_all(format!("{{\n\"node_range\":{node_range},\n").as_bytes())?;
-    node_loc_file.write_all("\"nodes\":\n[\n".as_bytes())?;
-    let mut node_iter = nodes.iter();
-    let mut next = node_iter.next();
-    while let Some(node) = next {
-        node_loc_file.write_all(
-            format!(
-                "{{\"id\":{},\"location\":{{\"x\":{},\"y\":{}}}}}",
-                node.id(),
-                node.location().x,
-                node.location().y
-            )
-            .as_bytes(),
-        )?;
-        next = node_iter.next();
-        if next.is_some() {
-            node_loc_file.write_all(",".as_bytes())?;
-        }
-        node_loc_file.write_all("\n".as_bytes())?;
-    }
-    node_loc_file.write_all("]\n}\n".as_bytes())?;
-    Ok(())
-}
+// `get_recipients`/`forward_message` (collision resolution against `ChannelModel`/`NetworkGraph`)
+// live in this crate's `std`-gated `routing` module instead of here, and `write_metadata_to_file`
+// (file I/O) lives in the `simulator` binary crate's `io` module; see this crate's top-level docs.
@@ -0,0 +1,29 @@
+//  _____       ______   ____
+// |_   _|     |  ____|/ ____|  Institute of Embedded Systems
+//   | |  _ __ | |__  | (___    Zurich University of Applied Sciences
+//   | | | '_ \|  __|  \___ \   8401 Winterthur, Switzerland
+//  _| |_| | | | |____ ____) |
+// |_____|_| |_|______|_____/
+//
+// Copyright 2025 Institute of Embedded Systems at Zurich University of Applied Sciences.
+// All rights reserved.
+// SPDX-License-Identifier: MIT
+
+use crate::EventWriter;
+
+/// Streams event lines out over RTT via `defmt` instead of persisting them to a file, for running
+/// the exact same event pipeline on the target MCU that `ProtocolEventFileWriter` runs in the
+/// simulator. `defmt`'s own framing already gives the host-side decoder replay/ordering for free,
+/// so unlike the file writer this has no format to select and nothing buffered to flush.
+#[derive(Debug, Default)]
+pub struct DefmtEventWriter;
+
+impl EventWriter for DefmtEventWriter {
+    fn write_event(&mut self, event: &str) {
+        defmt::println!("{=str}", event);
+    }
+
+    fn flush(&mut self) {
+        defmt::flush();
+    }
+}
@@ -0,0 +1,129 @@
+//  _____       ______   ____
+// |_   _|     |  ____|/ ____|  Institute of Embedded Systems
+//   | |  _ __ | |__  | (___    Zurich University of Applied Sciences
+//   | | | '_ \|  __|  \___ \   8401 Winterthur, Switzerland
+//  _| |_| | | | |____ ____) |
+// |_____|_| |_|______|_____/
+//
+// Copyright 2025 Institute of Embedded Systems at Zurich University of Applied Sciences.
+// All rights reserved.
+// SPDX-License-Identifier: MIT
+
+use std::{fs::File, io::Write};
+
+use serde::Serialize;
+
+use crate::{EventWriter, EVENT_INDICATOR_CHAR};
+
+const FILE_HEADER: &str = "uptime;node_id;kind;content";
+
+/// One decoded `$uptime;node_id;kind;content` line (see `LogSink` in the `lightning` crate's
+/// `event_sink` module for where that text is produced).
+///
+/// `content` is kept as the already-formatted JSON body rather than re-parsed into a richer type,
+/// since this crate is downstream of both the simulator's `log` backend and the on-device `defmt`
+/// backend and has no dependency on the protocol crate that defines the event schema; giving this
+/// struct a `Serialize` impl is what lets every `EventEncoder` below emit one canonical,
+/// schema-stable representation instead of hand-building a format-specific string.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ProtocolEvent {
+    pub uptime: u64,
+    pub node_id: u32,
+    pub kind: String,
+    pub content: String,
+}
+
+impl ProtocolEvent {
+    /// Parse one `$uptime;node_id;kind;content` line, with or without the leading indicator char.
+    /// Returns `None` for anything that doesn't match that shape (e.g. a log line that merely
+    /// contains a `$` elsewhere).
+    pub fn parse(line: &str) -> Option<Self> {
+        let line = line.strip_prefix(EVENT_INDICATOR_CHAR).unwrap_or(line);
+        let mut parts = line.splitn(4, ';');
+        Some(Self {
+            uptime: parts.next()?.parse().ok()?,
+            node_id: parts.next()?.parse().ok()?,
+            kind: parts.next()?.to_string(),
+            content: parts.next()?.to_string(),
+        })
+    }
+}
+
+/// A selectable on-disk encoding for `ProtocolEventFileWriter`. Named `EventEncoder` rather than
+/// `EventSink` to avoid confusion with the per-node `lightning::EventSink`, which this sits
+/// downstream of: that trait decides *what* gets emitted, this one decides how the emitted text
+/// is persisted to disk.
+pub trait EventEncoder {
+    /// A line to write once before any events, if this format needs one (e.g. CSV's columns).
+    fn header(&self) -> Option<String> {
+        None
+    }
+
+    fn encode(&self, event: &ProtocolEvent, w: &mut dyn Write) -> std::io::Result<()>;
+}
+
+/// One JSON object per line. A human-diffable format for tooling that doesn't care about on-disk
+/// size.
+pub struct NdjsonEncoder;
+
+impl EventEncoder for NdjsonEncoder {
+    fn encode(&self, event: &ProtocolEvent, w: &mut dyn Write) -> std::io::Result<()> {
+        let json = serde_json::to_string(event).expect("ProtocolEvent is always serializable");
+        writeln!(w, "{json}")
+    }
+}
+
+/// The original `uptime;node_id;kind;content` layout, for existing spreadsheet-style parsers.
+pub struct CsvEncoder;
+
+impl EventEncoder for CsvEncoder {
+    fn header(&self) -> Option<String> {
+        Some(FILE_HEADER.to_string())
+    }
+
+    fn encode(&self, event: &ProtocolEvent, w: &mut dyn Write) -> std::io::Result<()> {
+        writeln!(w, "{};{};{};{}", event.uptime, event.node_id, event.kind, event.content)
+    }
+}
+
+/// Length-prefixed `postcard`-encoded frames (the same compact, no_std-friendly binary codec the
+/// firmware itself uses, see `components/firmware/src/main.rs`), for the most compact event file.
+pub struct PostcardEncoder;
+
+impl EventEncoder for PostcardEncoder {
+    fn encode(&self, event: &ProtocolEvent, w: &mut dyn Write) -> std::io::Result<()> {
+        let bytes = postcard::to_allocvec(event).expect("ProtocolEvent is always serializable");
+        w.write_all(&(bytes.len() as u32).to_le_bytes())?;
+        w.write_all(&bytes)
+    }
+}
+
+pub struct ProtocolEventFileWriter {
+    file: File,
+    encoder: Box<dyn EventEncoder>,
+}
+
+impl ProtocolEventFileWriter {
+    pub fn new(output_file_path: &str, encoder: Box<dyn EventEncoder>) -> Self {
+        let mut file = File::create(output_file_path).expect("could not create event file");
+        if let Some(header) = encoder.header() {
+            file.write_all(header.as_bytes()).unwrap();
+            file.write_all(b"\n").unwrap();
+        }
+        Self { file, encoder }
+    }
+}
+
+impl EventWriter for ProtocolEventFileWriter {
+    /// Parse and write one `$uptime;node_id;kind;content` line in `self.encoder`'s format. Lines
+    /// that don't parse are silently dropped.
+    fn write_event(&mut self, event: &str) {
+        if let Some(event) = ProtocolEvent::parse(event) {
+            self.encoder.encode(&event, &mut self.file).unwrap();
+        }
+    }
+
+    fn flush(&mut self) {
+        self.file.flush().unwrap();
+    }
+}
@@ -9,32 +9,34 @@
 // All rights reserved.
 // SPDX-License-Identifier: MIT
 
-use std::{fs::File, io::Write};
+#![cfg_attr(not(feature = "std"), no_std)]
 
 pub const EVENT_INDICATOR_CHAR: char = '$';
 
-const FILE_HEADER: &str = "uptime;node_id;kind;content";
-
-pub struct ProtocolEventFileWriter {
-    file: File,
+/// Persists one already-formatted `$uptime;node_id;kind;content` event line (see `LogSink` in the
+/// `lightning` crate's `event_sink` module for where that text is produced) wherever this backend
+/// sends it: a file on the simulator's host, RTT/defmt on the target MCU, ... Named `EventWriter`
+/// rather than `lightning::EventSink`, which this sits downstream of: that trait decides *what*
+/// gets emitted, this one decides where the emitted text ends up.
+pub trait EventWriter {
+    fn write_event(&mut self, event: &str);
+    fn flush(&mut self);
 }
 
-impl ProtocolEventFileWriter {
-    pub fn new(output_file_path: &str) -> Self {
-        let mut file = File::create(output_file_path).expect("could not create event file");
-        file.write_all(FILE_HEADER.as_bytes()).unwrap();
-        file.write_all(b"\n").unwrap();
-        Self { file }
-    }
+#[cfg(feature = "std")]
+mod file;
+#[cfg(feature = "std")]
+pub use file::{
+    CsvEncoder, EventEncoder, NdjsonEncoder, PostcardEncoder, ProtocolEvent,
+    ProtocolEventFileWriter,
+};
 
-    pub fn write_event(&mut self, event: &str) {
-        // strip indicator char
-        let event = event.split_at(1).1;
-        self.file.write_all(event.as_bytes()).unwrap();
-        self.file.write_all(b"\n").unwrap();
-    }
+#[cfg(feature = "defmt")]
+mod defmt_writer;
+#[cfg(feature = "defmt")]
+pub use defmt_writer::DefmtEventWriter;
 
-    pub fn flush(&mut self) {
-        self.file.flush().unwrap();
-    }
-}
+#[cfg(feature = "std")]
+mod multi;
+#[cfg(feature = "std")]
+pub use multi::MultiEventWriter;
@@ -0,0 +1,31 @@
+//  _____       ______   ____
+// |_   _|     |  ____|/ ____|  Institute of Embedded Systems
+//   | |  _ __ | |__  | (___    Zurich University of Applied Sciences
+//   | | | '_ \|  __|  \___ \   8401 Winterthur, Switzerland
+//  _| |_| | | | |____ ____) |
+// |_____|_| |_|______|_____/
+//
+// Copyright 2025 Institute of Embedded Systems at Zurich University of Applied Sciences.
+// All rights reserved.
+// SPDX-License-Identifier: MIT
+
+use crate::EventWriter;
+
+/// Fans one `write_event`/`flush` call out to every wrapped `EventWriter`, so e.g. the on-disk CSV
+/// writer and an MQTT publisher can both observe the same event stream without every call site
+/// writing to each one separately.
+pub struct MultiEventWriter(pub Vec<Box<dyn EventWriter>>);
+
+impl EventWriter for MultiEventWriter {
+    fn write_event(&mut self, event: &str) {
+        for writer in &mut self.0 {
+            writer.write_event(event);
+        }
+    }
+
+    fn flush(&mut self) {
+        for writer in &mut self.0 {
+            writer.flush();
+        }
+    }
+}
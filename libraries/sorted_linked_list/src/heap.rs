@@ -0,0 +1,315 @@
+//  _____       ______   ____
+// |_   _|     |  ____|/ ____|  Institute of Embedded Systems
+//   | |  _ __ | |__  | (___    Zurich University of Applied Sciences
+//   | | | '_ \|  __|  \___ \   8401 Winterthur, Switzerland
+//  _| |_| | | | |____ ____) |
+// |_____|_| |_|______|_____/
+//
+// Copyright 2025 Institute of Embedded Systems at Zurich University of Applied Sciences.
+// All rights reserved.
+// SPDX-License-Identifier: MIT
+
+//! Min priority queue based on a d-ary heap, default backend of the crate (the `linked-list`
+//! feature switches back to the O(n)-insert list implementation for memory-constrained targets).
+//!
+//! `D` is the branching factor; 4 (the default) keeps each node's children within a couple of
+//! cache lines, which is usually faster than a binary heap despite the extra comparisons per
+//! level. Ties (equal elements) are broken by insertion order via a monotonically increasing
+//! sequence number, so the queue stays deterministic across runs - important for reproducible
+//! simulations.
+use std::fmt::Debug;
+
+pub struct SortedLinkedList<T: Ord, const D: usize = 4> {
+    entries: Vec<Entry<T>>,
+    next_seq: u64,
+}
+
+struct Entry<T> {
+    seq: u64,
+    element: T,
+}
+
+impl<T: Ord> PartialEq for Entry<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == std::cmp::Ordering::Equal
+    }
+}
+
+impl<T: Ord> Eq for Entry<T> {}
+
+impl<T: Ord> PartialOrd for Entry<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: Ord> Ord for Entry<T> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.element
+            .cmp(&other.element)
+            .then_with(|| self.seq.cmp(&other.seq))
+    }
+}
+
+impl<T: Ord, const D: usize> SortedLinkedList<T, D> {
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            next_seq: 0,
+        }
+    }
+
+    pub fn push(&mut self, element: T) {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.entries.push(Entry { seq, element });
+        let last = self.entries.len() - 1;
+        self.sift_up(last);
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        if self.entries.is_empty() {
+            return None;
+        }
+        let last = self.entries.len() - 1;
+        self.entries.swap(0, last);
+        let entry = self.entries.pop().unwrap();
+        if !self.entries.is_empty() {
+            self.sift_down(0);
+        }
+        Some(entry.element)
+    }
+
+    pub fn peek(&mut self) -> Option<&T> {
+        self.entries.first().map(|e| &e.element)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Iterate in ascending order without disturbing the heap's internal layout.
+    pub fn iter(&self) -> Iter<T> {
+        let mut order: Vec<usize> = (0..self.entries.len()).collect();
+        order.sort_by(|&a, &b| self.entries[a].cmp(&self.entries[b]));
+        Iter {
+            entries: &self.entries,
+            order,
+            pos: 0,
+        }
+    }
+
+    /// Warning: modifying an element such that its ordering relative to the other elements
+    /// changes breaks the heap invariant.
+    ///
+    /// Unlike `iter`, this sorts the backing storage in place before handing out references: a
+    /// fully ascending-sorted array is always a valid heap, so this stays correct for subsequent
+    /// `push`/`pop` calls while letting the caller rely on ascending iteration order (e.g. to
+    /// `break` early once two events are far enough apart that none of the remaining ones can
+    /// overlap either).
+    pub fn iter_mut(&mut self) -> IterMut<T> {
+        self.entries.sort();
+        IterMut {
+            inner: self.entries.iter_mut(),
+        }
+    }
+
+    pub fn retain(&mut self, mut f: impl FnMut(&T) -> bool) {
+        self.entries.retain(|e| f(&e.element));
+        self.heapify();
+    }
+
+    pub fn as_vec(&self) -> Vec<&T> {
+        Vec::from_iter(self.iter())
+    }
+
+    fn heapify(&mut self) {
+        if self.entries.len() < 2 {
+            return;
+        }
+        let last_parent = (self.entries.len() - 2) / D;
+        for i in (0..=last_parent).rev() {
+            self.sift_down(i);
+        }
+    }
+
+    fn sift_up(&mut self, mut i: usize) {
+        while i > 0 {
+            let parent = (i - 1) / D;
+            if self.entries[i] < self.entries[parent] {
+                self.entries.swap(i, parent);
+                i = parent;
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn sift_down(&mut self, mut i: usize) {
+        loop {
+            let first_child = i * D + 1;
+            if first_child >= self.entries.len() {
+                break;
+            }
+            let last_child = (first_child + D).min(self.entries.len());
+            let min_child = (first_child..last_child)
+                .min_by(|&a, &b| self.entries[a].cmp(&self.entries[b]))
+                .unwrap();
+
+            if self.entries[min_child] < self.entries[i] {
+                self.entries.swap(i, min_child);
+                i = min_child;
+            } else {
+                break;
+            }
+        }
+    }
+}
+
+impl<T: Ord, const D: usize> Default for SortedLinkedList<T, D> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Ord + Debug, const D: usize> Debug for SortedLinkedList<T, D> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+pub struct Iter<'a, T> {
+    entries: &'a [Entry<T>],
+    order: Vec<usize>,
+    pos: usize,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let index = *self.order.get(self.pos)?;
+        self.pos += 1;
+        Some(&self.entries[index].element)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.order.len() - self.pos;
+        (remaining, Some(remaining))
+    }
+
+    fn count(self) -> usize
+    where
+        Self: Sized,
+    {
+        self.order.len() - self.pos
+    }
+}
+
+pub struct IterMut<'a, T> {
+    inner: std::slice::IterMut<'a, Entry<T>>,
+}
+
+impl<'a, T> Iterator for IterMut<'a, T> {
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|e| &mut e.element)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+
+    fn count(self) -> usize
+    where
+        Self: Sized,
+    {
+        self.inner.count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_works() {
+        let mut l: SortedLinkedList<i32> = SortedLinkedList::new();
+        assert_eq!(l.len(), 0);
+        assert_eq!(l.pop(), None);
+        l.push(1);
+        assert_eq!(l.len(), 1);
+        l.push(2);
+        assert_eq!(l.len(), 2);
+
+        assert_eq!(l.iter().count(), 2);
+        assert_eq!(l.iter().size_hint(), (2, Some(2)));
+        assert_eq!(l.iter_mut().count(), 2);
+        assert_eq!(l.iter_mut().size_hint(), (2, Some(2)));
+
+        assert_eq!(l.pop(), Some(1));
+        assert_eq!(l.len(), 1);
+        assert_eq!(l.pop(), Some(2));
+        assert_eq!(l.len(), 0);
+    }
+
+    #[test]
+    fn is_sorted() {
+        let mut l: SortedLinkedList<i32> = SortedLinkedList::new();
+        l.push(4);
+        l.push(3);
+        l.push(4);
+        l.push(2);
+        l.push(1);
+        l.push(5);
+        l.push(0);
+        l.push(0);
+        assert_eq!(l.as_vec(), [&0, &0, &1, &2, &3, &4, &4, &5]);
+        assert_eq!(l.iter_mut().collect::<Vec<_>>(), [&0, &0, &1, &2, &3, &4, &4, &5]);
+    }
+
+    #[test]
+    fn retain() {
+        let mut l: SortedLinkedList<i32> = SortedLinkedList::new();
+        l.push(3);
+        l.push(1);
+        l.push(4);
+        l.push(2);
+        l.push(1);
+        l.push(0);
+        l.push(5);
+        l.push(0);
+        l.retain(|v| *v > 2);
+        assert_eq!(l.as_vec(), [&3, &4, &5]);
+        l.retain(|v| *v > 10);
+        assert_eq!(l.len(), 0);
+        assert_eq!(l.pop(), None);
+    }
+
+    #[test]
+    fn ties_break_by_insertion_order() {
+        let mut l: SortedLinkedList<(i32, &'static str)> = SortedLinkedList::new();
+        l.push((1, "a"));
+        l.push((1, "b"));
+        l.push((1, "c"));
+        assert_eq!(l.pop(), Some((1, "a")));
+        assert_eq!(l.pop(), Some((1, "b")));
+        assert_eq!(l.pop(), Some((1, "c")));
+    }
+
+    #[test]
+    fn many_elements_pop_in_order() {
+        let mut l: SortedLinkedList<i32> = SortedLinkedList::new();
+        for v in (0..200).rev() {
+            l.push(v);
+        }
+        for v in 0..200 {
+            assert_eq!(l.pop(), Some(v));
+        }
+    }
+}